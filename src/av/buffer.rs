@@ -0,0 +1,84 @@
+use super::*;
+
+av_wrapper!(AVBufferRef, ffmpeg_sys_next::AVBufferRef, av_buffer_unref);
+
+impl AVBufferRef {
+	/// A zeroed buffer of `size` bytes, followed by
+	/// [`INPUT_BUFFER_PADDING`] zeroed bytes
+	pub fn alloc(size: usize) -> Self {
+		let size = size.saturating_add(INPUT_BUFFER_PADDING);
+
+		Self(alloc_with(|| ffi!(av_buffer_allocz, size)))
+	}
+
+	/// Take a new reference to `buffer`
+	///
+	/// # Safety
+	/// `buffer` must be a valid buffer reference
+	pub unsafe fn from_ref(buffer: *const ffmpeg_sys_next::AVBufferRef) -> Self {
+		Self(alloc_with(|| ffi!(av_buffer_ref, buffer)))
+	}
+
+	/// Give up ownership of the reference, returning its pointer
+	pub fn into_raw(self) -> *mut ffmpeg_sys_next::AVBufferRef {
+		let ptr = self.as_mut_ptr();
+
+		forget(self);
+
+		ptr
+	}
+
+	pub fn data(&self) -> Ptr<[u8]> {
+		Ptr::slice_from_raw_parts(self.data.cast_const().into(), self.size)
+	}
+
+	/// The bytes of the buffer, for writing. Only valid
+	/// to write to if the buffer is not shared
+	pub fn data_mut(&mut self) -> MutPtr<[u8]> {
+		MutPtr::slice_from_raw_parts(self.data.into(), self.size)
+	}
+
+	/// Resize the buffer to `size` bytes, followed by [`INPUT_BUFFER_PADDING`]
+	/// bytes. Bytes past the old data are zeroed. The buffer is copied
+	/// if it is shared, or can't be resized in place
+	pub fn realloc(&mut self, size: usize) -> Result<()> {
+		let mut ptr = self.as_mut_ptr();
+		let old_size = self.size;
+
+		ffi!(
+			av_buffer_realloc,
+			&mut ptr,
+			size.saturating_add(INPUT_BUFFER_PADDING)
+		)?;
+
+		/* the old reference is released on success */
+		self.0 = alloc_with(|| ptr);
+
+		/* Safety: the buffer was just reallocated, and is not shared */
+		let bytes = unsafe { self.data_mut().as_mut() };
+
+		#[allow(clippy::arithmetic_side_effects)]
+		bytes[old_size.saturating_sub(INPUT_BUFFER_PADDING).min(size)..].fill(0);
+
+		Ok(())
+	}
+
+	/// Copy the buffer if it is shared, so it can be written to
+	pub fn make_writable(&mut self) -> Result<()> {
+		let mut ptr = self.as_mut_ptr();
+
+		ffi!(av_buffer_make_writable, &mut ptr)?;
+
+		/* the old reference is released if the buffer was copied */
+		self.0 = alloc_with(|| ptr);
+
+		Ok(())
+	}
+}
+
+impl Clone for AVBufferRef {
+	fn clone(&self) -> Self {
+		/* Safety: the buffer is valid */
+		unsafe { Self::from_ref(self.as_ptr()) }
+	}
+}
@@ -1,8 +1,52 @@
 use super::*;
 
+/// Copy a list terminated by `end`
+///
+/// # Safety
+/// `list` must be null or a valid list ending with `end`
+unsafe fn read_list<T: Copy + PartialEq>(list: *const T, end: T) -> Vec<T> {
+	let mut values = Vec::new();
+
+	if list.is_null() {
+		return values;
+	}
+
+	for index in 0.. {
+		/* Safety: guaranteed by caller */
+		let value = unsafe { *list.add(index) };
+
+		if value == end {
+			break;
+		}
+
+		values.push(value);
+	}
+
+	values
+}
+
 pub struct Codecs;
 
 impl Codecs {
+	/// The sample formats `codec` accepts. Empty if unknown
+	pub fn sample_formats(codec: NonNull<AVCodec>) -> Vec<SampleFormat> {
+		#[allow(clippy::multiple_unsafe_ops_per_block)]
+		/* Safety: the codec is valid, and the list ends with AV_SAMPLE_FMT_NONE */
+		let formats = unsafe { read_list(ptr!(codec=>sample_fmts), AVSampleFormat::AV_SAMPLE_FMT_NONE) };
+
+		formats.into_iter().map(Into::into).collect()
+	}
+
+	/// The sample rates `codec` accepts. Empty if any are
+	#[allow(clippy::cast_sign_loss)]
+	pub fn sample_rates(codec: NonNull<AVCodec>) -> Vec<u32> {
+		#[allow(clippy::multiple_unsafe_ops_per_block)]
+		/* Safety: the codec is valid, and the list ends with zero */
+		let rates = unsafe { read_list(ptr!(codec=>supported_samplerates), 0) };
+
+		rates.into_iter().map(|rate| rate as u32).collect()
+	}
+
 	pub fn find_encoder(id: AVCodecID) -> Option<NonNull<AVCodec>> {
 		NonNull::new(ffi!(avcodec_find_encoder, id).into())
 	}
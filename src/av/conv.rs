@@ -7,11 +7,7 @@ impl From<Rational> for AVRational {
 	fn from(value: Rational) -> Self {
 		let Rational { num, den } = value;
 
-		#[allow(clippy::unwrap_used)]
-		Self {
-			num: num.try_into().unwrap(),
-			den: den.try_into().unwrap()
-		}
+		Self { num, den }
 	}
 }
 
@@ -19,11 +15,7 @@ impl From<AVRational> for Rational {
 	fn from(value: AVRational) -> Self {
 		let AVRational { num, den } = value;
 
-		#[allow(clippy::unwrap_used)]
-		Self {
-			num: num.try_into().unwrap(),
-			den: den.try_into().unwrap()
-		}
+		Self { num, den }
 	}
 }
 
@@ -47,6 +39,20 @@ impl From<AVCodecID> for CodecId {
 	}
 }
 
+impl From<CodecId> for AVCodecID {
+	fn from(id: CodecId) -> Self {
+		match id {
+			CodecId::Aac => Self::AV_CODEC_ID_AAC,
+			CodecId::Opus => Self::AV_CODEC_ID_OPUS,
+			CodecId::Flac => Self::AV_CODEC_ID_FLAC,
+			CodecId::Vorbis => Self::AV_CODEC_ID_VORBIS,
+			CodecId::Mp2 => Self::AV_CODEC_ID_MP2,
+			CodecId::Mp3 => Self::AV_CODEC_ID_MP3,
+			CodecId::Unknown => Self::AV_CODEC_ID_NONE
+		}
+	}
+}
+
 pub(super) fn result_from_av(code: i32) -> Result<i32> {
 	if code >= 0 {
 		return Ok(code);
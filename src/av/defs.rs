@@ -49,6 +49,57 @@ define_av_alias_casts! {
 	}
 }
 
+impl SampleFormat {
+	/// Returns `true` if each channel is stored in its own plane
+	#[must_use]
+	pub const fn is_planar(self) -> bool {
+		matches!(
+			self,
+			Self::U8P | Self::I16P | Self::I32P | Self::F32P | Self::F64P | Self::I64P
+		)
+	}
+
+	/// The size of one sample of one channel, in bytes
+	#[must_use]
+	pub const fn bytes_per_sample(self) -> usize {
+		match self {
+			Self::None => 0,
+			Self::U8 | Self::U8P => 1,
+			Self::I16 | Self::I16P => 2,
+			Self::I32 | Self::I32P | Self::F32 | Self::F32P => 4,
+			Self::F64 | Self::F64P | Self::I64 | Self::I64P => 8
+		}
+	}
+
+	/// The packed format with the same sample type
+	#[must_use]
+	pub const fn packed(self) -> Self {
+		match self {
+			Self::U8P => Self::U8,
+			Self::I16P => Self::I16,
+			Self::I32P => Self::I32,
+			Self::F32P => Self::F32,
+			Self::F64P => Self::F64,
+			Self::I64P => Self::I64,
+			format => format
+		}
+	}
+
+	/// The planar format with the same sample type
+	#[must_use]
+	pub const fn planar(self) -> Self {
+		match self {
+			Self::U8 => Self::U8P,
+			Self::I16 => Self::I16P,
+			Self::I32 => Self::I32P,
+			Self::F32 => Self::F32P,
+			Self::F64 => Self::F64P,
+			Self::I64 => Self::I64P,
+			format => format
+		}
+	}
+}
+
 define_av_alias_casts! {
 	#[repr(i32)]
 	pub enum Channel = AVChannel {
@@ -419,13 +470,13 @@ define_av_alias_casts! {
 	}
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct ChannelCustom {
 	pub id: Channel,
 	pub name: [u8; 16]
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChannelLayout {
 	Unspec(u16),
 	Native(u16, u64),
@@ -534,6 +585,32 @@ impl ChannelLayout {
 			Self::Custom(custom) => custom.len().try_into().unwrap()
 		}
 	}
+
+	/// The channel at `index`, in the order samples are stored
+	pub fn channel(&self, index: u16) -> Channel {
+		match self {
+			Self::Native(_, mask) => {
+				let mut mask = *mask;
+
+				for _ in 0..index {
+					/* clear the lowest channel */
+					mask &= mask.wrapping_sub(1);
+				}
+
+				if mask == 0 {
+					return Channel::Unknown;
+				}
+
+				#[allow(clippy::cast_possible_wrap)]
+				Channel::from(mask.trailing_zeros() as i32)
+			}
+
+			Self::Custom(custom) => custom
+				.get(usize::from(index))
+				.map_or(Channel::Unknown, |channel| channel.id),
+			Self::Unspec(_) | Self::Ambisonic(..) => Channel::Unknown
+		}
+	}
 }
 
 impl Default for ChannelLayout {
@@ -34,6 +34,33 @@ impl FilterContext {
 		Object::from(self.0)
 	}
 
+	/// Send `command` to the running filter, changing its
+	/// parameters without rebuilding the graph. Fails with
+	/// [`ErrorKind::Unsupported`] if the filter takes no commands
+	#[allow(dead_code)]
+	pub fn send_command(&mut self, command: &str, arg: &str) -> Result<()> {
+		self.send_command_c(&into_cstr(command), &into_cstr(arg))
+	}
+
+	pub fn send_command_c(&mut self, command: &CStr, arg: &CStr) -> Result<()> {
+		let result = ffi!(
+			avfilter_process_command,
+			self.as_mut_ptr(),
+			command.as_ptr(),
+			arg.as_ptr(),
+			MutPtr::null().as_mut_ptr(),
+			0,
+			0
+		);
+
+		match result {
+			Err(err) if err.os_error() == Some(OsError::NoSys) => {
+				Err(ErrorKind::Unsupported.into())
+			}
+			result => result.map(|_| ())
+		}
+	}
+
 	pub fn link(&mut self, pad: u32, dst: &mut Self, dst_pad: u32) -> Result<()> {
 		ffi!(
 			avfilter_link,
@@ -79,6 +106,39 @@ impl FilterGraph {
 		FilterContext(ptr)
 	}
 
+	/// The filters whose names start with `prefix`, in the
+	/// order they were added
+	pub fn filters_with_prefix(&mut self, prefix: &[u8]) -> Vec<FilterContext> {
+		let mut filters = Vec::new();
+
+		for i in 0..self.nb_filters as usize {
+			/* Safety: the graph has `nb_filters` filters */
+			#[allow(clippy::multiple_unsafe_ops_per_block)]
+			let filter = unsafe { *self.filters.add(i) };
+
+			let Some(filter) = MutNonNull::new(filter.into()) else {
+				continue;
+			};
+
+			/* Safety: the filter is valid */
+			let name = unsafe { ptr!(filter=>name) };
+
+			if name.is_null() {
+				continue;
+			}
+
+			/* Safety: names are nul terminated */
+			if unsafe { CStr::from_ptr(name) }
+				.to_bytes()
+				.starts_with(prefix)
+			{
+				filters.push(FilterContext(filter));
+			}
+		}
+
+		filters
+	}
+
 	pub fn config(&mut self) -> Result<()> {
 		ffi!(
 			avfilter_graph_config,
@@ -90,6 +150,33 @@ impl FilterGraph {
 	}
 }
 
+av_wrapper!(
+	FilterInOut,
+	AVFilterInOut,
+	avfilter_inout_free,
+	avfilter_inout_alloc
+);
+
+impl FilterInOut {
+	/// An open end of a graph description, labelled `name`
+	fn endpoint(name: &CStr, filter: &mut FilterContext) -> Self {
+		let mut inout = Self::new();
+
+		inout.name = alloc_with(|| ffi!(av_strdup, name.as_ptr())).as_mut_ptr();
+		inout.filter_ctx = filter.as_mut_ptr();
+		inout.pad_idx = 0;
+		inout.next = MutPtr::null().as_mut_ptr();
+		inout
+	}
+
+	fn into_raw(self) -> *mut AVFilterInOut {
+		let ptr = self.as_mut_ptr();
+
+		forget(self);
+		ptr
+	}
+}
+
 #[derive(Clone)]
 pub struct AudioSrcOptions {
 	pub time_base: Option<Rational>,
@@ -107,6 +194,110 @@ pub struct AudioSinkOptions {
 
 pub struct AudioFilterGraph(FilterGraph, AudioBufferSrc, AudioBufferSink);
 
+/// # Panics
+/// if the sample rate cannot fit into an i32
+fn create_src(graph: &mut FilterGraph, input: &AudioSrcOptions) -> AudioBufferSrc {
+	let mut src = AudioBufferSrc::new(graph);
+
+	if let Some(time_base) = input.time_base {
+		src.set_time_base(time_base);
+	}
+
+	if let Some(sample_fmt) = input.sample_fmt {
+		src.set_sample_fmt(sample_fmt);
+	}
+
+	if let Some(ch_layout) = &input.ch_layout {
+		src.set_ch_layout(ch_layout);
+	}
+
+	if let Some(sample_rate) = input.sample_rate {
+		#[allow(clippy::unwrap_used)]
+		src.set_sample_rate(sample_rate.try_into().unwrap());
+	}
+
+	src
+}
+
+/// # Panics
+/// if the sample rate cannot fit into an i32
+fn create_sink(graph: &mut FilterGraph, output: &AudioSinkOptions) -> AudioBufferSink {
+	let mut sink = AudioBufferSink::new(graph);
+
+	if let Some(ch_layout) = &output.ch_layout {
+		sink.set_ch_layouts(&[ch_layout.clone()]);
+	} else {
+		sink.set_all_channel_counts(true);
+	}
+
+	if let Some(sample_fmt) = output.sample_fmt {
+		sink.set_sample_fmts(&[sample_fmt]);
+	}
+
+	if let Some(sample_rate) = output.sample_rate {
+		#[allow(clippy::unwrap_used)]
+		sink.set_sample_rates(&[sample_rate.try_into().unwrap()]);
+	}
+
+	sink
+}
+
+/// Link `filters` in a chain from `src` to `sink`, and configure the graph
+fn link_filters<'a, I>(
+	graph: &mut FilterGraph, src: &mut FilterContext, filters: I, sink: &mut FilterContext
+) -> Result<()>
+where
+	I: IntoIterator<Item = &'a mut FilterContext>
+{
+	let mut prev = src;
+
+	for filter in filters {
+		filter.init()?;
+		prev.link(0, filter, 0)?;
+		prev = filter;
+	}
+
+	prev.link(0, sink, 0)?;
+	graph.config()
+}
+
+/// Build `graph` from an ffmpeg filtergraph description, where each of
+/// `sources` is labelled by its name and `[out]` is the sink. Returns
+/// the filters of the description, in order
+fn parse_graph(
+	graph: &mut FilterGraph, sources: &mut [(&CStr, &mut FilterContext)], sink: &mut FilterContext,
+	description: &CStr
+) -> Result<Vec<FilterContext>> {
+	let mut outputs = MutPtr::null().as_mut_ptr();
+
+	for (name, source) in sources.iter_mut().rev() {
+		let mut end = FilterInOut::endpoint(name, source);
+
+		end.next = outputs;
+		outputs = end.into_raw();
+	}
+
+	let mut inputs = FilterInOut::endpoint(c"out", sink).into_raw();
+
+	let result = ffi!(
+		avfilter_graph_parse_ptr,
+		graph.as_mut_ptr(),
+		description.as_ptr(),
+		&mut inputs,
+		&mut outputs,
+		MutPtr::null().as_mut_ptr()
+	);
+
+	/* frees what the parser did not link */
+	ffi!(avfilter_inout_free, &mut inputs);
+	ffi!(avfilter_inout_free, &mut outputs);
+
+	result?;
+	graph.config()?;
+
+	Ok(graph.filters_with_prefix(b"Parsed_"))
+}
+
 impl AudioFilterGraph {
 	/// # Panics
 	/// if the sample rate cannot fit into an i32
@@ -115,39 +306,162 @@ impl AudioFilterGraph {
 
 		graph.nb_threads = threads as i32;
 
-		let mut src = AudioBufferSrc::new(&mut graph);
-		let mut sink = AudioBufferSink::new(&mut graph);
+		let src = create_src(&mut graph, input);
+		let sink = create_sink(&mut graph, output);
 
-		if let Some(time_base) = input.time_base {
-			src.set_time_base(time_base);
-		}
+		Self(graph, src, sink)
+	}
 
-		if let Some(sample_fmt) = input.sample_fmt {
-			src.set_sample_fmt(sample_fmt);
-		}
+	pub fn set_filters<'a, I>(&mut self, filters: I) -> Result<()>
+	where
+		I: IntoIterator<Item = &'a mut FilterContext>
+	{
+		self.1.init()?;
+		self.2.init()?;
 
-		if let Some(ch_layout) = &input.ch_layout {
-			src.set_ch_layout(ch_layout);
-		}
+		link_filters(&mut self.0, &mut self.1, filters, &mut self.2)
+	}
+
+	/// Build the graph from an ffmpeg filtergraph description, where
+	/// `[in]` is the source and `[out]` is the sink. Returns the filters
+	/// of the description, in order
+	pub fn parse(&mut self, description: &CStr) -> Result<Vec<FilterContext>> {
+		self.1.init()?;
+		self.2.init()?;
+
+		parse_graph(
+			&mut self.0,
+			&mut [(c"in", &mut **self.1)],
+			&mut self.2,
+			description
+		)
+	}
+
+	pub fn send_frame(&mut self, frame: AVFrame) -> Result<()> {
+		/* Safety: frame is valid */
+		unsafe { self.1.send_frame(frame) }
+	}
+
+	pub fn receive_frame(&mut self, frame: &mut AVFrame) -> Result<bool> {
+		/* Safety: frame is valid */
+		unsafe { self.2.receive_frame(frame) }
+	}
+
+	pub fn set_frame_size(&mut self, frame_size: u32) {
+		self.2.set_frame_size(frame_size);
+	}
+
+	pub fn drain(&mut self) -> Result<()> {
+		self.1.drain()
+	}
+}
+
+deref_inner!(AudioFilterGraph, FilterGraph);
 
-		if let Some(sample_rate) = input.sample_rate {
-			#[allow(clippy::unwrap_used)]
-			src.set_sample_rate(sample_rate.try_into().unwrap());
+/// A filter graph with several inputs, mixed down to one output
+pub struct MultiAudioFilterGraph(FilterGraph, Vec<AudioBufferSrc>, AudioBufferSink);
+
+impl MultiAudioFilterGraph {
+	/// # Panics
+	/// if a sample rate cannot fit into an i32
+	pub fn new(threads: u16, inputs: &[AudioSrcOptions], output: &AudioSinkOptions) -> Self {
+		let mut graph = FilterGraph::new();
+
+		graph.nb_threads = threads as i32;
+
+		let srcs = inputs
+			.iter()
+			.map(|input| create_src(&mut graph, input))
+			.collect();
+		let sink = create_sink(&mut graph, output);
+
+		Self(graph, srcs, sink)
+	}
+
+	/// Build the graph from an ffmpeg filtergraph description, where
+	/// the inputs are labelled by `labels` in order, and `[out]` is the
+	/// sink. Returns the filters of the description, in order
+	pub fn parse(&mut self, labels: &[&CStr], description: &CStr) -> Result<Vec<FilterContext>> {
+		for src in &mut self.1 {
+			src.init()?;
 		}
 
-		if let Some(ch_layout) = &output.ch_layout {
-			sink.set_ch_layouts(&[ch_layout.clone()]);
-		} else {
-			sink.set_all_channel_counts(true);
+		self.2.init()?;
+
+		let mut sources: Vec<_> = labels
+			.iter()
+			.zip(&mut self.1)
+			.map(|(label, src)| (*label, &mut ***src))
+			.collect();
+
+		parse_graph(&mut self.0, &mut sources, &mut self.2, description)
+	}
+
+	/// # Panics
+	/// if `input` is out of bounds
+	pub fn send_frame(&mut self, input: usize, frame: AVFrame) -> Result<()> {
+		/* Safety: frame is valid */
+		unsafe { self.1[input].send_frame(frame) }
+	}
+
+	pub fn receive_frame(&mut self, frame: &mut AVFrame) -> Result<bool> {
+		/* Safety: frame is valid */
+		unsafe { self.2.receive_frame(frame) }
+	}
+
+	pub fn set_frame_size(&mut self, frame_size: u32) {
+		self.2.set_frame_size(frame_size);
+	}
+
+	/// # Panics
+	/// if `input` is out of bounds
+	pub fn drain(&mut self, input: usize) -> Result<()> {
+		self.1[input].drain()
+	}
+}
+
+deref_inner!(MultiAudioFilterGraph, FilterGraph);
+
+#[derive(Clone)]
+pub struct VideoSrcOptions {
+	pub width: u32,
+	pub height: u32,
+	pub pix_fmt: PixelFormat,
+	pub time_base: Rational,
+	pub frame_rate: Option<Rational>,
+	pub sample_aspect_ratio: Option<Rational>
+}
+
+#[derive(Clone)]
+pub struct VideoSinkOptions {
+	pub pix_fmt: Option<PixelFormat>
+}
+
+pub struct VideoFilterGraph(FilterGraph, VideoBufferSrc, VideoBufferSink);
+
+impl VideoFilterGraph {
+	pub fn new(threads: u16, input: &VideoSrcOptions, output: &VideoSinkOptions) -> Self {
+		let mut graph = FilterGraph::new();
+
+		graph.nb_threads = threads as i32;
+
+		let mut src = VideoBufferSrc::new(&mut graph);
+		let mut sink = VideoBufferSink::new(&mut graph);
+
+		src.set_size(ImageSize(input.width, input.height));
+		src.set_pix_fmt(input.pix_fmt);
+		src.set_time_base(input.time_base);
+
+		if let Some(frame_rate) = input.frame_rate {
+			src.set_frame_rate(frame_rate);
 		}
 
-		if let Some(sample_fmt) = output.sample_fmt {
-			sink.set_sample_fmts(&[sample_fmt]);
+		if let Some(sample_aspect_ratio) = input.sample_aspect_ratio {
+			src.set_sample_aspect_ratio(sample_aspect_ratio);
 		}
 
-		if let Some(sample_rate) = output.sample_rate {
-			#[allow(clippy::unwrap_used)]
-			sink.set_sample_rates(&[sample_rate.try_into().unwrap()]);
+		if let Some(pix_fmt) = output.pix_fmt {
+			sink.set_pix_fmts(&[pix_fmt]);
 		}
 
 		Self(graph, src, sink)
@@ -160,17 +474,22 @@ impl AudioFilterGraph {
 		self.1.init()?;
 		self.2.init()?;
 
-		let mut prev = &mut **self.1;
-
-		for filter in filters {
-			filter.init()?;
-			prev.link(0, filter, 0)?;
-			prev = filter;
-		}
+		link_filters(&mut self.0, &mut self.1, filters, &mut self.2)
+	}
 
-		prev.link(0, &mut self.2, 0)?;
+	/// Build the graph from an ffmpeg filtergraph description, where
+	/// `[in]` is the source and `[out]` is the sink. Returns the filters
+	/// of the description, in order
+	pub fn parse(&mut self, description: &CStr) -> Result<Vec<FilterContext>> {
+		self.1.init()?;
+		self.2.init()?;
 
-		self.0.config()
+		parse_graph(
+			&mut self.0,
+			&mut [(c"in", &mut **self.1)],
+			&mut self.2,
+			description
+		)
 	}
 
 	pub fn send_frame(&mut self, frame: AVFrame) -> Result<()> {
@@ -183,13 +502,9 @@ impl AudioFilterGraph {
 		unsafe { self.2.receive_frame(frame) }
 	}
 
-	pub fn set_frame_size(&mut self, frame_size: u32) {
-		self.2.set_frame_size(frame_size);
-	}
-
 	pub fn drain(&mut self) -> Result<()> {
 		self.1.drain()
 	}
 }
 
-deref_inner!(AudioFilterGraph, FilterGraph);
+deref_inner!(VideoFilterGraph, FilterGraph);
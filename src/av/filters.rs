@@ -110,6 +110,31 @@ where
 	);
 }
 
+fn send_command_args(
+	filter: &mut FilterContext, command: &CStr, args: Arguments<'_>
+) -> Result<()> {
+	let mut result = Ok(());
+
+	format_cstr_args::<64, _>(args, |arg| result = filter.send_command_c(command, arg));
+
+	result
+}
+
+macro_rules! commands {
+	{
+		$($name:ident: $type:ty = $command:literal),*
+	} => {
+		paste! {
+			$(
+				#[allow(dead_code)]
+				pub fn [< send_ $name >](filter: &mut FilterContext, $name: $type) -> Result<()> {
+					send_command_args(filter, $command, format_args!("{}", $name))
+				}
+			)*
+		}
+	};
+}
+
 pub struct BufferSrc(FilterContext);
 
 impl BufferSrc {
@@ -218,6 +243,50 @@ impl AudioBufferSink {
 
 deref_inner!(AudioBufferSink, BufferSink);
 
+pub struct VideoBufferSrc(BufferSrc);
+
+impl VideoBufferSrc {
+	options! {
+		size: ImageSize = c"video_size",
+		pix_fmt: PixelFormat = c"pix_fmt",
+		time_base: Rational = c"time_base",
+		frame_rate: Rational = c"frame_rate",
+		sample_aspect_ratio: Rational = c"pixel_aspect"
+	}
+
+	/// # Panics
+	/// if the filter is not found
+	pub fn new(graph: &mut FilterGraph) -> Self {
+		#[allow(clippy::expect_used)]
+		let filter = Filters::find_by_name_c(c"buffer").expect("Filter not found");
+		let ctx = graph.create_filter_c(filter, Some(c"buffersrc"));
+
+		Self(BufferSrc(ctx))
+	}
+}
+
+deref_inner!(VideoBufferSrc, BufferSrc);
+
+pub struct VideoBufferSink(BufferSink);
+
+impl VideoBufferSink {
+	options! {
+		pix_fmts: &[PixelFormat] = c"pix_fmts"
+	}
+
+	/// # Panics
+	/// if the filter is not found
+	pub fn new(graph: &mut FilterGraph) -> Self {
+		#[allow(clippy::expect_used)]
+		let filter = Filters::find_by_name_c(c"buffersink").expect("Filter not found");
+		let ctx = graph.create_filter_c(filter, Some(c"buffersink"));
+
+		Self(BufferSink(ctx))
+	}
+}
+
+deref_inner!(VideoBufferSink, BufferSink);
+
 pub struct Volume(FilterContext);
 
 impl Volume {
@@ -227,6 +296,10 @@ impl Volume {
 		volume_str: &CStr = c"volume"
 	}
 
+	commands! {
+		volume: f64 = c"volume"
+	}
+
 	pub fn set_volume(&mut self, volume: f64) {
 		format_cstr_args::<32, _>(format_args!("{}", volume), |volume| {
 			self.set_volume_str(volume);
@@ -263,6 +336,10 @@ impl Tempo {
 	options! {
 		tempo: f64 = c"tempo"
 	}
+
+	commands! {
+		tempo: f64 = c"tempo"
+	}
 }
 
 deref_inner!(Tempo, FilterContext);
@@ -281,7 +358,10 @@ impl FirEqualizer {
 		use_fft_two_channels: bool = c"fft2"
 	}
 
-	pub fn set_gain_entries(&mut self, entries: &[(f64, f64)]) {
+	fn format_gain_entries<F>(entries: &[(f64, f64)], func: F)
+	where
+		F: FnOnce(&CStr)
+	{
 		format_cstr::<1024, _, _>(
 			|cursor| {
 				for (i, (frequency, gain)) in entries.iter().enumerate() {
@@ -292,9 +372,24 @@ impl FirEqualizer {
 					let _ = cursor.write_fmt(format_args!("entry({}, {})", frequency, gain));
 				}
 			},
-			|str| self.set_gain_entries_str(str)
+			func
 		);
 	}
+
+	pub fn set_gain_entries(&mut self, entries: &[(f64, f64)]) {
+		Self::format_gain_entries(entries, |str| self.set_gain_entries_str(str));
+	}
+
+	/// Only the gain entries of a running equalizer can change
+	pub fn send_gain_entries(filter: &mut FilterContext, entries: &[(f64, f64)]) -> Result<()> {
+		let mut result = Ok(());
+
+		Self::format_gain_entries(entries, |str| {
+			result = filter.send_command_c(c"gain_entry", str);
+		});
+
+		result
+	}
 }
 
 deref_inner!(FirEqualizer, FilterContext);
@@ -358,3 +453,232 @@ impl Echo {
 }
 
 deref_inner!(Echo, FilterContext);
+
+pub struct LoudNorm(FilterContext);
+
+impl LoudNorm {
+	new_filter!(c"loudnorm");
+
+	options! {
+		integrated: f64 = c"I",
+		range: f64 = c"LRA",
+		true_peak: f64 = c"TP",
+		measured_integrated: f64 = c"measured_I",
+		measured_range: f64 = c"measured_LRA",
+		measured_true_peak: f64 = c"measured_TP",
+		measured_threshold: f64 = c"measured_thresh",
+		offset: f64 = c"offset",
+		linear: bool = c"linear"
+	}
+}
+
+deref_inner!(LoudNorm, FilterContext);
+
+/// The name of `format`, such as `yuv420p`
+fn pix_fmt_name(format: PixelFormat) -> &'static CStr {
+	let name = ffi!(av_get_pix_fmt_name, format.into());
+
+	if name.is_null() {
+		return c"none";
+	}
+
+	/* Safety: names are static and nul terminated */
+	unsafe { CStr::from_ptr(name) }
+}
+
+pub struct Scale(FilterContext);
+
+impl Scale {
+	new_filter!(c"scale");
+
+	options! {
+		width_str: &CStr = c"w",
+		height_str: &CStr = c"h",
+		flags_str: &CStr = c"flags",
+		fit_str: &CStr = c"force_original_aspect_ratio"
+	}
+
+	/// Sizes of `None` keep the aspect ratio
+	pub fn set_size(&mut self, width: Option<u32>, height: Option<u32>) {
+		let size = |size: Option<u32>| size.map_or(-1, i64::from);
+
+		format_cstr_args::<32, _>(format_args!("{}", size(width)), |width| {
+			self.set_width_str(width);
+		});
+
+		format_cstr_args::<32, _>(format_args!("{}", size(height)), |height| {
+			self.set_height_str(height);
+		});
+	}
+}
+
+deref_inner!(Scale, FilterContext);
+
+pub struct Crop(FilterContext);
+
+impl Crop {
+	new_filter!(c"crop");
+
+	options! {
+		width_str: &CStr = c"w",
+		height_str: &CStr = c"h",
+		x_str: &CStr = c"x",
+		y_str: &CStr = c"y"
+	}
+
+	pub fn set_rect(&mut self, x: u32, y: u32, width: u32, height: u32) {
+		format_cstr_args::<16, _>(format_args!("{}", x), |x| self.set_x_str(x));
+		format_cstr_args::<16, _>(format_args!("{}", y), |y| self.set_y_str(y));
+		format_cstr_args::<16, _>(format_args!("{}", width), |width| {
+			self.set_width_str(width);
+		});
+
+		format_cstr_args::<16, _>(format_args!("{}", height), |height| {
+			self.set_height_str(height);
+		});
+	}
+}
+
+deref_inner!(Crop, FilterContext);
+
+pub struct Fps(FilterContext);
+
+impl Fps {
+	new_filter!(c"fps");
+
+	options! {
+		fps_str: &CStr = c"fps"
+	}
+
+	pub fn set_fps(&mut self, fps: Rational) {
+		format_cstr_args::<32, _>(format_args!("{}/{}", fps.num, fps.den), |fps| {
+			self.set_fps_str(fps);
+		});
+	}
+}
+
+deref_inner!(Fps, FilterContext);
+
+pub struct FormatFilter(FilterContext);
+
+impl FormatFilter {
+	new_filter!(c"format");
+
+	options! {
+		pix_fmts_str: &CStr = c"pix_fmts"
+	}
+
+	pub fn set_pix_fmts(&mut self, formats: &[PixelFormat]) {
+		format_cstr::<1024, _, _>(
+			|cursor| {
+				for (i, format) in formats.iter().enumerate() {
+					if i > 0 {
+						let _ = cursor.write(b"|");
+					}
+
+					let _ = cursor.write(pix_fmt_name(*format).to_bytes());
+				}
+			},
+			|str| self.set_pix_fmts_str(str)
+		);
+	}
+}
+
+deref_inner!(FormatFilter, FilterContext);
+
+pub struct Transpose(FilterContext);
+
+impl Transpose {
+	new_filter!(c"transpose");
+
+	options! {
+		dir: i32 = c"dir"
+	}
+}
+
+deref_inner!(Transpose, FilterContext);
+
+pub struct Pad(FilterContext);
+
+impl Pad {
+	new_filter!(c"pad");
+
+	options! {
+		width_str: &CStr = c"w",
+		height_str: &CStr = c"h",
+		x_str: &CStr = c"x",
+		y_str: &CStr = c"y",
+		color_str: &CStr = c"color"
+	}
+
+	/// Positions of `None` center the picture
+	pub fn set_rect(&mut self, x: Option<u32>, y: Option<u32>, width: u32, height: u32) {
+		format_cstr_args::<16, _>(format_args!("{}", width), |width| {
+			self.set_width_str(width);
+		});
+
+		format_cstr_args::<16, _>(format_args!("{}", height), |height| {
+			self.set_height_str(height);
+		});
+
+		match x {
+			Some(x) => format_cstr_args::<16, _>(format_args!("{}", x), |x| self.set_x_str(x)),
+			None => self.set_x_str(c"(ow-iw)/2")
+		}
+
+		match y {
+			Some(y) => format_cstr_args::<16, _>(format_args!("{}", y), |y| self.set_y_str(y)),
+			None => self.set_y_str(c"(oh-ih)/2")
+		}
+	}
+
+	/// `color` is red, green, blue and alpha
+	pub fn set_color(&mut self, color: [u8; 4]) {
+		let [r, g, b, a] = color;
+
+		format_cstr_args::<16, _>(
+			format_args!("0x{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+			|color| {
+				self.set_color_str(color);
+			}
+		);
+	}
+}
+
+deref_inner!(Pad, FilterContext);
+
+pub struct SetSar(FilterContext);
+
+impl SetSar {
+	new_filter!(c"setsar");
+
+	options! {
+		sar_str: &CStr = c"sar"
+	}
+
+	pub fn set_sar(&mut self, sar: Rational) {
+		format_cstr_args::<32, _>(format_args!("{}/{}", sar.num, sar.den), |sar| {
+			self.set_sar_str(sar);
+		});
+	}
+}
+
+deref_inner!(SetSar, FilterContext);
+
+pub struct SetTimeBase(FilterContext);
+
+impl SetTimeBase {
+	new_filter!(c"settb");
+
+	options! {
+		time_base_str: &CStr = c"expr"
+	}
+
+	pub fn set_time_base(&mut self, time_base: Rational) {
+		format_cstr_args::<32, _>(format_args!("{}/{}", time_base.num, time_base.den), |tb| {
+			self.set_time_base_str(tb);
+		});
+	}
+}
+
+deref_inner!(SetTimeBase, FilterContext);
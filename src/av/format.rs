@@ -11,6 +11,36 @@ pub struct ProbeResult {
 	pub format: NonNull<AVInputFormat>
 }
 
+/// Copy the entries of a dictionary
+///
+/// # Safety
+/// `dict` must be null or a valid dictionary
+pub unsafe fn read_metadata(dict: *const AVDictionary) -> Metadata {
+	let mut metadata = Metadata::default();
+	let mut entry = Ptr::<AVDictionaryEntry>::null().as_ptr();
+
+	loop {
+		entry = ffi!(av_dict_iterate, dict, entry);
+
+		if entry.is_null() {
+			break;
+		}
+
+		#[allow(clippy::multiple_unsafe_ops_per_block)]
+		/* Safety: entries have valid key and value strings */
+		let (key, value) = unsafe {
+			(
+				CStr::from_ptr((*entry).key).to_string_lossy(),
+				CStr::from_ptr((*entry).value).to_string_lossy()
+			)
+		};
+
+		metadata.0.push((key.into_owned(), value.into_owned()));
+	}
+
+	metadata
+}
+
 struct Guard(MutPtr<AVFormatContext>);
 
 ptr_deref!(Guard, AVFormatContext);
@@ -22,6 +52,14 @@ ptr_deref!(FormatContext, AVFormatContext);
 drop!(FormatContext, avformat_close_input);
 
 impl FormatContext {
+	/// Find an input format by its short name, such as `mp3`
+	pub fn find_input_format(name: &str) -> Option<NonNull<AVInputFormat>> {
+		let name = CString::new(name).ok()?;
+		let format = ffi!(av_find_input_format, name.as_ptr());
+
+		NonNull::new(format.into())
+	}
+
 	pub fn new() -> Self {
 		let context = IoContext::new();
 
@@ -35,65 +73,113 @@ impl FormatContext {
 
 #[asynchronous]
 impl FormatContext {
-	pub async fn probe(reader: &mut Reader) -> Result<Option<ProbeResult>> {
-		const_assert!(DEFAULT_BUFFER_SIZE <= u32::MAX as usize);
-
-		let read = |io: &mut IoContext| async move {
-			/// # Safety
-			/// valid cstr pointer
-			///
-			/// # Panics
-			/// if the resulting bytes is not utf8
-			unsafe fn cstr_to_str(cstr: *const c_char) -> String {
-				if cstr.is_null() {
-					return String::new();
-				}
+	/// Probe the input. The extension of `file_name` and the
+	/// `mime_type`, if any, are used as hints
+	///
+	/// This reads the input in growing steps as in `av_probe_input_buffer2`,
+	/// which only takes the MIME type from the options of a protocol
+	pub async fn probe(
+		reader: &mut Reader, file_name: Option<&str>, mime_type: Option<&str>
+	) -> Result<Option<ProbeResult>> {
+		/// # Safety
+		/// valid cstr pointer
+		///
+		/// # Panics
+		/// if the resulting bytes is not utf8
+		unsafe fn cstr_to_str(cstr: *const c_char) -> String {
+			if cstr.is_null() {
+				return String::new();
+			}
 
-				/* Safety: guaranteed by caller */
-				let str = unsafe { CStr::from_ptr(cstr) };
+			/* Safety: guaranteed by caller */
+			let str = unsafe { CStr::from_ptr(cstr) };
 
-				#[allow(clippy::unwrap_used)]
-				str.to_str().unwrap().to_string()
-			}
+			#[allow(clippy::unwrap_used)]
+			str.to_str().unwrap().to_string()
+		}
 
-			let mut format = Ptr::null().as_ptr();
+		const PROBE_SIZE_MIN: usize = 2048;
+		const_assert!(DEFAULT_BUFFER_SIZE <= i32::MAX as usize);
 
-			#[allow(clippy::cast_possible_truncation)]
-			let score = ffi!(
-				av_probe_input_buffer2,
-				io.as_mut_ptr(),
-				&mut format,
-				Ptr::null().as_ptr(),
-				MutPtr::null().as_mut_ptr(),
-				0,
-				DEFAULT_BUFFER_SIZE as u32
-			)?;
+		let file_name = CString::new(file_name.unwrap_or_default()).unwrap_or_default();
+		let mime_type = mime_type.and_then(|mime_type| CString::new(mime_type).ok());
+
+		#[allow(clippy::cast_sign_loss)]
+		let padding = AVPROBE_PADDING_SIZE as usize;
+		let mut buf = Vec::new();
+		let mut probe_size = PROBE_SIZE_MIN.min(DEFAULT_BUFFER_SIZE);
 
-			#[allow(clippy::multiple_unsafe_ops_per_block)]
-			/* Safety: ptr is non-null */
-			let result = unsafe {
-				let format = NonNull::new_unchecked(format.into());
-
-				#[allow(clippy::cast_precision_loss)]
-				ProbeResult {
-					name: cstr_to_str(ptr!(format=>name)),
-					long_name: cstr_to_str(ptr!(format=>long_name)),
-					mime_type: cstr_to_str(ptr!(format=>mime_type)),
-					score: score as f32 / AVPROBE_SCORE_MAX as f32,
-					format
+		loop {
+			let mut len = buf.len();
+			let mut eof = false;
+
+			buf.resize(probe_size, 0);
+
+			while len < probe_size {
+				let read = match reader.read_partial(&mut buf[len..]).await {
+					Ok(n) => n,
+					Err(err) if err == ErrorKind::UnexpectedEof => 0,
+					Err(err) => return Err(err)
+				};
+
+				if read == 0 {
+					eof = true;
+
+					break;
 				}
+
+				#[allow(clippy::arithmetic_side_effects)]
+				(len += read);
+			}
+
+			/* probe functions may read past the end, into zeroed padding */
+			buf.truncate(len);
+
+			#[allow(clippy::arithmetic_side_effects)]
+			buf.resize(len + padding, 0);
+
+			let mut score = if probe_size < DEFAULT_BUFFER_SIZE {
+				AVPROBE_SCORE_RETRY
+			} else {
+				0
 			};
 
-			Ok(result)
-		};
+			#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+			let data = AVProbeData {
+				filename: file_name.as_ptr(),
+				buf: buf.as_mut_ptr(),
+				buf_size: len as i32,
+				mime_type: mime_type
+					.as_deref()
+					.map_or(Ptr::null().as_ptr(), CStr::as_ptr)
+			};
 
-		let mut context = IoContext::new();
-		let mut adapter = Adapter::new(&mut context, reader);
+			let format = ffi!(av_probe_input_format2, &data, 1, &mut score);
 
-		match adapter.with(read).await {
-			Ok(probe) => Ok(Some(probe)),
-			Err(err) if err == AVError::InvalidData => Ok(None),
-			Err(err) => Err(err)
+			buf.truncate(len);
+
+			if let Some(format) = NonNull::new(format.into()) {
+				#[allow(clippy::multiple_unsafe_ops_per_block)]
+				/* Safety: ptr is non-null */
+				let result = unsafe {
+					#[allow(clippy::cast_precision_loss)]
+					ProbeResult {
+						name: cstr_to_str(ptr!(format=>name)),
+						long_name: cstr_to_str(ptr!(format=>long_name)),
+						mime_type: cstr_to_str(ptr!(format=>mime_type)),
+						score: score as f32 / AVPROBE_SCORE_MAX as f32,
+						format
+					}
+				};
+
+				return Ok(Some(result));
+			}
+
+			if eof || probe_size >= DEFAULT_BUFFER_SIZE {
+				return Ok(None);
+			}
+
+			probe_size = probe_size.saturating_mul(2).min(DEFAULT_BUFFER_SIZE);
 		}
 	}
 
@@ -161,8 +247,7 @@ impl FormatContext {
 		#[allow(clippy::unwrap_used)]
 		let track_index = track_index.try_into().unwrap();
 
-		#[allow(clippy::unwrap_used)]
-		let time = timecode.try_into().unwrap();
+		let time: i64 = timecode.try_into().map_err(|_| ErrorKind::Overflow)?;
 
 		let mut seek_flags = 0;
 
@@ -170,15 +255,29 @@ impl FormatContext {
 			seek_flags |= AVSEEK_FLAG_ANY;
 		}
 
+		if flags.intersects(SeekFlag::Byte) {
+			seek_flags |= AVSEEK_FLAG_BYTE;
+		}
+
+		if flags.intersects(SeekFlag::Frame) {
+			seek_flags |= AVSEEK_FLAG_FRAME;
+		}
+
+		let (min_time, max_time) = if flags.intersects(SeekFlag::Forward) {
+			(time, i64::MAX)
+		} else {
+			(i64::MIN, time)
+		};
+
 		let ptr = self.as_mut_ptr();
 		let read = |_: &mut IoContext| async move {
 			ffi!(
 				avformat_seek_file,
 				ptr,
 				track_index,
-				0,
-				time,
+				min_time,
 				time,
+				max_time,
 				seek_flags
 			)?;
 
@@ -18,6 +18,143 @@ impl AVFrame {
 		Ok(())
 	}
 
+	/// Allocate the buffers for the format, size and
+	/// channel layout already set on the frame
+	pub fn get_buffer(&mut self) -> Result<()> {
+		ffi!(av_frame_get_buffer, self.as_mut_ptr(), 0)?;
+
+		Ok(())
+	}
+
+	pub fn make_writable(&mut self) -> Result<()> {
+		ffi!(av_frame_make_writable, self.as_mut_ptr())?;
+
+		Ok(())
+	}
+
+	/// The number of planes of an audio frame, and the number of
+	/// values in each. `None` if the frame has no audio buffers
+	#[allow(clippy::cast_sign_loss)]
+	pub fn sample_planes(&self) -> Option<(usize, usize)> {
+		let format = SampleFormat::from(self.format);
+		let channels = self.ch_layout.nb_channels.max(0) as usize;
+		let samples = self.nb_samples.max(0) as usize;
+
+		if format == SampleFormat::None || self.extended_data.is_null() {
+			return None;
+		}
+
+		if format.is_planar() {
+			Some((channels, samples))
+		} else {
+			Some((1, samples.checked_mul(channels)?))
+		}
+	}
+
+	/// The stride, bytes per row and number of rows of a picture plane.
+	/// `None` if the plane does not exist
+	#[allow(clippy::cast_sign_loss, clippy::arithmetic_side_effects)]
+	pub fn picture_plane(&self, index: usize) -> Result<Option<(usize, usize, usize)>> {
+		let format: AVPixelFormat = PixelFormat::from(self.format).into();
+		let Some(desc) = NonNull::new(ffi!(av_pix_fmt_desc_get, format).into()) else {
+			return Ok(None);
+		};
+
+		if index >= self.data.len() || self.data[index].is_null() {
+			return Ok(None);
+		}
+
+		#[allow(clippy::multiple_unsafe_ops_per_block)]
+		/* Safety: the descriptor is valid */
+		let (flags, chroma_shift) = unsafe { (ptr!(desc=>flags), ptr!(desc=>log2_chroma_h)) };
+
+		/* the palette is stored after the indexes, and is not counted as a plane */
+		if index == 1 && flags & u64::from(AV_PIX_FMT_FLAG_PAL) != 0 {
+			/* 256 colors of 4 bytes each */
+			return Ok(Some((1024, 1024, 1)));
+		}
+
+		if index >= ffi!(av_pix_fmt_count_planes, format)? as usize {
+			return Ok(None);
+		}
+
+		let stride: usize = self.linesize[index]
+			.try_into()
+			.map_err(|_| ErrorKind::Unsupported)?;
+
+		#[allow(clippy::cast_possible_truncation)]
+		let width = ffi!(av_image_get_linesize, format, self.width, index as i32)?;
+		let height = if index == 1 || index == 2 {
+			/* rounded up, like AV_CEIL_RSHIFT */
+			-((-self.height) >> chroma_shift)
+		} else {
+			self.height
+		};
+
+		Ok(Some((stride, width as usize, height.max(0) as usize)))
+	}
+
+	/// The first `len` values of a data plane
+	///
+	/// # Safety
+	/// the plane must exist and hold at least `len` values of `T`
+	pub unsafe fn plane<T>(&self, index: usize, len: usize) -> &[T] {
+		/* Safety: guaranteed by caller */
+		let data = unsafe { ptr!(*self.extended_data.add(index)) };
+
+		/* Safety: guaranteed by caller */
+		unsafe { Ptr::slice_from_raw_parts(data.cast::<T>().cast_const().into(), len).as_ref() }
+	}
+
+	/// The first `len` values of a data plane, for writing
+	///
+	/// # Safety
+	/// the plane must exist, hold at least `len` values of `T`,
+	/// and be writable
+	pub unsafe fn plane_mut<T>(&mut self, index: usize, len: usize) -> &mut [T] {
+		/* Safety: guaranteed by caller */
+		let data = unsafe { ptr!(*self.extended_data.add(index)) };
+
+		/* Safety: guaranteed by caller */
+		unsafe { MutPtr::slice_from_raw_parts(data.cast::<T>().into(), len).as_mut() }
+	}
+
+	/// Remove the first `count` samples of an audio frame
+	pub fn skip_samples(&mut self, count: i32) -> Result<()> {
+		let count = count.clamp(0, self.nb_samples);
+
+		if count == 0 {
+			return Ok(());
+		}
+
+		ffi!(av_frame_make_writable, self.as_mut_ptr())?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let remaining = self.nb_samples - count;
+		let format: AVSampleFormat = SampleFormat::from(self.format).into();
+
+		/* overlapping copies are handled by ffmpeg */
+		ffi!(
+			av_samples_copy,
+			self.extended_data,
+			self.extended_data,
+			0,
+			count,
+			remaining,
+			self.ch_layout.nb_channels,
+			format
+		)?;
+
+		self.nb_samples = remaining;
+
+		Ok(())
+	}
+
+	/// Keep only the first `count` samples of an audio frame
+	pub fn truncate_samples(&mut self, count: i32) {
+		self.nb_samples = self.nb_samples.min(count.max(0));
+	}
+
 	#[allow(dead_code)]
 	pub fn move_ref(&mut self, other: &mut Self) {
 		ffi!(av_frame_move_ref, other.as_mut_ptr(), self.as_mut_ptr());
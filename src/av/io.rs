@@ -133,6 +133,104 @@ impl IoReader<'_> {
 	}
 }
 
+struct IoWriter<'a> {
+	context: &'a Context,
+	sink: &'a mut Sink,
+	error: Errors
+}
+
+#[asynchronous(sync)]
+impl IoWriter<'_> {
+	/// # Safety
+	/// valid ptr
+	unsafe fn with_adapter<F, O: From<i32>>(adapter: *mut c_void, func: F) -> O
+	where
+		F: AsyncFnOnce(&mut Sink, &mut Errors) -> O
+	{
+		/* Safety: guaranteed by caller */
+		let adapter: &mut Self = unsafe { ptr!(adapter).cast().as_mut() };
+
+		/* Safety: perform async write */
+		let result = catch_unwind_safe(|| unsafe {
+			scoped(
+				adapter.context,
+				func.call_once((adapter.sink, &mut adapter.error))
+			)
+		});
+
+		match result {
+			Ok(n) => n,
+			Err(err) => {
+				adapter.error = Errors::Panic(err);
+
+				AVERROR_BUG.into()
+			}
+		}
+	}
+
+	/// # Safety
+	/// valid ptrs
+	unsafe extern "C" fn write(adapter: *mut c_void, buf: *const u8, buf_size: i32) -> i32 {
+		let write = |sink: &mut Sink, error: &mut Errors| async move {
+			let Ok(size) = buf_size.try_into() else {
+				return AVERROR(OsError::Inval as i32);
+			};
+
+			/* Safety: the buffer is valid for `size` bytes */
+			let buf = unsafe { Ptr::slice_from_raw_parts(buf.into(), size).as_ref() };
+
+			match sink.write_all(buf).await {
+				Ok(()) => buf_size,
+				Err(err) => av_from_error(error.fail(err))
+			}
+		};
+
+		/* Safety: guaranteed by caller */
+		unsafe { Self::with_adapter(adapter, write) }
+	}
+
+	/// # Safety
+	/// valid ptrs
+	#[allow(clippy::unwrap_used, clippy::missing_panics_doc)]
+	unsafe extern "C" fn seek(adapter: *mut c_void, offset: i64, mut whence: i32) -> i64 {
+		let seek = |sink: &mut Sink, error: &mut Errors| async move {
+			if whence & AVSEEK_SIZE != 0 {
+				return match sink.stream_len().await {
+					Ok(n) => n.try_into().unwrap(),
+					Err(err) => av_from_error(error.fail(err)) as i64
+				};
+			}
+
+			whence &= !AVSEEK_FORCE;
+
+			let seek = match whence {
+				/* SEEK_SET */
+				0 => SeekFrom::Start(offset.try_into().unwrap()),
+
+				/* SEEK_CUR */
+				1 => SeekFrom::Current(offset),
+
+				/* SEEK_END */
+				2 => SeekFrom::End(offset),
+
+				_ => return AVERROR(OsError::Inval as i32) as i64
+			};
+
+			match sink.seek(seek).await {
+				Ok(position) => position.try_into().unwrap(),
+				Err(err) => av_from_error(error.fail(err)) as i64
+			}
+		};
+
+		/* Safety: guaranteed by caller */
+		unsafe { Self::with_adapter(adapter, seek) }
+	}
+}
+
+type ReadFn = unsafe extern "C" fn(*mut c_void, *mut u8, i32) -> i32;
+type WriteFn = unsafe extern "C" fn(*mut c_void, *const u8, i32) -> i32;
+type SeekFn = unsafe extern "C" fn(*mut c_void, i64, i32) -> i64;
+
 struct Buf(MutNonNull<u8>);
 
 impl Drop for Buf {
@@ -145,21 +243,36 @@ pub struct IoContext(MutNonNull<AVIOContext>);
 
 impl IoContext {
 	pub fn new() -> Self {
+		Self::alloc(false)
+	}
+
+	/// A context that writes to a [`Sink`], for muxing
+	pub fn new_output() -> Self {
+		Self::alloc(true)
+	}
+
+	fn alloc(output: bool) -> Self {
 		const_assert!(DEFAULT_BUFFER_SIZE < i32::MAX as usize);
 
 		let buf = Buf(alloc_with(|| ffi!(av_malloc, DEFAULT_BUFFER_SIZE)).cast());
 
+		let (read, write, seek): (Option<ReadFn>, WriteFn, SeekFn) = if output {
+			(None, IoWriter::write, IoWriter::seek)
+		} else {
+			(Some(IoReader::read), IoReader::write, IoReader::seek)
+		};
+
 		#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
 		let ptr = alloc_with(|| {
 			ffi!(
 				avio_alloc_context,
 				buf.0.as_mut_ptr().cast(),
 				DEFAULT_BUFFER_SIZE as i32,
-				0,
+				output.into(),
 				MutPtr::null().as_mut_ptr(),
-				Some(IoReader::read),
-				Some(IoReader::write),
-				Some(IoReader::seek)
+				read,
+				Some(write),
+				Some(seek)
 			)
 		});
 
@@ -226,3 +339,44 @@ impl<'a> Adapter<'a> {
 		}
 	}
 }
+
+pub struct OutputAdapter<'a> {
+	context: &'a mut IoContext,
+	sink: &'a mut Sink
+}
+
+#[asynchronous]
+impl<'a> OutputAdapter<'a> {
+	pub fn new(context: &'a mut IoContext, sink: &'a mut Sink) -> Self {
+		Self { context, sink }
+	}
+
+	pub async fn with<F, Output>(&mut self, func: F) -> Result<Output>
+	where
+		F: AsyncFnOnce(&mut IoContext) -> Result<Output>
+	{
+		self.context.seekable = if self.sink.seekable() {
+			AVIO_SEEKABLE_NORMAL
+		} else {
+			0
+		};
+
+		let mut writer = IoWriter {
+			context: get_context().await,
+			sink: self.sink,
+			error: Errors::None
+		};
+
+		self.context.opaque = ptr!(&mut writer).as_mut_ptr().cast();
+
+		let result = func.call_once(self.context).await;
+
+		self.context.opaque = MutPtr::null().as_mut_ptr();
+
+		match writer.error {
+			Errors::None => result,
+			Errors::Err(err) => Err(err),
+			Errors::Panic(panic) => resume_unwind(panic)
+		}
+	}
+}
@@ -29,6 +29,7 @@ use xx_core::pointer::*;
 use xx_pulse::*;
 
 use crate::format::*;
+use crate::muxer::Sink;
 use crate::reader::*;
 use crate::{FormatError, Rational};
 
@@ -36,6 +37,7 @@ pub const UNKNOWN_TIMESTAMP: i64 = AV_NOPTS_VALUE;
 pub const INPUT_BUFFER_PADDING: usize = AV_INPUT_BUFFER_PADDING_SIZE as usize;
 pub const TIME_BASE: u32 = AV_TIME_BASE as u32;
 
+mod buffer;
 mod codec;
 mod conv;
 mod defs;
@@ -46,10 +48,13 @@ mod format;
 mod frame;
 mod io;
 mod macros;
+mod mux;
 mod opt;
 mod packet;
 mod parser;
+mod resample;
 
+pub use buffer::AVBufferRef;
 pub use codec::*;
 pub use defs::*;
 pub use error::*;
@@ -57,9 +62,11 @@ pub use filter::*;
 pub use filters::*;
 pub use format::*;
 pub use frame::AVFrame;
+pub use mux::*;
 pub use opt::*;
 pub use packet::AVPacket;
 pub use parser::*;
+pub use resample::*;
 
 use self::conv::*;
 use self::io::*;
@@ -0,0 +1,194 @@
+use super::*;
+use crate::codec::CodecParams;
+
+pub struct MuxContext(MutNonNull<AVFormatContext>, IoContext);
+
+ptr_deref!(MuxContext, AVFormatContext);
+
+impl Drop for MuxContext {
+	fn drop(&mut self) {
+		/* the io context is ours, and is freed after */
+		self.pb = MutPtr::null().as_mut_ptr();
+
+		ffi!(avformat_free_context, self.as_mut_ptr());
+	}
+}
+
+impl MuxContext {
+	/// Create a muxer for the output format named `name`, such as `webm`
+	pub fn new(name: &str) -> Result<Self> {
+		let name = CString::new(name).map_err(|_| ErrorKind::InvalidInput)?;
+		let mut ptr = MutPtr::null().as_mut_ptr();
+
+		ffi!(
+			avformat_alloc_output_context2,
+			&mut ptr,
+			Ptr::null().as_ptr(),
+			name.as_ptr(),
+			Ptr::null().as_ptr()
+		)?;
+
+		let ptr = MutNonNull::new(ptr.into()).ok_or(AVError::MuxerNotFound)?;
+		let mut this = Self(ptr, IoContext::new_output());
+
+		this.pb = this.1.as_mut_ptr();
+		this.flags |= AVFMT_FLAG_CUSTOM_IO;
+
+		Ok(this)
+	}
+
+	fn stream(&self, index: u32) -> &AVStream {
+		assert!(index < self.nb_streams);
+
+		let stream_ptr = ptr!(self.streams);
+
+		#[allow(clippy::multiple_unsafe_ops_per_block)]
+		/* Safety: index is less than nb_streams */
+		unsafe {
+			ptr!(ptr!(*stream_ptr.add(index as usize))).as_ref()
+		}
+	}
+
+	/// The time base the muxer chose for the stream at `index`.
+	/// Only final once the header is written
+	///
+	/// # Panics
+	/// if `index` is out of range
+	pub fn stream_time_base(&self, index: u32) -> Rational {
+		self.stream(index).time_base.into()
+	}
+
+	/// Add a stream described by `params`, with timestamps in `time_base`
+	#[allow(clippy::unwrap_used, clippy::missing_panics_doc)]
+	pub fn add_stream(&mut self, params: &CodecParams, time_base: Rational) -> Result<u32> {
+		let ptr = self.as_mut_ptr();
+		let mut stream = alloc_with(|| ffi!(avformat_new_stream, ptr, Ptr::null().as_ptr()));
+
+		/* Safety: the stream is valid */
+		let stream = unsafe { stream.as_mut() };
+
+		stream.time_base = time_base.into();
+
+		/* Safety: codecpar is allocated with the stream */
+		let par = unsafe { ptr!(stream.codecpar).as_mut() };
+
+		par.codec_type = params.ty.into();
+		par.codec_id = params.id.into();
+		par.bit_rate = params.bit_rate.into();
+		par.bits_per_raw_sample = params.bit_depth.into();
+		par.seek_preroll = params.seek_preroll.try_into().unwrap();
+
+		if !params.config.is_empty() {
+			let len = params.config.len();
+			let size = len.checked_add(INPUT_BUFFER_PADDING).unwrap();
+			let extradata: MutNonNull<u8> = alloc_with(|| ffi!(av_mallocz, size)).cast();
+
+			/* Safety: the allocation is at least `len` bytes */
+			let data = unsafe {
+				MutPtr::slice_from_raw_parts(extradata.as_mut_ptr().into(), len).as_mut()
+			};
+
+			data.copy_from_slice(&params.config);
+
+			par.extradata = extradata.as_mut_ptr();
+			par.extradata_size = len.try_into().unwrap();
+		}
+
+		match params.ty {
+			MediaType::Audio => {
+				par.format = params.sample_format as i32;
+				par.sample_rate = params.sample_rate.try_into().unwrap();
+				par.ch_layout = (&params.ch_layout).into();
+				par.frame_size = params.frame_size.try_into().unwrap();
+
+				/* muxers expect the padding in samples */
+				let samples = |value: u32| {
+					if params.time_base.num == 0 || params.sample_rate == 0 {
+						u64::from(value)
+					} else {
+						Rational::inverse(params.sample_rate)
+							.rescale(u64::from(value), params.time_base)
+					}
+				};
+
+				par.initial_padding = samples(params.delay).try_into().unwrap();
+				par.trailing_padding = samples(params.padding).try_into().unwrap();
+			}
+
+			MediaType::Video => {
+				par.format = params.pixel_format as i32;
+				par.width = params.width.try_into().unwrap();
+				par.height = params.height.try_into().unwrap();
+				par.sample_aspect_ratio = params.sample_aspect_ratio.into();
+				par.framerate = params.framerate.into();
+				par.video_delay = params.delay.try_into().unwrap();
+			}
+
+			_ => ()
+		}
+
+		Ok(self.nb_streams.checked_sub(1).unwrap())
+	}
+
+	/// Set a metadata entry on the output
+	pub fn set_metadata(&mut self, key: &str, value: &str) -> Result<()> {
+		let key = CString::new(key).map_err(|_| ErrorKind::InvalidInput)?;
+		let value = CString::new(value).map_err(|_| ErrorKind::InvalidInput)?;
+
+		ffi!(
+			av_dict_set,
+			&mut self.metadata,
+			key.as_ptr(),
+			value.as_ptr(),
+			0
+		)?;
+
+		Ok(())
+	}
+}
+
+#[asynchronous]
+impl MuxContext {
+	pub async fn write_header(&mut self, sink: &mut Sink) -> Result<()> {
+		let ptr = self.as_mut_ptr();
+		let write = |_: &mut IoContext| async move {
+			ffi!(avformat_write_header, ptr, MutPtr::null().as_mut_ptr())?;
+
+			Ok(())
+		};
+
+		let mut adapter = OutputAdapter::new(&mut self.1, sink);
+
+		adapter.with(write).await
+	}
+
+	/// Write `packet`, interleaving it with the other streams.
+	/// The packet is unreferenced
+	pub async fn write_packet(&mut self, packet: &mut AVPacket, sink: &mut Sink) -> Result<()> {
+		let ptr = self.as_mut_ptr();
+		let write = |_: &mut IoContext| async move {
+			ffi!(av_interleaved_write_frame, ptr, packet.as_mut_ptr())?;
+
+			Ok(())
+		};
+
+		let mut adapter = OutputAdapter::new(&mut self.1, sink);
+
+		adapter.with(write).await
+	}
+
+	/// Flush any interleaved packets and write the trailer
+	pub async fn write_trailer(&mut self, sink: &mut Sink) -> Result<()> {
+		let ptr = self.as_mut_ptr();
+		let write = |io: &mut IoContext| async move {
+			ffi!(av_write_trailer, ptr)?;
+			ffi!(avio_flush, io.as_mut_ptr());
+
+			Ok(())
+		};
+
+		let mut adapter = OutputAdapter::new(&mut self.1, sink);
+
+		adapter.with(write).await
+	}
+}
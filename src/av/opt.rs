@@ -235,6 +235,30 @@ impl OptionSetter for ChannelLayout {
 	}
 }
 
+/// The kind of value an option holds
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OptionKind {
+	Int,
+	Float,
+	Bool,
+	Rational,
+
+	/// Set from a string, such as strings, lists and formats
+	Other
+}
+
+impl OptionKind {
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Int => "integer",
+			Self::Float => "float",
+			Self::Bool => "boolean",
+			Self::Rational => "rational",
+			Self::Other => "string"
+		}
+	}
+}
+
 pub struct Object<'a>(MutNonNull<()>, PhantomData<&'a ()>);
 
 impl Object<'_> {
@@ -255,4 +279,40 @@ impl Object<'_> {
 		/* Safety: guaranteed by caller */
 		unsafe { T::set_c(self, option, value) }
 	}
+
+	/// The kind of `option`, or `None` if it does not exist
+	///
+	/// # Safety
+	/// the pointer passed to this `Object` is valid
+	pub unsafe fn find(&self, option: &CStr) -> Option<OptionKind> {
+		let found = ffi!(
+			av_opt_find,
+			self.0.as_mut_ptr().cast(),
+			option.as_ptr(),
+			Ptr::null().as_ptr(),
+			0,
+			AV_OPT_SEARCH_CHILDREN
+		);
+
+		/* Safety: options are static */
+		let found = unsafe { found.as_ref() }?;
+
+		#[allow(clippy::wildcard_enum_match_arm)]
+		Some(match found.type_ {
+			AVOptionType::AV_OPT_TYPE_FLAGS |
+			AVOptionType::AV_OPT_TYPE_INT |
+			AVOptionType::AV_OPT_TYPE_INT64 |
+			AVOptionType::AV_OPT_TYPE_UINT64 |
+			AVOptionType::AV_OPT_TYPE_DURATION => OptionKind::Int,
+			AVOptionType::AV_OPT_TYPE_DOUBLE | AVOptionType::AV_OPT_TYPE_FLOAT => OptionKind::Float,
+			AVOptionType::AV_OPT_TYPE_BOOL => OptionKind::Bool,
+			AVOptionType::AV_OPT_TYPE_RATIONAL | AVOptionType::AV_OPT_TYPE_VIDEO_RATE => {
+				OptionKind::Rational
+			}
+
+			/* named values of another option */
+			AVOptionType::AV_OPT_TYPE_CONST => return None,
+			_ => OptionKind::Other
+		})
+	}
 }
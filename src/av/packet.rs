@@ -1,4 +1,7 @@
+use std::ptr::null_mut;
+
 use super::*;
+use crate::packet::*;
 
 av_wrapper!(
 	AVPacket,
@@ -16,4 +19,175 @@ impl AVPacket {
 	pub fn data(&self) -> Ptr<[u8]> {
 		Ptr::slice_from_raw_parts(self.data.cast_const().into(), self.size.try_into().unwrap())
 	}
+
+	/// The side data of type `ty`, if the packet has any
+	#[allow(clippy::missing_panics_doc, clippy::unwrap_used)]
+	pub fn side_data(&self, ty: AVPacketSideDataType) -> Option<Ptr<[u8]>> {
+		let mut size = 0;
+		let data = ffi!(av_packet_get_side_data, self.as_ptr(), ty, &mut size);
+
+		if data.is_null() {
+			return None;
+		}
+
+		Some(Ptr::slice_from_raw_parts(data.cast_const().into(), size))
+	}
+
+	/// A new reference to the packet's buffer, if it is ref counted
+	pub fn buffer(&self) -> Option<AVBufferRef> {
+		if self.buf.is_null() {
+			return None;
+		}
+
+		/* Safety: the buffer is valid */
+		Some(unsafe { AVBufferRef::from_ref(self.buf) })
+	}
+
+	/// Point the packet at `data`, referencing `buffer` if it holds the data
+	#[allow(clippy::missing_panics_doc, clippy::unwrap_used)]
+	pub fn set_data(&mut self, buffer: Option<&AVBufferRef>, data: &[u8]) {
+		self.buf = buffer.map_or(null_mut(), |buffer| buffer.clone().into_raw());
+		self.data = data.as_ptr().cast_mut();
+		self.size = data.len().try_into().unwrap();
+	}
+
+	/// The samples to skip from the start and end of the decoded packet
+	#[allow(clippy::missing_panics_doc, clippy::unwrap_used)]
+	pub fn skip_samples(&self) -> (u32, u32) {
+		let Some(skip) = self.side_data(AVPacketSideDataType::AV_PKT_DATA_SKIP_SAMPLES) else {
+			return (0, 0);
+		};
+
+		/* Safety: side data is valid while the packet is referenced */
+		let skip = unsafe { skip.as_ref() };
+		let read = |bytes: Option<&[u8]>| {
+			bytes.map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+		};
+
+		(read(skip.get(0..4)), read(skip.get(4..8)))
+	}
+
+	/// The side data of the packet that has a typed equivalent
+	pub fn read_side_data(&self) -> Vec<SideData> {
+		let mut side_data = Vec::new();
+
+		if let Some(data) = self.side_data(AVPacketSideDataType::AV_PKT_DATA_NEW_EXTRADATA) {
+			/* Safety: side data is valid while the packet is referenced */
+			side_data.push(SideData::NewExtradata(unsafe { data.as_ref() }.to_vec()));
+		}
+
+		if let Some(data) = self.side_data(AVPacketSideDataType::AV_PKT_DATA_REPLAYGAIN) {
+			/* Safety: side data is valid while the packet is referenced */
+			if let Some(gain) = parse_replay_gain(unsafe { data.as_ref() }) {
+				side_data.push(SideData::ReplayGain(gain));
+			}
+		}
+
+		let addition = AVPacketSideDataType::AV_PKT_DATA_MATROSKA_BLOCKADDITIONAL;
+
+		if let Some(data) = self.side_data(addition) {
+			/* Safety: side data is valid while the packet is referenced */
+			let data = unsafe { data.as_ref() };
+
+			/* a big endian id, followed by the data */
+			if let Some((id, data)) = data.split_first_chunk::<8>() {
+				side_data.push(SideData::BlockAdditional {
+					id: u64::from_be_bytes(*id),
+					data: data.to_vec()
+				});
+			}
+		}
+
+		if let Some(data) = self.side_data(AVPacketSideDataType::AV_PKT_DATA_ENCRYPTION_INFO) {
+			/* Safety: side data is valid while the packet is referenced */
+			if let Some(info) = parse_encryption_info(unsafe { data.as_ref() }) {
+				side_data.push(SideData::Encryption(Box::new(info)));
+			}
+		}
+
+		side_data
+	}
+}
+
+/// Parse an `AVReplayGain`
+#[allow(clippy::cast_precision_loss)]
+fn parse_replay_gain(data: &[u8]) -> Option<ReplayGain> {
+	/* gains are in microbels, and peaks in units of 1/100000 */
+	const SCALE: f32 = 100_000.0;
+
+	let read = |offset: usize| {
+		data.get(offset..offset.checked_add(4)?)
+			.and_then(|bytes| bytes.try_into().ok())
+	};
+
+	let gain = |offset| {
+		read(offset)
+			.map(i32::from_ne_bytes)
+			.filter(|gain| *gain != i32::MIN)
+			.map(|gain| gain as f32 / SCALE)
+	};
+
+	let peak = |offset| {
+		read(offset)
+			.map(u32::from_ne_bytes)
+			.filter(|peak| *peak != 0)
+			.map(|peak| peak as f32 / SCALE)
+	};
+
+	if data.len() < 16 {
+		return None;
+	}
+
+	Some(ReplayGain {
+		track_gain: gain(0),
+		track_peak: peak(4),
+		album_gain: gain(8),
+		album_peak: peak(12)
+	})
+}
+
+fn read_be_bytes<'a>(data: &'a [u8], pos: &mut usize, size: usize) -> Option<&'a [u8]> {
+	let end = pos.checked_add(size)?;
+	let bytes = data.get(*pos..end)?;
+
+	*pos = end;
+
+	Some(bytes)
+}
+
+fn read_be_u32(data: &[u8], pos: &mut usize) -> Option<u32> {
+	Some(u32::from_be_bytes(
+		read_be_bytes(data, pos, 4)?.try_into().ok()?
+	))
+}
+
+/// Parse encryption info side data. Every field is big endian
+fn parse_encryption_info(data: &[u8]) -> Option<EncryptionInfo> {
+	let mut pos = 0;
+	let scheme = read_be_u32(data, &mut pos)?;
+	let crypt_byte_block = read_be_u32(data, &mut pos)?;
+	let skip_byte_block = read_be_u32(data, &mut pos)?;
+	let key_id_size = read_be_u32(data, &mut pos)?;
+	let iv_size = read_be_u32(data, &mut pos)?;
+	let subsample_count = read_be_u32(data, &mut pos)?;
+
+	let key_id = read_be_bytes(data, &mut pos, key_id_size.try_into().ok()?)?.to_vec();
+	let iv = read_be_bytes(data, &mut pos, iv_size.try_into().ok()?)?.to_vec();
+	let mut subsamples = Vec::new();
+
+	for _ in 0..subsample_count {
+		let clear_bytes = read_be_u32(data, &mut pos)?;
+		let protected_bytes = read_be_u32(data, &mut pos)?;
+
+		subsamples.push(Subsample { clear_bytes, protected_bytes });
+	}
+
+	Some(EncryptionInfo {
+		scheme,
+		crypt_byte_block,
+		skip_byte_block,
+		key_id,
+		iv,
+		subsamples
+	})
 }
@@ -0,0 +1,106 @@
+use super::*;
+
+av_wrapper!(SwrContext, ffmpeg_sys_next::SwrContext, swr_free, swr_alloc);
+
+impl SwrContext {
+	options! {
+		in_ch_layout: &ChannelLayout = c"in_chlayout",
+		in_sample_fmt: SampleFormat = c"in_sample_fmt",
+		in_sample_rate: i64 = c"in_sample_rate",
+		out_ch_layout: &ChannelLayout = c"out_chlayout",
+		out_sample_fmt: SampleFormat = c"out_sample_fmt",
+		out_sample_rate: i64 = c"out_sample_rate",
+		dither_method: i32 = c"dither_method",
+		filter_size: i32 = c"filter_size",
+		phase_shift: i32 = c"phase_shift",
+		linear_interp: bool = c"linear_interp",
+		cutoff: f64 = c"cutoff"
+	}
+
+	#[allow(clippy::needless_pass_by_ref_mut)]
+	pub fn options(&mut self) -> Object<'_> {
+		Object::from(self.0)
+	}
+
+	/// Initialize the context once the options are set
+	pub fn init(&mut self) -> Result<()> {
+		ffi!(swr_init, self.as_mut_ptr())?;
+
+		Ok(())
+	}
+
+	/// Set the remixing matrix, where output channel `o` takes
+	/// `matrix[o * stride + i]` of input channel `i`. Must be
+	/// called before [`Self::init`]
+	pub fn set_matrix(&mut self, matrix: &[f64], stride: usize) -> Result<()> {
+		#[allow(clippy::unwrap_used)]
+		ffi!(
+			swr_set_matrix,
+			self.as_mut_ptr(),
+			matrix.as_ptr(),
+			stride.try_into().unwrap()
+		)?;
+
+		Ok(())
+	}
+
+	/// The samples buffered in the context, in samples
+	/// at `sample_rate`, rounded up
+	#[allow(clippy::cast_sign_loss)]
+	pub fn delay(&self, sample_rate: u32) -> u64 {
+		/* Safety: FFI call */
+		let delay = unsafe { swr_get_delay(self.as_ptr().cast_mut(), sample_rate.into()) };
+
+		delay.max(0) as u64
+	}
+
+	/// Drop the next `samples` output samples
+	pub fn drop_output(&mut self, samples: u32) -> Result<()> {
+		#[allow(clippy::unwrap_used)]
+		ffi!(
+			swr_drop_output,
+			self.as_mut_ptr(),
+			samples.try_into().unwrap()
+		)?;
+
+		Ok(())
+	}
+
+	/// Add `samples` samples of silence to the input
+	pub fn inject_silence(&mut self, samples: u32) -> Result<()> {
+		#[allow(clippy::unwrap_used)]
+		ffi!(
+			swr_inject_silence,
+			self.as_mut_ptr(),
+			samples.try_into().unwrap()
+		)?;
+
+		Ok(())
+	}
+
+	/// Convert `input` into `output`, configuring the context from the
+	/// two frames if needed. Buffers are allocated for `output` if it
+	/// has none
+	pub fn convert_frame(&mut self, output: &mut AVFrame, input: &AVFrame) -> Result<()> {
+		ffi!(
+			swr_convert_frame,
+			self.as_mut_ptr(),
+			output.as_mut_ptr(),
+			input.as_ptr()
+		)?;
+
+		Ok(())
+	}
+
+	/// Convert the samples left in the context into `output`
+	pub fn flush_frame(&mut self, output: &mut AVFrame) -> Result<()> {
+		ffi!(
+			swr_convert_frame,
+			self.as_mut_ptr(),
+			output.as_mut_ptr(),
+			Ptr::null().as_ptr()
+		)?;
+
+		Ok(())
+	}
+}
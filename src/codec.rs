@@ -58,6 +58,14 @@ pub struct CodecParams {
 	pub delay: u32,
 	pub seek_preroll: u32,
 
+	/// Samples of encoder padding at the end of the stream,
+	/// in `time_base` like `delay`
+	pub padding: u32,
+
+	/// The number of samples left after removing `delay` and `padding`,
+	/// if the container records it
+	pub valid_samples: Option<u64>,
+
 	pub sample_rate: u32,
 	pub ch_layout: ChannelLayout,
 	pub frame_size: u32,
@@ -75,6 +83,7 @@ impl CodecParams {
 	pub fn change_time_base(&mut self, time_base: Rational) {
 		self.delay = time_base.rescale(self.delay as u64, self.time_base) as u32;
 		self.seek_preroll = time_base.rescale(self.seek_preroll as u64, self.time_base) as u32;
+		self.padding = time_base.rescale(self.padding as u64, self.time_base) as u32;
 		self.time_base = time_base;
 	}
 }
@@ -150,6 +159,12 @@ impl Codec {
 	}
 }
 
+impl From<Box<dyn CodecImpl + Send + Sync>> for Codec {
+	fn from(value: Box<dyn CodecImpl + Send + Sync>) -> Self {
+		Self(value)
+	}
+}
+
 pub trait CodecParserImpl {
 	fn id(&self) -> CodecId;
 
@@ -14,12 +14,42 @@ pub struct AVCodec {
 	frame: AVFrame
 }
 
+/// Replace a sample format or rate that the encoder does not accept
+fn negotiate_encoder(codec: NonNull<ffmpeg_sys_next::AVCodec>, params: &mut CodecParams) {
+	let formats = Codecs::sample_formats(codec);
+
+	if !formats.contains(&params.sample_format) {
+		if let Some(format) = formats.first() {
+			params.sample_format = *format;
+		}
+	}
+
+	let rates = Codecs::sample_rates(codec);
+
+	if !rates.is_empty() && !rates.contains(&params.sample_rate) {
+		/* the closest rate that loses no bandwidth, if any */
+		let higher = rates
+			.iter()
+			.copied()
+			.filter(|rate| *rate >= params.sample_rate)
+			.min();
+
+		if let Some(rate) = higher.or_else(|| rates.iter().copied().max()) {
+			params.sample_rate = rate;
+		}
+	}
+}
+
 #[allow(clippy::unwrap_used, clippy::missing_panics_doc)]
 fn open_codec(
 	codec: NonNull<ffmpeg_sys_next::AVCodec>, params: &mut CodecParams, mode: Mode
 ) -> Result<CodecContext> {
 	let mut context = CodecContext::new(codec);
 
+	if mode == Mode::Encode {
+		negotiate_encoder(codec, params);
+	}
+
 	params.config.reserve_exact(INPUT_BUFFER_PADDING);
 
 	for spare in params
@@ -65,6 +95,19 @@ fn open_codec(
 
 	result?;
 
+	/* encoders create the config muxers need, such as the opus header */
+	if mode == Mode::Encode && !context.extradata.is_null() {
+		/* Safety: extradata is valid for extradata_size bytes */
+		params.config = unsafe {
+			MutPtr::slice_from_raw_parts(
+				context.extradata.into(),
+				context.extradata_size.try_into().unwrap()
+			)
+			.as_ref()
+			.to_vec()
+		};
+	}
+
 	params.time_base = context.time_base.into();
 	params.bit_rate = context.bit_rate.try_into().unwrap();
 	params.bit_depth = context.bits_per_raw_sample.try_into().unwrap();
@@ -108,15 +151,18 @@ impl CodecImpl for AVCodec {
 	#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_wrap)]
 	fn send_packet(&mut self, packet: &Packet) -> Result<()> {
 		self.packet.time_base = packet.time_base.into();
-		self.packet.data = packet.data.as_ptr().cast_mut().cast();
-		self.packet.size = packet.data.len().try_into().unwrap();
-		self.packet.dts = packet.timestamp;
-		self.packet.pts = packet.timestamp - self.context.delay as i64;
+		self.packet.set_data(packet.data.buffer(), &packet.data);
+		self.packet.pts = packet.presentation_timestamp;
+		self.packet.dts = packet.decode_timestamp;
 		self.packet.duration = packet.duration.try_into().unwrap();
 		self.packet.flags = packet.flags.bits() as i32;
 
 		/* Safety: all data is valid */
-		unsafe { self.context.send_packet(&self.packet) }
+		let result = unsafe { self.context.send_packet(&self.packet) };
+
+		self.packet.unref();
+
+		result
 	}
 
 	fn send_frame(&mut self, frame: &Frame) -> Result<()> {
@@ -138,11 +184,10 @@ impl CodecImpl for AVCodec {
 			return Ok(false);
 		}
 
-		/* Safety: non-null */
-		packet.data = unsafe { self.packet.data().as_ref().to_vec() };
-		packet.timestamp = self.packet.dts;
-		packet.duration = self.packet.duration.try_into().unwrap();
-		packet.flags = BitFlags::from_bits_truncate(self.packet.flags as u32);
+		packet.read_from_av(&self.packet);
+		packet.time_base = self.context.time_base.into();
+		packet.presentation_timestamp = self.packet.pts;
+		packet.decode_timestamp = self.packet.dts;
 
 		self.packet.unref();
 
@@ -205,8 +250,8 @@ impl CodecParserImpl for AVCodecParser {
 		if !self.parser.parse(
 			&mut self.codec,
 			&packet.data,
-			packet.timestamp,
-			packet.timestamp,
+			packet.presentation_timestamp,
+			packet.decode_timestamp,
 			-1,
 			&mut duration
 		) {
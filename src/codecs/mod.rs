@@ -17,7 +17,9 @@ pub use aac::*;
 pub use flac::*;
 pub use mp2::*;
 pub use mp3::*;
-pub use opus::{OpusDecoder, OpusEncoder, OpusParser};
+pub use opus::{
+	OpusApplication, OpusDecoder, OpusEncoder, OpusEncoderSettings, OpusHead, OpusParser
+};
 pub use vorbis::*;
 
 macro_rules! codec_pair {
@@ -0,0 +1,670 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use ::opus::{Channels, Decoder};
+
+use super::*;
+
+/// The longest duration of an opus packet, in samples at 48 kHz
+const MAX_PACKET_SAMPLES: u32 = 5760;
+
+/// The shortest duration of an opus frame, in samples at 48 kHz
+const MIN_FRAME_SAMPLES: u32 = 120;
+
+/// Channel layouts of mapping family 1, by channel count
+const VORBIS_LAYOUTS: &[ChannelLayout] = &[
+	ChannelLayout::LAYOUT_MONO,
+	ChannelLayout::LAYOUT_STEREO,
+	ChannelLayout::LAYOUT_SURROUND,
+	ChannelLayout::LAYOUT_QUAD,
+	ChannelLayout::LAYOUT_5POINT0_BACK,
+	ChannelLayout::LAYOUT_5POINT1_BACK,
+	ChannelLayout::LAYOUT_6POINT1,
+	ChannelLayout::LAYOUT_7POINT1
+];
+
+/// Where each channel in vorbis order goes in the native order
+/// of the matching layout
+const VORBIS_ORDER: &[&[u8]] = &[
+	&[0],
+	&[0, 1],
+	&[0, 2, 1],
+	&[0, 1, 2, 3],
+	&[0, 2, 1, 3, 4],
+	&[0, 2, 1, 4, 5, 3],
+	&[0, 2, 1, 5, 6, 4, 3],
+	&[0, 2, 1, 6, 7, 4, 5, 3]
+];
+
+#[allow(clippy::arithmetic_side_effects)]
+fn read_length(data: &[u8], pos: &mut usize) -> Option<usize> {
+	let first = *data.get(*pos)?;
+
+	if first < 252 {
+		*pos = pos.checked_add(1)?;
+
+		return Some(first.into());
+	}
+
+	let second = *data.get(pos.checked_add(1)?)?;
+
+	*pos = pos.checked_add(2)?;
+
+	Some(usize::from(second) * 4 + usize::from(first))
+}
+
+/// Convert the self delimited packet at the start of `data` to
+/// the standard framing. Returns the packet and the bytes used
+///
+/// Every stream but the last of a multistream packet is self delimited,
+/// with an extra length for the frame that is normally implied
+#[allow(clippy::arithmetic_side_effects)]
+fn split_self_delimited(data: &[u8]) -> Option<(Vec<u8>, usize)> {
+	let toc = *data.first()?;
+	let mut pos = 1;
+
+	let (extra_start, extra_end, size) = match toc & 0x3 {
+		0 => {
+			let start = pos;
+			let size = read_length(data, &mut pos)?;
+
+			(start, pos, size)
+		}
+
+		1 => {
+			let start = pos;
+			let size = read_length(data, &mut pos)?;
+
+			(start, pos, size * 2)
+		}
+
+		2 => {
+			let first = read_length(data, &mut pos)?;
+			let start = pos;
+			let second = read_length(data, &mut pos)?;
+
+			(start, pos, first + second)
+		}
+
+		_ => {
+			let count = *data.get(pos)?;
+			let frames = usize::from(count & 0x3f);
+			let mut padding = 0;
+
+			pos += 1;
+
+			if count & 0x40 != 0 {
+				loop {
+					let value = *data.get(pos)?;
+
+					pos += 1;
+					padding += if value == 255 {
+						254
+					} else {
+						usize::from(value)
+					};
+
+					if value != 255 {
+						break;
+					}
+				}
+			}
+
+			let mut size = padding;
+
+			if count & 0x80 != 0 {
+				for _ in 1..frames {
+					size += read_length(data, &mut pos)?;
+				}
+
+				let start = pos;
+				let last = read_length(data, &mut pos)?;
+
+				(start, pos, size + last)
+			} else {
+				let start = pos;
+				let frame = read_length(data, &mut pos)?;
+
+				(start, pos, size + frame * frames)
+			}
+		}
+	};
+
+	let end = extra_end.checked_add(size)?;
+	let mut packet = data.get(..extra_start)?.to_vec();
+
+	packet.extend_from_slice(data.get(extra_end..end)?);
+
+	Some((packet, end))
+}
+
+struct StreamDecoder {
+	decoder: Exclusive<Decoder>,
+	channels: usize,
+	output: Vec<f32>
+}
+
+/// Decodes opus with libopus, through the `opus` crate
+///
+/// Mapping families 0, 1 and 255 are supported, including multistream
+/// layouts. The pre-skip is reported as the codec delay, and removed
+/// by [`GaplessTrimmer`] like the delay of any other codec
+///
+/// A packet with no data marks lost audio. It is concealed, using the
+/// forward error correction data of the next packet if it has any
+pub struct OpusDecoder {
+	head: OpusHead,
+	ch_layout: ChannelLayout,
+
+	/// The position of each channel of the header in the output
+	order: Vec<usize>,
+	streams: Vec<StreamDecoder>,
+
+	/// Samples lost since the last packet
+	lost: u32,
+	last_samples: u32,
+
+	frames: VecDeque<Frame>
+}
+
+impl OpusDecoder {
+	#[allow(
+		clippy::missing_panics_doc,
+		clippy::arithmetic_side_effects,
+		clippy::cast_possible_truncation
+	)]
+	pub fn new(params: &mut CodecParams) -> Result<Box<dyn CodecImpl + Send + Sync>> {
+		let head = if params.config.is_empty() {
+			/* raw streams have no header, so the channels must be known */
+			let channels = params.ch_layout.channel_count().clamp(1, 2);
+
+			OpusHead::new(channels as u8, 0, 0)
+		} else {
+			OpusHead::parse(&params.config)?
+		};
+
+		let channels = usize::from(head.channels);
+		let (ch_layout, order) = match head.mapping_family {
+			0 | 1 if channels <= VORBIS_LAYOUTS.len() => (
+				VORBIS_LAYOUTS[channels - 1].clone(),
+				VORBIS_ORDER[channels - 1]
+					.iter()
+					.map(|index| usize::from(*index))
+					.collect()
+			),
+
+			255 | 1 => (
+				ChannelLayout::Unspec(channels as u16),
+				(0..channels).collect()
+			),
+			family => return Err(OpusError::UnsupportedMapping(family).into())
+		};
+
+		let mut streams = Vec::new();
+
+		for index in 0..head.streams {
+			let (mode, channels) = if index < head.coupled_streams {
+				(Channels::Stereo, 2)
+			} else {
+				(Channels::Mono, 1)
+			};
+
+			let mut decoder = Decoder::new(SAMPLE_RATE, mode).map_err(OpusError::from)?;
+
+			decoder
+				.set_gain(head.output_gain.into())
+				.map_err(OpusError::from)?;
+
+			streams.push(StreamDecoder {
+				decoder: Exclusive::new(decoder),
+				channels,
+				output: Vec::new()
+			});
+		}
+
+		params.sample_rate = SAMPLE_RATE;
+		params.ch_layout = ch_layout.clone();
+		params.sample_format = SampleFormat::F32;
+		params.change_time_base(Rational::inverse(SAMPLE_RATE));
+
+		if params.delay == 0 {
+			params.delay = head.pre_skip.into();
+		}
+
+		Ok(Box::new(Self {
+			head,
+			ch_layout,
+			order,
+			streams,
+			lost: 0,
+			last_samples: 0,
+			frames: VecDeque::new()
+		}))
+	}
+
+	/// The stream and channel within it that decode `index`
+	fn locate(&self, index: u8) -> Option<(usize, usize)> {
+		let index = usize::from(index);
+		let coupled = usize::from(self.head.coupled_streams);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		match index {
+			255 => None,
+			index if index < coupled * 2 => Some((index / 2, index % 2)),
+			index => Some((index - coupled, 0))
+		}
+	}
+
+	/// Decode `samples` samples from `data`. No data conceals lost audio
+	#[allow(clippy::arithmetic_side_effects)]
+	fn decode(&mut self, data: &[u8], fec: bool, samples: u32, timestamp: i64) -> Result<()> {
+		let count = samples as usize;
+		let mut rest = data;
+		let last = self.streams.len() - 1;
+
+		for (index, stream) in self.streams.iter_mut().enumerate() {
+			let packet = if data.is_empty() || index == last {
+				Cow::Borrowed(rest)
+			} else {
+				let (packet, used) = split_self_delimited(rest).ok_or(OpusError::InvalidPacket)?;
+
+				rest = &rest[used..];
+
+				Cow::Owned(packet)
+			};
+
+			stream.output.resize(count * stream.channels, 0.0);
+
+			let decoded = stream
+				.decoder
+				.get_mut()
+				.decode_float(&packet, &mut stream.output, fec)
+				.map_err(OpusError::from)?;
+
+			if decoded != count {
+				return Err(OpusError::InvalidPacket.into());
+			}
+		}
+
+		let mut frame = Frame::audio(SampleFormat::F32, &self.ch_layout, SAMPLE_RATE, samples)?;
+
+		let channels = self.order.len();
+		let output = frame.sample_plane_mut::<f32>(0)?;
+
+		for (channel, index) in self.head.mapping.iter().enumerate() {
+			let position = self.order[channel];
+
+			let Some((stream, offset)) = self.locate(*index) else {
+				for sample in 0..count {
+					output[sample * channels + position] = 0.0;
+				}
+
+				continue;
+			};
+
+			let stream = &self.streams[stream];
+
+			for sample in 0..count {
+				output[sample * channels + position] =
+					stream.output[sample * stream.channels + offset];
+			}
+		}
+
+		frame.time_base = Rational::inverse(SAMPLE_RATE);
+		frame.presentation_timestamp = timestamp;
+		frame.best_effort_timestamp = timestamp;
+		frame.duration = samples.into();
+
+		self.frames.push_back(frame);
+
+		Ok(())
+	}
+
+	/// Conceal the lost audio before a packet of `samples` samples
+	/// ending at `timestamp`
+	#[allow(clippy::arithmetic_side_effects)]
+	fn conceal(&mut self, data: &[u8], samples: u32, timestamp: i64) -> Result<()> {
+		let fec = self.lost.min(samples) / MIN_FRAME_SAMPLES * MIN_FRAME_SAMPLES;
+		let mut remaining = (self.lost - fec) / MIN_FRAME_SAMPLES * MIN_FRAME_SAMPLES;
+		let start = |remaining: u32| {
+			if timestamp == UNKNOWN_TIMESTAMP {
+				timestamp
+			} else {
+				timestamp.saturating_sub(i64::from(remaining))
+			}
+		};
+
+		self.lost = 0;
+
+		while remaining != 0 {
+			let chunk = remaining.min(MAX_PACKET_SAMPLES);
+
+			self.decode(&[], false, chunk, start(remaining + fec))?;
+
+			remaining -= chunk;
+		}
+
+		if fec != 0 {
+			self.decode(data, true, fec, start(fec))?;
+		}
+
+		Ok(())
+	}
+}
+
+impl CodecImpl for OpusDecoder {
+	fn id(&self) -> CodecId {
+		CodecId::Opus
+	}
+
+	fn send_packet(&mut self, packet: &Packet) -> Result<()> {
+		let sample_base = Rational::inverse(SAMPLE_RATE);
+		let timestamp = if packet.time_base.num != 0 {
+			sample_base.rescale_timestamp(
+				packet.presentation_timestamp,
+				packet.time_base,
+				Rounding::NearInf
+			)
+		} else {
+			UNKNOWN_TIMESTAMP
+		};
+
+		if packet.data.is_empty() {
+			#[allow(clippy::cast_possible_truncation)]
+			let samples = if packet.duration != 0 && packet.time_base.num != 0 {
+				sample_base.rescale_rnd(packet.duration, packet.time_base, Rounding::NearInf) as u32
+			} else {
+				self.last_samples
+			};
+
+			self.lost = self.lost.saturating_add(samples);
+
+			return Ok(());
+		}
+
+		let samples = get_nb_samples(&packet.data, SAMPLE_RATE)?;
+
+		if self.lost != 0 {
+			self.conceal(&packet.data, samples, timestamp)?;
+		}
+
+		self.last_samples = samples;
+		self.decode(&packet.data, false, samples, timestamp)
+	}
+
+	fn send_frame(&mut self, _: &Frame) -> Result<()> {
+		Err(ErrorKind::InvalidInput.into())
+	}
+
+	fn receive_packet(&mut self, _: &mut Packet) -> Result<bool> {
+		Err(ErrorKind::InvalidInput.into())
+	}
+
+	fn receive_frame(&mut self, frame: &mut Frame) -> Result<bool> {
+		let Some(decoded) = self.frames.pop_front() else {
+			return Ok(false);
+		};
+
+		*frame = decoded;
+
+		Ok(true)
+	}
+
+	fn drain(&mut self) -> Result<()> {
+		Ok(())
+	}
+
+	fn flush(&mut self) -> Result<()> {
+		for stream in &mut self.streams {
+			stream
+				.decoder
+				.get_mut()
+				.reset_state()
+				.map_err(OpusError::from)?;
+		}
+
+		self.lost = 0;
+		self.frames.clear();
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+#[allow(
+	clippy::unwrap_used,
+	clippy::panic,
+	clippy::arithmetic_side_effects,
+	clippy::cast_possible_truncation,
+	clippy::cast_precision_loss
+)]
+mod tests {
+	use ::opus::{Application, Encoder};
+	use ffmpeg_sys_next::{
+		AV_CH_BACK_CENTER, AV_CH_BACK_LEFT, AV_CH_BACK_RIGHT, AV_CH_FRONT_CENTER, AV_CH_FRONT_LEFT,
+		AV_CH_FRONT_RIGHT, AV_CH_LOW_FREQUENCY, AV_CH_SIDE_LEFT, AV_CH_SIDE_RIGHT
+	};
+
+	use super::*;
+
+	const FRAME_SAMPLES: usize = 960;
+
+	/// Prefix the frame of a single frame packet with its length
+	fn self_delimit(packet: &[u8]) -> Vec<u8> {
+		assert_eq!(packet[0] & 0x3, 0);
+
+		let len = packet.len() - 1;
+		let mut out = vec![packet[0]];
+
+		if len < 252 {
+			out.push(len as u8);
+		} else {
+			let first = 252 + (len & 0x3);
+
+			out.extend_from_slice(&[first as u8, ((len - first) / 4) as u8]);
+		}
+
+		out.extend_from_slice(&packet[1..]);
+		out
+	}
+
+	#[test]
+	fn split_code_0() {
+		let data = [0x00, 3, 1, 2, 3, 9, 9];
+		let (packet, used) = split_self_delimited(&data).unwrap();
+
+		assert_eq!(packet, [0x00, 1, 2, 3]);
+		assert_eq!(used, 5);
+	}
+
+	#[test]
+	fn split_code_1() {
+		let data = [0x01, 2, 1, 2, 3, 4, 9];
+		let (packet, used) = split_self_delimited(&data).unwrap();
+
+		assert_eq!(packet, [0x01, 1, 2, 3, 4]);
+		assert_eq!(used, 6);
+	}
+
+	#[test]
+	fn split_code_2() {
+		let data = [0x02, 1, 2, 1, 2, 3, 9];
+		let (packet, used) = split_self_delimited(&data).unwrap();
+
+		assert_eq!(packet, [0x02, 1, 1, 2, 3]);
+		assert_eq!(used, 6);
+	}
+
+	#[test]
+	fn split_code_3() {
+		/* three cbr frames of two bytes, with one byte of padding */
+		let data = [0x03, 0x43, 1, 2, 1, 2, 3, 4, 5, 6, 0, 9];
+		let (packet, used) = split_self_delimited(&data).unwrap();
+
+		assert_eq!(packet, [0x03, 0x43, 1, 1, 2, 3, 4, 5, 6, 0]);
+		assert_eq!(used, 11);
+
+		/* two vbr frames */
+		let data = [0x03, 0x82, 1, 2, 7, 8, 9, 9];
+		let (packet, used) = split_self_delimited(&data).unwrap();
+
+		assert_eq!(packet, [0x03, 0x82, 1, 7, 8, 9]);
+		assert_eq!(used, 7);
+	}
+
+	#[test]
+	fn split_two_byte_length() {
+		let mut data = self_delimit(&[0x00; 301]);
+
+		assert_eq!(&data[1..3], [252, 12]);
+
+		data.push(9);
+
+		let (packet, used) = split_self_delimited(&data).unwrap();
+
+		assert_eq!(packet.len(), 301);
+		assert_eq!(used, data.len() - 1);
+	}
+
+	#[test]
+	fn split_truncated() {
+		assert!(split_self_delimited(&[]).is_none());
+		assert!(split_self_delimited(&[0x00, 4, 1, 2]).is_none());
+		assert!(split_self_delimited(&[0x03]).is_none());
+	}
+
+	/// Decode `channels` mono streams in vorbis order, with a tone in
+	/// only one of them, and return the loudest output channel
+	fn loudest_channel(channels: u8, toned: u8) -> usize {
+		let mut head = OpusHead::new(channels, 0, SAMPLE_RATE);
+
+		head.mapping_family = 1;
+		head.streams = channels;
+		head.coupled_streams = 0;
+
+		let mut params = CodecParams::default();
+
+		params.config = head.to_bytes();
+
+		let mut decoder = OpusDecoder::new(&mut params).unwrap();
+		let mut encoders: Vec<_> = (0..channels)
+			.map(|_| Encoder::new(SAMPLE_RATE, Channels::Mono, Application::Audio).unwrap())
+			.collect();
+
+		let tone: Vec<f32> = (0..FRAME_SAMPLES)
+			.map(|index| (index as f32 * 0.06).sin() * 0.5)
+			.collect();
+
+		let silence = vec![0.0; FRAME_SAMPLES];
+		let mut frame = Frame::new();
+
+		for _ in 0..2 {
+			let mut data = Vec::new();
+
+			for (index, encoder) in encoders.iter_mut().enumerate() {
+				let input = if index == usize::from(toned) {
+					&tone
+				} else {
+					&silence
+				};
+
+				let packet = encoder.encode_vec_float(input, 4000).unwrap();
+
+				if index + 1 == usize::from(channels) {
+					data.extend_from_slice(&packet);
+				} else {
+					data.extend_from_slice(&self_delimit(&packet));
+				}
+			}
+
+			let mut packet = Packet::new();
+
+			packet.data = data.into();
+			decoder.send_packet(&packet).unwrap();
+
+			assert!(decoder.receive_frame(&mut frame).unwrap());
+		}
+
+		let output = frame.sample_plane::<f32>(0).unwrap();
+		let energy = |channel: usize| {
+			output
+				.iter()
+				.skip(channel)
+				.step_by(channels.into())
+				.map(|sample| sample * sample)
+				.sum::<f32>()
+		};
+
+		(0..channels.into())
+			.max_by(|a, b| energy(*a).total_cmp(&energy(*b)))
+			.unwrap()
+	}
+
+	/// Check that each channel in vorbis order lands on its
+	/// position in the native order of `layout`
+	fn check_order(layout: &ChannelLayout, vorbis: &[u64]) {
+		let ChannelLayout::Native(_, mask) = layout else {
+			panic!("Expected a native layout");
+		};
+
+		for (index, channel) in vorbis.iter().enumerate() {
+			let position = (mask & (channel - 1)).count_ones() as usize;
+
+			assert_eq!(
+				loudest_channel(vorbis.len() as u8, index as u8),
+				position,
+				"channel {} of {}",
+				index,
+				vorbis.len()
+			);
+		}
+	}
+
+	#[test]
+	fn surround_5_1_order() {
+		check_order(
+			&ChannelLayout::LAYOUT_5POINT1_BACK,
+			&[
+				AV_CH_FRONT_LEFT,
+				AV_CH_FRONT_CENTER,
+				AV_CH_FRONT_RIGHT,
+				AV_CH_BACK_LEFT,
+				AV_CH_BACK_RIGHT,
+				AV_CH_LOW_FREQUENCY
+			]
+		);
+	}
+
+	#[test]
+	fn surround_6_1_order() {
+		check_order(
+			&ChannelLayout::LAYOUT_6POINT1,
+			&[
+				AV_CH_FRONT_LEFT,
+				AV_CH_FRONT_CENTER,
+				AV_CH_FRONT_RIGHT,
+				AV_CH_SIDE_LEFT,
+				AV_CH_SIDE_RIGHT,
+				AV_CH_BACK_CENTER,
+				AV_CH_LOW_FREQUENCY
+			]
+		);
+	}
+
+	#[test]
+	fn surround_7_1_order() {
+		check_order(
+			&ChannelLayout::LAYOUT_7POINT1,
+			&[
+				AV_CH_FRONT_LEFT,
+				AV_CH_FRONT_CENTER,
+				AV_CH_FRONT_RIGHT,
+				AV_CH_SIDE_LEFT,
+				AV_CH_SIDE_RIGHT,
+				AV_CH_BACK_LEFT,
+				AV_CH_BACK_RIGHT,
+				AV_CH_LOW_FREQUENCY
+			]
+		);
+	}
+}
@@ -0,0 +1,488 @@
+use std::collections::VecDeque;
+use std::ffi::c_int;
+use std::time::Duration;
+
+/* the `opus` crate links libopus through these bindings, but wraps
+ * neither the complexity nor DTX controls, so the encoder is driven
+ * directly
+ */
+use audiopus_sys as ffi;
+use xx_core::pointer::*;
+
+use super::*;
+
+/// The largest packet libopus produces
+const MAX_PACKET_SIZE: usize = 1275 * 3 + 7;
+
+/// Decoders need 80 ms to converge after a seek
+const SEEK_PREROLL: u32 = 3840;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OpusApplication {
+	/// Best for speech
+	Voip,
+
+	/// Best for music and mixed content
+	#[default]
+	Audio,
+
+	/// Lowest delay, disabling the speech modes
+	LowDelay
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpusEncoderSettings {
+	pub application: OpusApplication,
+
+	/// Bits per second, or `None` to let the encoder pick
+	pub bitrate: Option<u32>,
+
+	/// Vary the size of packets to keep the quality constant
+	pub vbr: bool,
+
+	/// From 0 to 10. Higher is slower, with better quality
+	pub complexity: u8,
+
+	/// The duration of each packet, from 2.5 to 120 ms in
+	/// steps of 2.5 ms. Packets over 20 ms hold multiple frames
+	pub frame_duration: Duration,
+
+	/// Send fewer packets during silence
+	pub dtx: bool,
+
+	/// Add data to recover the previous packet if it is lost
+	pub inband_fec: bool,
+
+	/// The expected packet loss, in percent. Tunes `inband_fec`
+	pub packet_loss: u8
+}
+
+impl Default for OpusEncoderSettings {
+	fn default() -> Self {
+		Self {
+			application: OpusApplication::Audio,
+			bitrate: None,
+			vbr: true,
+			complexity: 10,
+			frame_duration: Duration::from_millis(20),
+			dtx: false,
+			inband_fec: false,
+			packet_loss: 0
+		}
+	}
+}
+
+/// An owned libopus encoder. Every method takes `&mut self`,
+/// as libopus states are not safe to use from two threads at once
+struct RawEncoder(MutNonNull<ffi::OpusEncoder>);
+
+impl RawEncoder {
+	fn check(code: c_int) -> Result<c_int> {
+		if code < 0 {
+			Err(OpusError::Library(code).into())
+		} else {
+			Ok(code)
+		}
+	}
+
+	fn new(channels: c_int, application: c_int) -> Result<Self> {
+		let mut error = 0;
+
+		#[allow(clippy::cast_possible_wrap)]
+		/* Safety: FFI call */
+		let encoder = unsafe {
+			ffi::opus_encoder_create(SAMPLE_RATE as i32, channels, application, &mut error)
+		};
+
+		Self::check(error)?;
+
+		let encoder = MutNonNull::new(encoder.into()).ok_or(OpusError::Library(error))?;
+
+		Ok(Self(encoder))
+	}
+
+	fn set(&mut self, request: c_int, value: c_int) -> Result<()> {
+		/* Safety: every request set here takes an int */
+		Self::check(unsafe { ffi::opus_encoder_ctl(self.0.as_mut_ptr(), request, value) })?;
+
+		Ok(())
+	}
+
+	fn lookahead(&mut self) -> Result<u32> {
+		let mut lookahead: c_int = 0;
+
+		/* Safety: the request takes an int pointer */
+		Self::check(unsafe {
+			ffi::opus_encoder_ctl(
+				self.0.as_mut_ptr(),
+				ffi::OPUS_GET_LOOKAHEAD_REQUEST,
+				ptr!(&mut lookahead).as_mut_ptr()
+			)
+		})?;
+
+		lookahead
+			.try_into()
+			.map_err(|_| OpusError::Library(lookahead).into())
+	}
+
+	fn reset(&mut self) -> Result<()> {
+		/* Safety: the request takes no arguments */
+		Self::check(unsafe { ffi::opus_encoder_ctl(self.0.as_mut_ptr(), ffi::OPUS_RESET_STATE) })?;
+
+		Ok(())
+	}
+
+	#[allow(clippy::unwrap_used, clippy::cast_sign_loss)]
+	fn encode(&mut self, pcm: &[f32], frame_size: usize, data: &mut [u8]) -> Result<usize> {
+		/* Safety: pcm holds `frame_size` samples of every channel */
+		let size = unsafe {
+			ffi::opus_encode_float(
+				self.0.as_mut_ptr(),
+				pcm.as_ptr(),
+				frame_size.try_into().unwrap(),
+				data.as_mut_ptr(),
+				data.len().try_into().unwrap()
+			)
+		};
+
+		Ok(Self::check(size)? as usize)
+	}
+}
+
+impl Drop for RawEncoder {
+	fn drop(&mut self) {
+		/* Safety: we own the encoder */
+		unsafe { ffi::opus_encoder_destroy(self.0.as_mut_ptr()) };
+	}
+}
+
+/* Safety: the encoder state is plain memory owned by `RawEncoder`, with
+ * no thread local or thread affine state in libopus, so it may be used and
+ * freed on any thread. Sharing is left to `Exclusive`
+ */
+unsafe impl Send for RawEncoder {}
+
+/// Encodes mono or stereo opus at 48 kHz with libopus
+///
+/// Input frames must be packed `F32` in the layout and rate of the
+/// codec parameters. They are cut into packets of the frame duration.
+/// Draining pads the input with silence to flush out the lookahead,
+/// and the samples past the end of the input are recorded in the
+/// `trim_end` of the final packet
+pub struct OpusEncoder {
+	encoder: Exclusive<RawEncoder>,
+	channels: usize,
+	frame_size: usize,
+	delay: u32,
+
+	/// Interleaved samples waiting for a full packet
+	pending: Vec<f32>,
+
+	/// The timestamp of the first pending sample
+	next_timestamp: Option<i64>,
+
+	/// Samples of each channel sent and encoded so far
+	samples_in: u64,
+	samples_out: u64,
+	packets: VecDeque<Packet>,
+	output: Vec<u8>
+}
+
+impl OpusEncoder {
+	pub fn new(params: &mut CodecParams) -> Result<Box<dyn CodecImpl + Send + Sync>> {
+		let mut settings = OpusEncoderSettings::default();
+
+		if params.bit_rate != 0 {
+			settings.bitrate = Some(params.bit_rate);
+		}
+
+		if params.frame_size != 0 && params.sample_rate != 0 {
+			let rate = Rational::inverse(params.sample_rate);
+
+			settings.frame_duration =
+				Duration::from_nanos(Rational::nanos().rescale(params.frame_size.into(), rate));
+		}
+
+		Self::with_settings(params, &settings)
+	}
+
+	/// An encoder with the options libopus offers. The sample rate
+	/// of `params` is always set to 48 kHz
+	#[allow(clippy::missing_panics_doc, clippy::unwrap_used)]
+	pub fn with_settings(
+		params: &mut CodecParams, settings: &OpusEncoderSettings
+	) -> Result<Box<dyn CodecImpl + Send + Sync>> {
+		let channels = params.ch_layout.channel_count();
+
+		/* surround needs the multistream encoder, which is not wrapped here */
+		if !(1..=2).contains(&channels) {
+			return Err(ErrorKind::Unsupported.into());
+		}
+
+		#[allow(clippy::cast_possible_truncation)]
+		let frame_size = Rational::inverse(SAMPLE_RATE)
+			.rescale(settings.frame_duration.as_nanos() as u64, Rational::nanos());
+
+		#[allow(clippy::arithmetic_side_effects)]
+		if frame_size == 0 ||
+			frame_size % 120 != 0 ||
+			frame_size > 5760 ||
+			settings.complexity > 10 ||
+			settings.packet_loss > 100
+		{
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let application = match settings.application {
+			OpusApplication::Voip => ffi::OPUS_APPLICATION_VOIP,
+			OpusApplication::Audio => ffi::OPUS_APPLICATION_AUDIO,
+			OpusApplication::LowDelay => ffi::OPUS_APPLICATION_RESTRICTED_LOWDELAY
+		};
+
+		let mut encoder = RawEncoder::new(channels.into(), application)?;
+		let bitrate = match settings.bitrate {
+			Some(bitrate) => bitrate.try_into().map_err(|_| ErrorKind::Overflow)?,
+			None => ffi::OPUS_AUTO
+		};
+
+		encoder.set(ffi::OPUS_SET_BITRATE_REQUEST, bitrate)?;
+		encoder.set(ffi::OPUS_SET_VBR_REQUEST, settings.vbr.into())?;
+		encoder.set(ffi::OPUS_SET_COMPLEXITY_REQUEST, settings.complexity.into())?;
+		encoder.set(ffi::OPUS_SET_DTX_REQUEST, settings.dtx.into())?;
+		encoder.set(ffi::OPUS_SET_INBAND_FEC_REQUEST, settings.inband_fec.into())?;
+		encoder.set(
+			ffi::OPUS_SET_PACKET_LOSS_PERC_REQUEST,
+			settings.packet_loss.into()
+		)?;
+
+		let delay = encoder.lookahead()?;
+		let input_sample_rate = params.sample_rate;
+
+		params.id = CodecId::Opus;
+		params.ty = MediaType::Audio;
+		params.sample_rate = SAMPLE_RATE;
+		params.sample_format = SampleFormat::F32;
+		params.ch_layout = ChannelLayout::get_default_for_count(channels);
+		params.time_base = Rational::inverse(SAMPLE_RATE);
+		params.frame_size = frame_size.try_into().unwrap();
+		params.delay = delay;
+		params.seek_preroll = SEEK_PREROLL;
+		params.bit_rate = settings.bitrate.unwrap_or(0);
+
+		#[allow(clippy::cast_possible_truncation)]
+		(params.config =
+			OpusHead::new(channels as u8, delay.try_into().unwrap(), input_sample_rate).to_bytes());
+
+		Ok(Box::new(Self {
+			encoder: Exclusive::new(encoder),
+			channels: channels.into(),
+			frame_size: frame_size.try_into().unwrap(),
+			delay,
+			pending: Vec::new(),
+			next_timestamp: None,
+			samples_in: 0,
+			samples_out: 0,
+			packets: VecDeque::new(),
+			output: vec![0; MAX_PACKET_SIZE]
+		}))
+	}
+
+	/// Encode one packet from the start of the pending samples
+	#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_wrap)]
+	fn encode_packet(&mut self, padding: u32) -> Result<()> {
+		let values = self.frame_size * self.channels;
+		let size = self.encoder.get_mut().encode(
+			&self.pending[..values],
+			self.frame_size,
+			&mut self.output
+		)?;
+
+		let timestamp = self.next_timestamp.unwrap_or(0);
+		let mut packet = Packet::new();
+
+		packet.data = self.output[..size].into();
+		packet.time_base = Rational::inverse(SAMPLE_RATE);
+		packet.presentation_timestamp = timestamp - i64::from(self.delay);
+		packet.decode_timestamp = packet.presentation_timestamp;
+		packet.duration = self.frame_size as u64;
+		packet.flags = PacketFlag::Keyframe.into();
+		packet.trim_end = padding;
+
+		self.next_timestamp = Some(timestamp + self.frame_size as i64);
+		self.samples_out += self.frame_size as u64;
+		self.pending.drain(..values);
+		self.packets.push_back(packet);
+
+		Ok(())
+	}
+}
+
+impl CodecImpl for OpusEncoder {
+	fn id(&self) -> CodecId {
+		CodecId::Opus
+	}
+
+	fn send_packet(&mut self, _: &Packet) -> Result<()> {
+		Err(ErrorKind::InvalidInput.into())
+	}
+
+	#[allow(clippy::arithmetic_side_effects)]
+	fn send_frame(&mut self, frame: &Frame) -> Result<()> {
+		if frame.sample_rate != SAMPLE_RATE ||
+			frame.sample_format != SampleFormat::F32 ||
+			usize::from(frame.ch_layout.channel_count()) != self.channels
+		{
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		if self.next_timestamp.is_none() && frame.presentation_timestamp != UNKNOWN_TIMESTAMP {
+			let time_base = if frame.time_base.num != 0 {
+				frame.time_base
+			} else {
+				Rational::inverse(SAMPLE_RATE)
+			};
+
+			self.next_timestamp = Some(
+				Rational::inverse(SAMPLE_RATE).rescale(frame.presentation_timestamp, time_base)
+			);
+		}
+
+		self.pending
+			.extend_from_slice(frame.sample_plane::<f32>(0)?);
+		self.samples_in += u64::from(frame.samples);
+
+		while self.pending.len() >= self.frame_size * self.channels {
+			self.encode_packet(0)?;
+		}
+
+		Ok(())
+	}
+
+	fn receive_packet(&mut self, packet: &mut Packet) -> Result<bool> {
+		let Some(encoded) = self.packets.pop_front() else {
+			return Ok(false);
+		};
+
+		*packet = encoded;
+
+		Ok(true)
+	}
+
+	fn receive_frame(&mut self, _: &mut Frame) -> Result<bool> {
+		Err(ErrorKind::InvalidInput.into())
+	}
+
+	#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+	fn drain(&mut self) -> Result<()> {
+		/* decoders drop the lookahead from the start, so the input
+		 * only ends `delay` samples after the last one sent
+		 */
+		let end = if self.samples_in != 0 {
+			self.samples_in + u64::from(self.delay)
+		} else {
+			0
+		};
+
+		while self.samples_out < end {
+			let values = self.frame_size * self.channels;
+
+			self.pending.resize(values.max(self.pending.len()), 0.0);
+
+			let padding = (self.samples_out + self.frame_size as u64).saturating_sub(end);
+
+			self.encode_packet(padding as u32)?;
+		}
+
+		Ok(())
+	}
+
+	fn flush(&mut self) -> Result<()> {
+		self.encoder.get_mut().reset()?;
+		self.pending.clear();
+		self.packets.clear();
+		self.next_timestamp = None;
+		self.samples_in = 0;
+		self.samples_out = 0;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::arithmetic_side_effects)]
+mod tests {
+	use std::mem::take;
+
+	use super::*;
+
+	fn encoder() -> (Box<dyn CodecImpl + Send + Sync>, CodecParams) {
+		let mut params = CodecParams::default();
+
+		params.sample_rate = SAMPLE_RATE;
+		params.ch_layout = ChannelLayout::LAYOUT_MONO;
+
+		(OpusEncoder::new(&mut params).unwrap(), params)
+	}
+
+	fn receive_all(encoder: &mut dyn CodecImpl) -> Vec<Packet> {
+		let mut packets = Vec::new();
+		let mut packet = Packet::new();
+
+		while encoder.receive_packet(&mut packet).unwrap() {
+			packets.push(take(&mut packet));
+		}
+
+		packets
+	}
+
+	/// Encode and drain `samples` samples, checking that decoders keep
+	/// exactly the input once the delay and the trim are removed
+	fn check_trim(samples: u32) {
+		let (mut encoder, params) = encoder();
+		let mut frame =
+			Frame::audio(SampleFormat::F32, &params.ch_layout, SAMPLE_RATE, samples).unwrap();
+
+		frame.time_base = Rational::inverse(SAMPLE_RATE);
+		frame.presentation_timestamp = 0;
+
+		encoder.send_frame(&frame).unwrap();
+		encoder.drain().unwrap();
+
+		let packets = receive_all(encoder.as_mut());
+		let total: u64 = packets.iter().map(|packet| packet.duration).sum();
+		let trim: u64 = packets
+			.iter()
+			.map(|packet| u64::from(packet.trim_end))
+			.sum();
+
+		assert!(packets[..packets.len() - 1]
+			.iter()
+			.all(|packet| packet.trim_end == 0));
+		assert_eq!(total - u64::from(params.delay) - trim, u64::from(samples));
+		assert!(trim < u64::from(params.frame_size));
+
+		/* nothing is left to drain */
+		encoder.drain().unwrap();
+
+		assert!(receive_all(encoder.as_mut()).is_empty());
+	}
+
+	#[test]
+	fn drain_partial_packet() {
+		check_trim(1000);
+	}
+
+	#[test]
+	fn drain_whole_packets() {
+		check_trim(1920);
+	}
+
+	#[test]
+	fn drain_empty() {
+		let (mut encoder, _) = encoder();
+
+		encoder.drain().unwrap();
+
+		assert!(receive_all(encoder.as_mut()).is_empty());
+	}
+}
@@ -0,0 +1,276 @@
+use std::result;
+
+use super::*;
+
+mod decoder;
+mod encoder;
+
+pub use decoder::*;
+pub use encoder::*;
+
+use self::exclusive::*;
+
+pub const SAMPLE_RATE: u32 = 48_000;
+
+#[errors]
+pub enum OpusError {
+	#[display("Invalid opus packet")]
+	#[kind = ErrorKind::InvalidData]
+	InvalidPacket,
+
+	#[display("Invalid opus header")]
+	#[kind = ErrorKind::InvalidData]
+	InvalidHeader,
+
+	#[display("Unsupported channel mapping family {}", f0)]
+	#[kind = ErrorKind::Unsupported]
+	UnsupportedMapping(u8),
+
+	#[display("Opus library error {}", f0)]
+	Library(i32)
+}
+
+mod exclusive {
+	/// A value only reachable through exclusive references, like the
+	/// unstable `std::sync::Exclusive`. The libopus states are not
+	/// thread safe, but have no methods taking shared references either
+	pub(super) struct Exclusive<T>(T);
+
+	impl<T> Exclusive<T> {
+		pub(super) const fn new(value: T) -> Self {
+			Self(value)
+		}
+
+		pub(super) const fn get_mut(&mut self) -> &mut T {
+			&mut self.0
+		}
+	}
+
+	/* Safety: a shared reference gives no access to the value, so no two
+	 * threads can ever use it at once. Sending it still requires `T: Send`
+	 */
+	unsafe impl<T: Send> Sync for Exclusive<T> {}
+}
+
+impl From<::opus::Error> for OpusError {
+	fn from(err: ::opus::Error) -> Self {
+		Self::Library(err.code() as i32)
+	}
+}
+
+/// The parsed `OpusHead` identification header
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpusHead {
+	pub channels: u8,
+
+	/// Samples at 48 kHz to drop from the start of the decoded output
+	pub pre_skip: u16,
+	pub input_sample_rate: u32,
+
+	/// Gain to apply to the output, in Q7.8 dB
+	pub output_gain: i16,
+	pub mapping_family: u8,
+	pub streams: u8,
+	pub coupled_streams: u8,
+
+	/// The stream channel of each output channel. 255 is silence
+	pub mapping: Vec<u8>
+}
+
+impl OpusHead {
+	/// A header for a single stream with one or two channels
+	#[must_use]
+	pub fn new(channels: u8, pre_skip: u16, input_sample_rate: u32) -> Self {
+		Self {
+			channels,
+			pre_skip,
+			input_sample_rate,
+			output_gain: 0,
+			mapping_family: 0,
+			streams: 1,
+			coupled_streams: u8::from(channels > 1),
+			mapping: (0..channels).collect()
+		}
+	}
+
+	pub fn parse(config: &[u8]) -> result::Result<Self, OpusError> {
+		let header = config
+			.strip_prefix(b"OpusHead")
+			.ok_or(OpusError::InvalidHeader)?;
+
+		let [version, channels, p0, p1, r0, r1, r2, r3, g0, g1, mapping_family, rest @ ..] = header
+		else {
+			return Err(OpusError::InvalidHeader);
+		};
+
+		/* only the major version is incompatible */
+		if version >> 4 != 0 || *channels == 0 {
+			return Err(OpusError::InvalidHeader);
+		}
+
+		let mut head = Self::new(
+			*channels,
+			u16::from_le_bytes([*p0, *p1]),
+			u32::from_le_bytes([*r0, *r1, *r2, *r3])
+		);
+
+		head.output_gain = i16::from_le_bytes([*g0, *g1]);
+		head.mapping_family = *mapping_family;
+
+		if head.mapping_family == 0 {
+			if head.channels > 2 {
+				return Err(OpusError::InvalidHeader);
+			}
+
+			return Ok(head);
+		}
+
+		let [streams, coupled_streams, mapping @ ..] = rest else {
+			return Err(OpusError::InvalidHeader);
+		};
+
+		let mapping = mapping
+			.get(..*channels as usize)
+			.ok_or(OpusError::InvalidHeader)?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let stream_channels = u16::from(*streams) + u16::from(*coupled_streams);
+
+		if *streams == 0 ||
+			coupled_streams > streams ||
+			mapping
+				.iter()
+				.any(|index| *index != 255 && u16::from(*index) >= stream_channels)
+		{
+			return Err(OpusError::InvalidHeader);
+		}
+
+		head.streams = *streams;
+		head.coupled_streams = *coupled_streams;
+		head.mapping = mapping.to_vec();
+
+		Ok(head)
+	}
+
+	#[must_use]
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = b"OpusHead".to_vec();
+
+		bytes.extend_from_slice(&[1, self.channels]);
+		bytes.extend_from_slice(&self.pre_skip.to_le_bytes());
+		bytes.extend_from_slice(&self.input_sample_rate.to_le_bytes());
+		bytes.extend_from_slice(&self.output_gain.to_le_bytes());
+		bytes.push(self.mapping_family);
+
+		if self.mapping_family != 0 {
+			bytes.extend_from_slice(&[self.streams, self.coupled_streams]);
+			bytes.extend_from_slice(&self.mapping);
+		}
+
+		bytes
+	}
+}
+
+pub fn get_nb_frames(packet: &[u8]) -> result::Result<u32, OpusError> {
+	let [config, rest @ ..] = packet else {
+		return Err(OpusError::InvalidPacket);
+	};
+
+	#[allow(clippy::unreachable)]
+	Ok(match config & 0x3 {
+		0 => 1,
+		1 | 2 => 2,
+		3 => *rest.first().ok_or(OpusError::InvalidPacket)? as u32,
+		_ => unreachable!()
+	})
+}
+
+#[must_use]
+pub const fn get_samples_per_frame(config: u8, sample_rate: u32) -> u32 {
+	if config & 0x80 != 0 {
+		let audio_size = (config >> 3) & 0x3;
+
+		(sample_rate << audio_size) / 400
+	} else if config & 0x60 == 0x60 {
+		if config & 0x08 != 0 {
+			sample_rate / 50
+		} else {
+			sample_rate / 100
+		}
+	} else {
+		let audio_size = (config >> 3) & 0x3;
+
+		if audio_size == 3 {
+			#[allow(clippy::arithmetic_side_effects)]
+			(sample_rate * 60 / 1000)
+		} else {
+			(sample_rate << audio_size) / 100
+		}
+	}
+}
+
+#[allow(clippy::arithmetic_side_effects)]
+pub fn get_nb_samples(packet: &[u8], sample_rate: u32) -> result::Result<u32, OpusError> {
+	let frames = get_nb_frames(packet)?;
+	let samples = frames * get_samples_per_frame(packet[0], sample_rate);
+
+	if samples * 25 > sample_rate * 3 {
+		return Err(OpusError::InvalidPacket);
+	}
+
+	Ok(samples)
+}
+
+/// The pre-skip of an `OpusHead` identification header,
+/// in samples at 48 kHz
+#[must_use]
+pub fn get_pre_skip(config: &[u8]) -> Option<u16> {
+	let header = config.strip_prefix(b"OpusHead")?;
+
+	header.get(2..4)?.try_into().ok().map(u16::from_le_bytes)
+}
+
+pub struct OpusParser;
+
+impl OpusParser {
+	pub fn new(
+		_: CodecParse, params: &mut CodecParams
+	) -> Result<Box<dyn CodecParserImpl + Send + Sync>> {
+		params.sample_rate = SAMPLE_RATE;
+		params.change_time_base(Rational::inverse(SAMPLE_RATE));
+
+		if params.delay == 0 {
+			if let Some(pre_skip) = get_pre_skip(&params.config) {
+				params.delay = pre_skip.into();
+			}
+		}
+
+		Ok(Box::new(Self))
+	}
+}
+
+impl CodecParserImpl for OpusParser {
+	fn id(&self) -> CodecId {
+		CodecId::Opus
+	}
+
+	fn parse(&mut self, packet: &mut Packet) -> Result<()> {
+		if let Ok(samples) = get_nb_samples(&packet.data, SAMPLE_RATE) {
+			let new_timescale = Rational::inverse(SAMPLE_RATE);
+
+			let rescale = |timestamp: i64| {
+				new_timescale.rescale_timestamp(timestamp, packet.time_base, Rounding::NearInf)
+			};
+
+			packet.presentation_timestamp = rescale(packet.presentation_timestamp);
+			packet.decode_timestamp = rescale(packet.decode_timestamp);
+
+			packet.time_base = new_timescale;
+			packet.duration = samples as u64;
+
+			Ok(())
+		} else {
+			Err(FormatError::InvalidData("Invalid opus packet".into()).into())
+		}
+	}
+}
@@ -1,16 +1,49 @@
 #![allow(unreachable_pub)]
 
-use ffmpeg_sys_next::AVInputFormat;
+use ffmpeg_sys_next::AVSideDataParamChangeFlags::{
+	AV_SIDE_DATA_PARAM_CHANGE_DIMENSIONS, AV_SIDE_DATA_PARAM_CHANGE_SAMPLE_RATE
+};
+use ffmpeg_sys_next::{
+	AVCodecID, AVCodecParameters, AVInputFormat, AVPacketSideDataType, AVStream,
+	AVFMT_EVENT_FLAG_METADATA_UPDATED, AVSTREAM_EVENT_FLAG_METADATA_UPDATED
+};
 use xx_core::impls::UintExt;
 use xx_core::pointer::*;
 
 use super::*;
-use crate::av::{AVPacket, FormatContext, ProbeResult, TIME_BASE};
+use crate::av::{read_metadata, AVPacket, FormatContext, ProbeResult, TIME_BASE};
+
+/// The fields of a stream's parameters that signal a change
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct ParamsSnapshot {
+	codec_id: AVCodecID,
+	sample_rate: i32,
+	channels: i32,
+	width: i32,
+	height: i32,
+	extradata: usize,
+	extradata_size: i32
+}
+
+impl ParamsSnapshot {
+	fn new(params: &AVCodecParameters) -> Self {
+		Self {
+			codec_id: params.codec_id,
+			sample_rate: params.sample_rate,
+			channels: params.ch_layout.nb_channels,
+			width: params.width,
+			height: params.height,
+			extradata: params.extradata as usize,
+			extradata_size: params.extradata_size
+		}
+	}
+}
 
 struct AVDemuxer {
 	format: FormatContext,
 	reader: Reader,
-	packet: AVPacket
+	packet: AVPacket,
+	params: Vec<ParamsSnapshot>
 }
 
 #[asynchronous]
@@ -23,88 +56,256 @@ impl AVDemuxer {
 			.unwrap_or_default()
 			.as_ptr();
 
-		Self { format, reader, packet: AVPacket::new() }
+		Self {
+			format,
+			reader,
+			packet: AVPacket::new(),
+			params: Vec::new()
+		}
+	}
+
+	fn stream(&self, index: u32) -> &AVStream {
+		let stream_ptr = ptr!(self.format.streams);
+
+		#[allow(clippy::multiple_unsafe_ops_per_block)]
+		/* Safety: index is less than nb_streams */
+		unsafe {
+			ptr!(ptr!(*stream_ptr.add(index as usize))).as_ref()
+		}
+	}
+
+	fn stream_mut(&mut self, index: u32) -> &mut AVStream {
+		let stream_ptr = ptr!(self.format.streams);
+
+		#[allow(clippy::multiple_unsafe_ops_per_block)]
+		/* Safety: index is less than nb_streams */
+		unsafe {
+			ptr!(ptr!(*stream_ptr.add(index as usize))).as_mut()
+		}
 	}
-}
 
-#[asynchronous]
-impl DemuxerImpl for AVDemuxer {
 	#[allow(clippy::unwrap_used)]
-	async fn open(&mut self, context: &mut FormatData) -> Result<()> {
-		self.format.open(&mut self.reader).await?;
+	fn codec_params(params: &AVCodecParameters) -> (MediaType, CodecParams) {
+		let mut codec_params = CodecParams::default();
+
+		codec_params.id = params.codec_id.into();
+
+		if !params.extradata.is_null() {
+			/* Safety: extradata is valid for extradata_size bytes */
+			codec_params.config = unsafe {
+				MutPtr::slice_from_raw_parts(
+					params.extradata.into(),
+					params.extradata_size.try_into().unwrap()
+				)
+				.as_mut()
+				.to_vec()
+			};
+		}
 
-		context.start_time = self.format.start_time;
-		context.duration = self.format.duration.try_into().unwrap();
-		context.duration = context
-			.duration
-			.checked_sub_signed(context.start_time)
-			.unwrap();
-		context.time_base = Rational::inverse(TIME_BASE);
+		codec_params.bit_rate = params.bit_rate.try_into().unwrap();
+		codec_params.bit_depth = params.bits_per_raw_sample.try_into().unwrap();
+		codec_params.seek_preroll = params.seek_preroll.try_into().unwrap();
+
+		codec_params.ch_layout = (&params.ch_layout).into();
+		codec_params.sample_rate = params.sample_rate.try_into().unwrap();
+		codec_params.frame_size = params.frame_size.try_into().unwrap();
+
+		codec_params.width = params.width.try_into().unwrap();
+		codec_params.height = params.height.try_into().unwrap();
+		codec_params.sample_aspect_ratio = params.sample_aspect_ratio.into();
+		codec_params.framerate = params.framerate.into();
+
+		let ty = params.codec_type.into();
+
+		match ty {
+			MediaType::Video => codec_params.delay = params.video_delay.try_into().unwrap(),
+			MediaType::Audio => {
+				codec_params.delay = params.initial_padding.try_into().unwrap();
+				codec_params.padding = params.trailing_padding.try_into().unwrap();
+				codec_params.time_base = Rational::inverse(codec_params.sample_rate);
+			}
+
+			_ => ()
+		}
+
+		(ty, codec_params)
+	}
+
+	#[allow(clippy::unwrap_used)]
+	fn track(&self, index: u32) -> Track {
+		let stream = self.stream(index);
+
+		/* Safety: streams always have codec parameters */
+		let (ty, codec_params) = Self::codec_params(unsafe { ptr!(stream.codecpar).as_ref() });
+
+		let start_time = match stream.start_time {
+			UNKNOWN_TIMESTAMP => 0,
+			ts => ts
+		};
+
+		Track {
+			ty,
+			time_base: stream.time_base.into(),
+			codec_params,
+			id: stream.id.try_into().unwrap(),
+			start_time,
+			duration: stream.duration.try_into().unwrap_or(0),
+			/* Safety: metadata is null or valid */
+			metadata: unsafe { read_metadata(stream.metadata) },
+			..Default::default()
+		}
+	}
+
+	fn snapshot(&self, index: u32) -> ParamsSnapshot {
+		/* Safety: streams always have codec parameters */
+		ParamsSnapshot::new(unsafe { ptr!(self.stream(index).codecpar).as_ref() })
+	}
+
+	/// Report streams found while reading and metadata updates. Returns
+	/// the tracks whose codec parameters changed
+	#[allow(clippy::cast_possible_truncation)]
+	fn update_tracks(&mut self, context: &mut FormatData) -> Vec<u32> {
+		let mut changed = Vec::new();
+
+		if self.format.event_flags & AVFMT_EVENT_FLAG_METADATA_UPDATED != 0 {
+			self.format.event_flags &= !AVFMT_EVENT_FLAG_METADATA_UPDATED;
+
+			/* Safety: metadata is null or valid */
+			context.metadata = unsafe { read_metadata(self.format.metadata) };
+			context.push_event(FormatEvent::MetadataUpdated(None));
+		}
 
 		for index in 0..self.format.nb_streams {
-			let stream_ptr = ptr!(self.format.streams);
+			if index as usize >= context.tracks.len() {
+				context.add_track(self.track(index));
+				self.params.push(self.snapshot(index));
 
-			#[allow(clippy::multiple_unsafe_ops_per_block)]
-			/* Safety: FFI */
-			unsafe {
-				let stream = ptr!(ptr!(*stream_ptr.add(index as usize))).as_ref();
-				let params = ptr!(stream.codecpar).as_ref();
+				continue;
+			}
 
-				let mut codec_params = CodecParams::default();
+			let stream = self.stream_mut(index);
 
-				codec_params.id = params.codec_id.into();
+			if stream.event_flags & AVSTREAM_EVENT_FLAG_METADATA_UPDATED != 0 {
+				stream.event_flags &= !AVSTREAM_EVENT_FLAG_METADATA_UPDATED;
 
-				if !params.extradata.is_null() {
-					codec_params.config = MutPtr::slice_from_raw_parts(
-						params.extradata.into(),
-						params.extradata_size.try_into().unwrap()
-					)
-					.as_mut()
-					.to_vec();
-				}
+				/* Safety: metadata is null or valid */
+				let metadata = unsafe { read_metadata(stream.metadata) };
 
-				codec_params.bit_rate = params.bit_rate.try_into().unwrap();
-				codec_params.bit_depth = params.bits_per_raw_sample.try_into().unwrap();
-				codec_params.seek_preroll = params.seek_preroll.try_into().unwrap();
+				context.tracks[index as usize].metadata = metadata;
+				context.push_event(FormatEvent::MetadataUpdated(Some(index)));
+			}
+
+			let snapshot = self.snapshot(index);
 
-				codec_params.ch_layout = (&params.ch_layout).into();
-				codec_params.sample_rate = params.sample_rate.try_into().unwrap();
-				codec_params.frame_size = params.frame_size.try_into().unwrap();
+			if self.params[index as usize] != snapshot {
+				self.params[index as usize] = snapshot;
+				self.replace_codec_params(context, index);
+				changed.push(index);
+			}
+		}
 
-				codec_params.width = params.width.try_into().unwrap();
-				codec_params.height = params.height.try_into().unwrap();
-				codec_params.sample_aspect_ratio = params.sample_aspect_ratio.into();
-				codec_params.framerate = params.framerate.into();
+		changed
+	}
 
-				let ty = params.codec_type.into();
+	fn replace_codec_params(&self, context: &mut FormatData, index: u32) {
+		/* Safety: streams always have codec parameters */
+		let (_, codec_params) =
+			Self::codec_params(unsafe { ptr!(self.stream(index).codecpar).as_ref() });
 
-				match ty {
-					MediaType::Video => codec_params.delay = params.video_delay.try_into().unwrap(),
-					MediaType::Audio => {
-						codec_params.delay = params.initial_padding.try_into().unwrap();
-						codec_params.time_base = Rational::inverse(codec_params.sample_rate);
-					}
+		context.tracks[index as usize].codec_params = codec_params;
+	}
 
-					_ => ()
+	/// Apply parameter changes carried in the packet's side data.
+	/// Returns whether there were any
+	#[allow(clippy::unwrap_used)]
+	fn apply_side_data(&self, context: &mut FormatData, index: u32) -> bool {
+		let extradata = self
+			.packet
+			.side_data(AVPacketSideDataType::AV_PKT_DATA_NEW_EXTRADATA);
+		let param_change = self
+			.packet
+			.side_data(AVPacketSideDataType::AV_PKT_DATA_PARAM_CHANGE);
+
+		if extradata.is_none() && param_change.is_none() {
+			return false;
+		}
+
+		let track = &mut context.tracks[index as usize];
+
+		if let Some(extradata) = extradata {
+			/* Safety: side data is valid while the packet is referenced */
+			track.codec_params.config = unsafe { extradata.as_ref().to_vec() };
+		}
+
+		if let Some(data) = param_change {
+			/* Safety: side data is valid while the packet is referenced */
+			let data = unsafe { data.as_ref() };
+			let read = |offset: usize| {
+				data.get(offset..offset.checked_add(4)?)
+					.map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+			};
+
+			let flags = read(0).unwrap_or(0);
+			let mut offset = 4;
+
+			if flags & AV_SIDE_DATA_PARAM_CHANGE_SAMPLE_RATE as u32 != 0 {
+				if let Some(sample_rate) = read(offset) {
+					track.codec_params.sample_rate = sample_rate;
 				}
 
-				let start_time = match stream.start_time {
-					UNKNOWN_TIMESTAMP => 0,
-					ts => ts
-				};
-
-				context.tracks.push(Track {
-					ty,
-					time_base: stream.time_base.into(),
-					codec_params,
-					id: stream.id.try_into().unwrap(),
-					start_time,
-					duration: stream.duration.try_into().unwrap_or(0),
-					..Default::default()
-				});
+				offset = offset.saturating_add(4);
+			}
+
+			if flags & AV_SIDE_DATA_PARAM_CHANGE_DIMENSIONS as u32 != 0 {
+				let height = read(offset.saturating_add(4));
+
+				if let (Some(width), Some(height)) = (read(offset), height) {
+					track.codec_params.width = width;
+					track.codec_params.height = height;
+				}
 			}
 		}
 
+		true
+	}
+
+	/// Report each track in `changed` once, even if its parameters
+	/// changed in more than one way
+	fn push_params_changed(context: &mut FormatData, mut changed: Vec<u32>) {
+		changed.sort_unstable();
+		changed.dedup();
+
+		for index in changed {
+			context.push_event(FormatEvent::CodecParamsChanged(index));
+		}
+	}
+}
+
+#[asynchronous]
+impl DemuxerImpl for AVDemuxer {
+	#[allow(clippy::unwrap_used)]
+	async fn open(&mut self, context: &mut FormatData) -> Result<()> {
+		self.format.open(&mut self.reader).await?;
+
+		context.start_time = self.format.start_time;
+		context.duration = self.format.duration.try_into().unwrap();
+		context.duration = context
+			.duration
+			.checked_sub_signed(context.start_time)
+			.unwrap();
+		context.time_base = Rational::inverse(TIME_BASE);
+
+		/* Safety: metadata is null or valid */
+		context.metadata = unsafe { read_metadata(self.format.metadata) };
+
+		self.format.event_flags = 0;
+
+		for index in 0..self.format.nb_streams {
+			context.tracks.push(self.track(index));
+			self.params.push(self.snapshot(index));
+			self.stream_mut(index).event_flags = 0;
+		}
+
 		Ok(())
 	}
 
@@ -114,16 +315,17 @@ impl DemuxerImpl for AVDemuxer {
 	) -> Result<()> {
 		let track = &data.tracks[track_index as usize];
 
-		#[allow(clippy::unwrap_used)]
+		/* byte offsets and frame indices are not shifted by the start time */
+		let timecode = if flags.intersects(SeekFlag::Byte | SeekFlag::Frame) {
+			timecode
+		} else {
+			timecode
+				.checked_add_signed(track.start_time.checked_neg().ok_or(ErrorKind::Overflow)?)
+				.ok_or(ErrorKind::Overflow)?
+		};
+
 		self.format
-			.seek(
-				track_index,
-				timecode
-					.checked_add_signed(track.start_time.checked_neg().unwrap())
-					.unwrap(),
-				flags,
-				&mut self.reader
-			)
+			.seek(track_index, timecode, flags, &mut self.reader)
 			.await?;
 
 		Ok(())
@@ -132,15 +334,11 @@ impl DemuxerImpl for AVDemuxer {
 	#[allow(clippy::unwrap_used, clippy::cast_sign_loss)]
 	async fn read_packet(&mut self, context: &mut FormatData, packet: &mut Packet) -> Result<bool> {
 		for index in 0..self.format.nb_streams {
-			let stream_ptr = ptr!(self.format.streams);
-
-			#[allow(clippy::multiple_unsafe_ops_per_block)]
-			/* Safety: FFI */
-			unsafe {
-				let stream = ptr!(ptr!(*stream_ptr.add(index as usize)));
+			let Some(track) = context.tracks.get(index as usize) else {
+				break;
+			};
 
-				ptr!(stream=>discard) = context.tracks[index as usize].discard.into();
-			}
+			self.stream_mut(index).discard = track.discard.into();
 		}
 
 		if !self
@@ -148,21 +346,43 @@ impl DemuxerImpl for AVDemuxer {
 			.read_frame(&mut self.packet, &mut self.reader)
 			.await?
 		{
+			let changed = self.update_tracks(context);
+
+			Self::push_params_changed(context, changed);
+
 			return Ok(false);
 		}
 
+		let mut changed = self.update_tracks(context);
 		let index: usize = self.packet.stream_index.try_into().unwrap();
+
+		#[allow(clippy::cast_possible_truncation)]
+		if self.apply_side_data(context, index as u32) {
+			changed.push(index as u32);
+		}
+
+		Self::push_params_changed(context, changed);
+
 		let track = &context.tracks[index];
 
 		#[allow(clippy::cast_possible_truncation)]
 		(packet.track_index = index as u32);
 		packet.time_base = track.time_base;
 
-		/* Safety: non-null */
-		packet.data = unsafe { self.packet.data().as_ref().to_vec() };
-		packet.timestamp = self.packet.dts.checked_sub(track.start_time).unwrap();
-		packet.duration = self.packet.duration.try_into().unwrap_or(0);
-		packet.flags = BitFlags::from_bits_truncate(self.packet.flags as u32);
+		/* timestamps start at zero, not at the start time of the track */
+		let rebase = |timestamp: i64| {
+			if timestamp == UNKNOWN_TIMESTAMP {
+				Ok(timestamp)
+			} else {
+				timestamp
+					.checked_sub(track.start_time)
+					.ok_or(ErrorKind::Overflow)
+			}
+		};
+
+		packet.read_from_av(&self.packet);
+		packet.presentation_timestamp = rebase(self.packet.pts)?;
+		packet.decode_timestamp = rebase(self.packet.dts)?;
 
 		self.packet.unref();
 
@@ -179,7 +399,16 @@ impl AVFormatClass {
 		Box::new(AVDemuxer::new(reader, format))
 	}
 
-	pub async fn probe(reader: &mut Reader) -> Result<Option<ProbeResult>> {
-		FormatContext::probe(reader).await
+	pub async fn probe(reader: &mut Reader, hints: &ProbeHints) -> Result<Option<ProbeResult>> {
+		let file_name = hints
+			.extension()
+			.map(|extension| format!("input.{}", extension));
+		let mime_type = hints.mime_type();
+
+		FormatContext::probe(reader, file_name.as_deref(), mime_type.as_deref()).await
+	}
+
+	pub fn find(name: &str) -> Option<NonNull<AVInputFormat>> {
+		FormatContext::find_input_format(name)
 	}
 }
@@ -8,6 +8,7 @@ use xx_core::error::*;
 use xx_core::{trace, warn};
 
 use super::*;
+use crate::codecs::opus;
 
 mod ebml;
 mod spec;
@@ -60,6 +61,7 @@ struct Matroska {
 
 	seen_header: bool,
 	tracks: Option<Tracks>,
+	tracks_changed: bool,
 	seek_head: Option<SeekHead>,
 	cues: Option<Cues>,
 
@@ -82,6 +84,7 @@ impl Matroska {
 
 			seen_header: false,
 			tracks: None,
+			tracks_changed: false,
 			seek_head: None,
 			cues: None,
 
@@ -260,6 +263,7 @@ impl Matroska {
 				Segment::TRACKS_ID => {
 					self.trace_element("Tracks", &element);
 					self.tracks = Some(Tracks::parse(self, &element).await?);
+					self.tracks_changed = true;
 
 					stop = true;
 				}
@@ -328,6 +332,176 @@ impl Matroska {
 
 		Ok(())
 	}
+
+	/// Read the rest of a block group, returning its discard
+	/// padding and block additions
+	async fn read_block_group_tail(&mut self) -> Result<(Option<u64>, Vec<SideData>)> {
+		let mut discard_padding = None;
+		let mut side_data = Vec::new();
+
+		loop {
+			#[allow(clippy::arithmetic_side_effects)]
+			let master = self.stack[self.level - 1];
+
+			let Some(element) = master.next_element(&mut self.reader).await? else {
+				self.post_read(&master.element).await?;
+				self.stack_pop();
+
+				break;
+			};
+
+			if element.id == BlockGroup::DISCARD_PADDING_ID {
+				self.trace_element("DiscardPadding", &element);
+
+				/* negative padding is trimmed from the start, which the codec delay covers */
+				let padding = Signed::parse(self, &element).await?.0;
+
+				discard_padding = padding.try_into().ok();
+			}
+
+			if element.id == BlockGroup::ADDITIONS_ID {
+				self.trace_element("BlockAdditions", &element);
+
+				let additions = BlockAdditions::parse(self, &element).await?;
+
+				for more in additions.more {
+					side_data.push(SideData::BlockAdditional { id: more.id.0, data: more.data.0 });
+				}
+			}
+
+			self.post_read(&element).await?;
+		}
+
+		Ok((discard_padding, side_data))
+	}
+
+	/// The offset of the cluster to resume `track` from, chosen from the cues
+	fn cue_seek_offset(&self, track: u32, timecode: u64, flags: BitFlags<SeekFlag>) -> Result<u64> {
+		#[allow(clippy::unwrap_used)]
+		let track = &self.tracks.as_ref().unwrap().tracks[track as usize];
+		let cues = self.cues.as_ref().ok_or(FormatError::CannotSeek)?;
+
+		let points: Vec<_> = cues
+			.points
+			.iter()
+			.filter_map(|point| {
+				point
+					.track_positions
+					.iter()
+					.find(|pos| pos.track == track.number)
+					.map(|pos| (point.time.0, pos.cluster_position.0))
+			})
+			.collect();
+
+		/* without a cue before the target, start from the first cluster */
+		let position = pick_seek_point(&points, timecode, flags).map_or(0, |(_, pos)| pos);
+
+		Ok(self
+			.segment_offset
+			.checked_add(position)
+			.ok_or(ErrorKind::Overflow)?)
+	}
+
+	/// The offset of the cluster at or before the byte `offset`, or after it
+	/// for [`SeekFlag::Forward`], from the cues if there are any, or by
+	/// scanning for a cluster otherwise
+	async fn byte_seek_offset(&mut self, offset: u64, flags: BitFlags<SeekFlag>) -> Result<u64> {
+		if let Some(cues) = &self.cues {
+			let mut points: Vec<_> = cues
+				.points
+				.iter()
+				.flat_map(|point| &point.track_positions)
+				.filter_map(|pos| self.segment_offset.checked_add(pos.cluster_position.0))
+				.map(|pos| (pos, pos))
+				.collect();
+
+			points.sort_unstable();
+			points.dedup();
+
+			return Ok(
+				pick_seek_point(&points, offset, flags).map_or(self.segment_offset, |(_, pos)| pos)
+			);
+		}
+
+		if flags.intersects(SeekFlag::Forward) {
+			self.find_cluster_after(offset).await
+		} else {
+			self.find_cluster_before(offset).await
+		}
+	}
+
+	/// Read up to `len` bytes at `start`, stopping early at the end of the
+	/// stream
+	async fn read_window(&mut self, start: u64, len: usize) -> Result<Vec<u8>> {
+		let mut window = vec![0; len];
+		let mut read = 0;
+
+		self.reader.seek(SeekFrom::Start(start)).await?;
+
+		while read < len {
+			let amount = self.reader.read_partial(&mut window[read..]).await?;
+
+			if amount == 0 {
+				break;
+			}
+
+			#[allow(clippy::arithmetic_side_effects)]
+			(read += amount);
+		}
+
+		window.truncate(read);
+
+		Ok(window)
+	}
+
+	/// Find the first cluster starting at or after `offset`
+	#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+	async fn find_cluster_after(&mut self, offset: u64) -> Result<u64> {
+		let id = (Segment::CLUSTERS_ID as u32).to_be_bytes();
+		let mut start = offset.max(self.segment_offset);
+
+		loop {
+			let window = self.read_window(start, CLUSTER_SCAN_WINDOW).await?;
+
+			if let Some(index) = window.windows(id.len()).position(|bytes| bytes == id) {
+				return Ok(start + index as u64);
+			}
+
+			if window.len() < CLUSTER_SCAN_WINDOW {
+				return Err(FormatError::CannotSeek.into());
+			}
+
+			/* overlap windows so ids crossing the boundary are found */
+			start += (CLUSTER_SCAN_WINDOW - id.len() + 1) as u64;
+		}
+	}
+
+	/// Find the last cluster starting at or before `offset`, or
+	/// the start of the segment if there is none
+	#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+	async fn find_cluster_before(&mut self, offset: u64) -> Result<u64> {
+		let id = (Segment::CLUSTERS_ID as u32).to_be_bytes();
+		let mut end = offset.saturating_add(id.len() as u64);
+
+		while end > self.segment_offset {
+			let start = end
+				.saturating_sub(CLUSTER_SCAN_WINDOW as u64)
+				.max(self.segment_offset);
+			let window = self.read_window(start, (end - start) as usize).await?;
+
+			if let Some(index) = window.windows(id.len()).rposition(|bytes| bytes == id) {
+				return Ok(start + index as u64);
+			}
+
+			if start == self.segment_offset {
+				break;
+			}
+
+			end = start + id.len() as u64 - 1;
+		}
+
+		Ok(self.segment_offset)
+	}
 }
 
 impl Deref for Matroska {
@@ -387,6 +561,26 @@ impl EbmlReader for Matroska {
 	}
 }
 
+/// How far to read at a time when scanning for a cluster
+const CLUSTER_SCAN_WINDOW: usize = 64 * 1024;
+
+/// Choose among `(key, value)` points sorted by key, the last one at
+/// or before `target`, or the first one after it for [`SeekFlag::Forward`]
+fn pick_seek_point(
+	points: &[(u64, u64)], target: u64, flags: BitFlags<SeekFlag>
+) -> Option<(u64, u64)> {
+	if flags.intersects(SeekFlag::Forward) {
+		let after = points.partition_point(|(key, _)| *key < target);
+
+		return points.get(after).or(points.last()).copied();
+	}
+
+	points
+		.partition_point(|(key, _)| *key <= target)
+		.checked_sub(1)
+		.map(|index| points[index])
+}
+
 const fn get_track_type(ty: TrackType) -> MediaType {
 	match ty {
 		TrackType::Video => MediaType::Video,
@@ -451,9 +645,89 @@ fn get_track_codec_params(track: &tracks::Track) -> Result<CodecParams> {
 	params.delay = trunc(track.codec_delay.0)?;
 	params.seek_preroll = trunc(track.seek_preroll.0)?;
 
+	/* older muxers only record the delay in the opus header */
+	if params.delay == 0 && params.id == CodecId::Opus {
+		if let Some(pre_skip) = opus::get_pre_skip(&params.config) {
+			let sample_base = Rational::inverse(opus::SAMPLE_RATE);
+
+			params.delay = trunc(Rational::nanos().rescale(u64::from(pre_skip), sample_base))?;
+		}
+	}
+
 	Ok(params)
 }
 
+/// Whether a decoder configured with `old` must be recreated for `new`
+fn codec_params_changed(old: &CodecParams, new: &CodecParams) -> bool {
+	old.id != new.id ||
+		old.config != new.config ||
+		old.sample_rate != new.sample_rate ||
+		old.bit_depth != new.bit_depth ||
+		old.width != new.width ||
+		old.height != new.height
+}
+
+impl Matroska {
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+	fn get_track(&self, track: &tracks::Track) -> Result<Track> {
+		let params = get_track_codec_params(track)?;
+
+		let parse = if params.id != CodecId::Aac {
+			CodecParse::Header
+		} else {
+			CodecParse::default()
+		};
+
+		Ok(Track {
+			ty: get_track_type(track.ty),
+			codec_params: params,
+			id: track.number.0,
+			duration: self.duration as u64,
+			time_base: self.timecode_scale,
+			parse,
+			..Default::default()
+		})
+	}
+
+	/// Merge the tracks of a new segment, reporting new tracks
+	/// and tracks whose codec changed
+	#[allow(clippy::cast_possible_truncation)]
+	fn update_tracks(&self, context: &mut FormatData) -> Result<()> {
+		let Some(tracks) = &self.tracks else {
+			return Ok(());
+		};
+
+		for track in &tracks.tracks {
+			let track = self.get_track(track)?;
+
+			let Some(index) = context
+				.tracks
+				.iter()
+				.position(|existing| existing.id == track.id)
+			else {
+				context.add_track(track);
+
+				continue;
+			};
+
+			let existing = &mut context.tracks[index];
+
+			existing.time_base = track.time_base;
+			existing.duration = track.duration;
+
+			if codec_params_changed(&existing.codec_params, &track.codec_params) {
+				existing.ty = track.ty;
+				existing.codec_params = track.codec_params;
+				existing.parse = track.parse;
+
+				context.push_event(FormatEvent::CodecParamsChanged(index as u32));
+			}
+		}
+
+		Ok(())
+	}
+}
+
 #[asynchronous]
 impl DemuxerImpl for Matroska {
 	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
@@ -466,63 +740,23 @@ impl DemuxerImpl for Matroska {
 		context.time_base = self.timecode_scale;
 
 		for track in &tracks.tracks {
-			let params = get_track_codec_params(track)?;
-
-			let parse = if params.id != CodecId::Aac {
-				CodecParse::Header
-			} else {
-				CodecParse::default()
-			};
-
-			context.tracks.push(Track {
-				ty: get_track_type(track.ty),
-				codec_params: params,
-				id: track.number.0,
-				duration: self.duration as u64,
-				time_base: self.timecode_scale,
-				parse,
-				..Default::default()
-			});
+			context.tracks.push(self.get_track(track)?);
 		}
 
+		self.tracks_changed = false;
+
 		Ok(())
 	}
 
 	async fn seek(
-		&mut self, _: &mut FormatData, track: u32, timecode: u64, _flags: BitFlags<SeekFlag>
+		&mut self, _: &mut FormatData, track: u32, timecode: u64, flags: BitFlags<SeekFlag>
 	) -> Result<()> {
-		#[allow(clippy::unwrap_used)]
-		let track = &self.tracks.as_ref().unwrap().tracks[track as usize];
-		let cues = self.cues.as_ref().ok_or(FormatError::CannotSeek)?;
-
-		#[allow(clippy::arithmetic_side_effects)]
-		let index = match cues
-			.points
-			.binary_search_by(|point| point.time.0.cmp(&timecode))
-		{
-			Ok(index) => index,
-			Err(index) => index - 1
+		let offset = if flags.intersects(SeekFlag::Byte) {
+			self.byte_seek_offset(timecode, flags).await?
+		} else {
+			self.cue_seek_offset(track, timecode, flags)?
 		};
 
-		let mut cluster_position = None;
-
-		for point in cues.points[0..=index].iter().rev() {
-			if let Some(pos) = point
-				.track_positions
-				.iter()
-				.find(|pos| pos.track == track.number)
-			{
-				cluster_position = Some(pos.cluster_position.0);
-
-				break;
-			}
-		}
-
-		let offset = self
-			.segment_offset
-			.checked_add(cluster_position.unwrap_or(0))
-			.ok_or(ErrorKind::Overflow)?;
-
 		self.reader.seek(SeekFrom::Start(offset)).await?;
 
 		#[allow(clippy::arithmetic_side_effects)]
@@ -537,17 +771,48 @@ impl DemuxerImpl for Matroska {
 
 	#[allow(clippy::unwrap_used)]
 	async fn read_packet(&mut self, context: &mut FormatData, packet: &mut Packet) -> Result<bool> {
-		if self.block.is_none() {
-			self.read_root().await?;
-		}
+		let block = loop {
+			if self.block.is_none() {
+				self.read_root().await?;
+			}
+
+			/* a new segment stops reading at its tracks */
+			let tracks_changed = self.tracks_changed;
+
+			if tracks_changed {
+				self.tracks_changed = false;
+				self.update_tracks(context)?;
+			}
 
-		let block = match self.block.take() {
-			Some(block) => block,
-			None => return Ok(false)
+			match self.block.take() {
+				Some(block) => break block,
+				None if tracks_changed => (),
+				None => return Ok(false)
+			}
 		};
 
 		context.get_packet_fields_for(packet, block.track_id)?;
-		packet.timestamp = block.timecode.try_into().unwrap();
+
+		let track = &context.tracks[packet.track_index as usize];
+		let params = &track.codec_params;
+		let delay = if params.time_base.num == 0 {
+			0
+		} else {
+			track
+				.time_base
+				.rescale(u64::from(params.delay), params.time_base)
+		};
+
+		let timecode: i64 = block.timecode.try_into().map_err(|_| ErrorKind::Overflow)?;
+		let delay: i64 = delay.try_into().map_err(|_| ErrorKind::Overflow)?;
+
+		/* the codec delay is included in block timestamps, but never presented */
+		packet.presentation_timestamp = timecode.saturating_sub(delay);
+
+		/* only video may be reordered, leaving its decode timestamps unknown */
+		if track.ty != MediaType::Video {
+			packet.decode_timestamp = packet.presentation_timestamp;
+		}
 
 		if block.flags & 0x80 != 0 {
 			packet.flags |= PacketFlag::Keyframe;
@@ -555,9 +820,25 @@ impl DemuxerImpl for Matroska {
 
 		packet.data = self
 			.reader
-			.read_bytes(block.size.try_into().map_err(|_| ErrorKind::Overflow)?)
+			.read_data(block.size.try_into().map_err(|_| ErrorKind::Overflow)?)
 			.await?;
 
+		#[allow(clippy::arithmetic_side_effects)]
+		if self.stack[self.level - 1].element.id == Cluster::BLOCK_GROUPS_ID {
+			let (discard_padding, side_data) = self.read_block_group_tail().await?;
+			let sample_rate = context.tracks[packet.track_index as usize]
+				.codec_params
+				.sample_rate;
+
+			packet.side_data = side_data;
+
+			if let Some(padding) = discard_padding.filter(|_| sample_rate != 0) {
+				let samples = Rational::inverse(sample_rate).rescale(padding, Rational::nanos());
+
+				packet.trim_end = samples.try_into().unwrap_or(u32::MAX);
+			}
+		}
+
 		Ok(true)
 	}
 }
@@ -628,11 +909,24 @@ impl DemuxerClassImpl for MatroskaClass {
 		"Matroska / WebM"
 	}
 
+	fn extensions(&self) -> &'static [&'static str] {
+		&["mkv", "mka", "mks", "mk3d", "webm"]
+	}
+
+	fn mime_types(&self) -> &'static [&'static str] {
+		&[
+			"video/x-matroska",
+			"audio/x-matroska",
+			"video/webm",
+			"audio/webm"
+		]
+	}
+
 	async fn create(&self, reader: Reader) -> Result<Demuxer> {
 		Ok(Box::new(Matroska::new(reader)))
 	}
 
-	async fn probe(&self, reader: &mut Reader) -> Result<f32> {
+	async fn probe(&self, reader: &mut Reader, _: &ProbeHints) -> Result<f32> {
 		let err = match do_probe(reader).await {
 			Ok(ok) => return Ok(ok),
 			Err(err) => err
@@ -1,6 +1,8 @@
 pub(crate) mod av;
 pub mod mkv;
+mod registry;
 
+pub use self::registry::*;
 use super::*;
 
 #[asynchronous(impl(mut, box))]
@@ -16,13 +18,126 @@ pub trait DemuxerImpl {
 
 pub type Demuxer = Box<dyn DemuxerImpl + Send + Sync>;
 
+/// What is known about the input besides its bytes
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct ProbeHints {
+	/// The file name extension, such as `mp3`
+	pub extension: Option<String>,
+
+	/// The MIME type, such as an HTTP `Content-Type`
+	pub mime_type: Option<String>
+}
+
+impl ProbeHints {
+	/// The extension in lowercase, without a leading dot
+	#[must_use]
+	pub fn extension(&self) -> Option<String> {
+		let extension = self.extension.as_deref()?.trim().trim_start_matches('.');
+
+		(!extension.is_empty()).then(|| extension.to_ascii_lowercase())
+	}
+
+	/// The MIME type in lowercase, without parameters
+	#[must_use]
+	pub fn mime_type(&self) -> Option<String> {
+		let mime_type = self.mime_type.as_deref()?.split(';').next()?.trim();
+
+		(!mime_type.is_empty()).then(|| mime_type.to_ascii_lowercase())
+	}
+
+	/// The score earned from the hints alone. A matching MIME type
+	/// is worth more than a matching extension, as in avformat
+	#[must_use]
+	pub fn score(&self, extensions: &[&str], mime_types: &[&str]) -> f32 {
+		if self
+			.mime_type()
+			.is_some_and(|mime_type| mime_types.contains(&mime_type.as_str()))
+		{
+			return 0.75;
+		}
+
+		if self
+			.extension()
+			.is_some_and(|extension| extensions.contains(&extension.as_str()))
+		{
+			return 0.5;
+		}
+
+		0.0
+	}
+}
+
+/// Hints with `extension` and `mime_type`, for tests
+#[cfg(test)]
+pub(crate) fn hints(extension: Option<&str>, mime_type: Option<&str>) -> ProbeHints {
+	ProbeHints {
+		extension: extension.map(ToString::to_string),
+		mime_type: mime_type.map(ToString::to_string)
+	}
+}
+
 #[asynchronous(impl(mut, box))]
 pub trait DemuxerClassImpl: Send + Sync {
 	fn name(&self) -> &'static str;
 
+	/// File name extensions of the format, in lowercase
+	fn extensions(&self) -> &'static [&'static str] {
+		&[]
+	}
+
+	/// MIME types of the format, in lowercase
+	fn mime_types(&self) -> &'static [&'static str] {
+		&[]
+	}
+
 	async fn create(&self, reader: Reader) -> Result<Demuxer>;
 
-	async fn probe(&self, reader: &mut Reader) -> Result<f32>;
+	/// Score how likely the input is this format, from 0.0 to 1.0
+	///
+	/// If the bytes are inconclusive, `hints` may be used
+	/// to break the tie
+	async fn probe(&self, reader: &mut Reader, hints: &ProbeHints) -> Result<f32>;
 }
 
 pub type DemuxerClass<'a> = &'a dyn DemuxerClassImpl;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn extension() {
+		assert_eq!(hints(Some("MP3"), None).extension().as_deref(), Some("mp3"));
+		assert_eq!(
+			hints(Some(" .Flac"), None).extension().as_deref(),
+			Some("flac")
+		);
+		assert_eq!(hints(Some("."), None).extension(), None);
+		assert_eq!(hints(None, None).extension(), None);
+	}
+
+	#[test]
+	fn mime_type() {
+		let mime_type = hints(None, Some("Audio/MPEG; charset=binary")).mime_type();
+
+		assert_eq!(mime_type.as_deref(), Some("audio/mpeg"));
+		assert_eq!(hints(None, Some(" ; q=1")).mime_type(), None);
+		assert_eq!(hints(None, None).mime_type(), None);
+	}
+
+	#[test]
+	#[allow(clippy::float_cmp)]
+	fn score() {
+		let extensions = &["mp3"];
+		let mime_types = &["audio/mpeg"];
+		let score =
+			|extension, mime_type| hints(extension, mime_type).score(extensions, mime_types);
+
+		assert_eq!(score(Some("mp3"), Some("audio/mpeg")), 0.75);
+		assert_eq!(score(Some("ogg"), Some("audio/mpeg")), 0.75);
+		assert_eq!(score(Some("MP3"), Some("audio/ogg")), 0.5);
+		assert_eq!(score(Some("mp3"), None), 0.5);
+		assert_eq!(score(Some("ogg"), Some("audio/ogg")), 0.0);
+		assert_eq!(score(None, None), 0.0);
+	}
+}
@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::*;
+
+/// Formats that may be opened with avformat by default
+const DEFAULT_AV_FORMATS: &[&str] = &[
+	"aac", "matroska", "webm", "mp3", "ogg", "mpegts", "wav", "mov", "mp4", "m4a", "3gp", "flac"
+];
+
+/// Extensions and MIME types of avformat demuxers, used when
+/// the bytes alone are inconclusive
+#[allow(clippy::type_complexity)]
+const AV_HINTS: &[(&str, &[&str], &[&str])] = &[
+	(
+		"aac",
+		&["aac", "adts"],
+		&["audio/aac", "audio/aacp", "audio/x-aac"]
+	),
+	(
+		"mp3",
+		&["mp3"],
+		&["audio/mpeg", "audio/mp3", "audio/x-mpeg"]
+	),
+	(
+		"ogg",
+		&["ogg", "oga", "opus"],
+		&["audio/ogg", "application/ogg", "audio/opus"]
+	),
+	("flac", &["flac"], &["audio/flac", "audio/x-flac"]),
+	(
+		"wav",
+		&["wav"],
+		&["audio/wav", "audio/x-wav", "audio/wave", "audio/vnd.wave"]
+	),
+	("mpegts", &["ts", "m2ts", "mts"], &["video/mp2t"]),
+	(
+		"mov",
+		&["mp4", "m4a", "m4v", "mov", "3gp"],
+		&[
+			"audio/mp4",
+			"video/mp4",
+			"audio/x-m4a",
+			"video/quicktime",
+			"video/3gpp"
+		]
+	),
+	(
+		"matroska",
+		&["mkv", "mka", "webm"],
+		&[
+			"video/x-matroska",
+			"audio/x-matroska",
+			"video/webm",
+			"audio/webm"
+		]
+	)
+];
+
+#[derive(Clone)]
+struct Entry {
+	class: Arc<dyn DemuxerClassImpl>,
+	priority: i32
+}
+
+/// The demuxers tried when opening a format
+///
+/// Every registered demuxer probes the input, and the highest score
+/// wins. Ties go to the higher priority, then to the one registered
+/// first. If no demuxer is certain, avformat probes the input too,
+/// but is only used for the format names that are enabled
+#[derive(Clone)]
+pub struct FormatRegistry {
+	/// Sorted by priority, highest first
+	entries: Vec<Entry>,
+	av_fallback: bool,
+	av_formats: HashSet<String>
+}
+
+impl FormatRegistry {
+	/// A registry with no demuxers and the avformat fallback disabled
+	#[must_use]
+	pub fn empty() -> Self {
+		Self {
+			entries: Vec::new(),
+			av_fallback: false,
+			av_formats: HashSet::new()
+		}
+	}
+
+	/// Add a demuxer. Higher priorities win ties in probe score
+	pub fn register(&mut self, class: Arc<dyn DemuxerClassImpl>, priority: i32) -> &mut Self {
+		let index = self
+			.entries
+			.iter()
+			.position(|entry| entry.priority < priority)
+			.unwrap_or(self.entries.len());
+
+		self.entries.insert(index, Entry { class, priority });
+		self
+	}
+
+	/// Remove every demuxer named `name`. Returns `true` if any were removed
+	pub fn unregister(&mut self, name: &str) -> bool {
+		let len = self.entries.len();
+
+		self.entries.retain(|entry| entry.class.name() != name);
+		self.entries.len() != len
+	}
+
+	/// The registered demuxers, in the order they win ties
+	pub fn demuxers(&self) -> impl Iterator<Item = (&dyn DemuxerClassImpl, i32)> {
+		self.entries
+			.iter()
+			.map(|entry| (&*entry.class, entry.priority))
+	}
+
+	/// Find a registered demuxer by name, ignoring case
+	#[must_use]
+	pub fn find(&self, name: &str) -> Option<&dyn DemuxerClassImpl> {
+		self.entries
+			.iter()
+			.find(|entry| entry.class.name().eq_ignore_ascii_case(name))
+			.map(|entry| &*entry.class)
+	}
+
+	/// Enable or disable probing with avformat
+	pub fn set_av_fallback(&mut self, enabled: bool) -> &mut Self {
+		self.av_fallback = enabled;
+		self
+	}
+
+	#[must_use]
+	pub const fn av_fallback(&self) -> bool {
+		self.av_fallback
+	}
+
+	/// Allow or refuse opening the avformat demuxer named `name`,
+	/// such as `mp3` or `mpegts`
+	pub fn set_av_format(&mut self, name: &str, enabled: bool) -> &mut Self {
+		if enabled {
+			self.av_formats.insert(name.to_string());
+		} else {
+			self.av_formats.remove(name);
+		}
+
+		self
+	}
+
+	/// Returns `true` if the avformat demuxer `names` may be opened.
+	/// Names are comma separated, as avformat reports them
+	#[must_use]
+	pub fn av_format_enabled(&self, names: &str) -> bool {
+		self.av_fallback && names.split(',').any(|name| self.av_formats.contains(name))
+	}
+
+	/// The enabled avformat demuxer that `hints` point to, with the
+	/// score earned from the hints
+	#[must_use]
+	pub fn av_format_for(&self, hints: &ProbeHints) -> Option<(&'static str, f32)> {
+		AV_HINTS
+			.iter()
+			.filter(|(name, ..)| self.av_format_enabled(name))
+			.map(|(name, extensions, mime_types)| (*name, hints.score(extensions, mime_types)))
+			.filter(|(_, score)| *score > 0.0)
+			.max_by(|a, b| a.1.total_cmp(&b.1))
+	}
+}
+
+impl Default for FormatRegistry {
+	/// The built in demuxers, with avformat enabled for common audio formats
+	fn default() -> Self {
+		let mut registry = Self::empty();
+
+		registry
+			.register(Arc::new(mkv::MatroskaClass), 0)
+			.set_av_fallback(true);
+
+		for name in DEFAULT_AV_FORMATS {
+			registry.set_av_format(name, true);
+		}
+
+		registry
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn av_format_for() {
+		let mut registry = FormatRegistry::empty();
+
+		registry
+			.set_av_fallback(true)
+			.set_av_format("mp3", true)
+			.set_av_format("mov", true);
+
+		let format = |extension, mime_type| {
+			registry
+				.av_format_for(&hints(extension, mime_type))
+				.map(|(name, _)| name)
+		};
+
+		assert_eq!(format(Some("mp3"), None), Some("mp3"));
+		assert_eq!(format(None, Some("audio/mp4")), Some("mov"));
+
+		/* the MIME type outweighs the extension */
+		assert_eq!(format(Some("m4a"), Some("audio/mpeg")), Some("mp3"));
+
+		/* disabled formats are never suggested */
+		assert_eq!(format(Some("flac"), Some("audio/flac")), None);
+		assert_eq!(format(None, None), None);
+
+		registry.set_av_fallback(false);
+
+		assert_eq!(registry.av_format_for(&hints(Some("mp3"), None)), None);
+	}
+
+	#[test]
+	fn av_format_enabled() {
+		let mut registry = FormatRegistry::empty();
+
+		registry.set_av_format("mp4", true);
+
+		assert!(!registry.av_format_enabled("mov,mp4,m4a"));
+
+		registry.set_av_fallback(true);
+
+		assert!(registry.av_format_enabled("mov,mp4,m4a"));
+		assert!(!registry.av_format_enabled("matroska,webm"));
+	}
+}
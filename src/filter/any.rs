@@ -0,0 +1,153 @@
+use std::ffi::CString;
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+use crate::av::OptionKind;
+
+/// A value for a filter option
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionValue {
+	Int(i64),
+	Float(f64),
+	Bool(bool),
+	Rational(Rational),
+
+	/// Parsed by the filter, for options of any type
+	String(String)
+}
+
+impl Display for OptionValue {
+	fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Int(value) => write!(fmt, "{}", value),
+			Self::Float(value) => write!(fmt, "{}", value),
+			Self::Bool(value) => write!(fmt, "{}", value),
+			Self::Rational(value) => write!(fmt, "{}/{}", value.num, value.den),
+			Self::String(value) => fmt.write_str(value)
+		}
+	}
+}
+
+macro_rules! impl_from {
+	($type:ty, $variant:ident) => {
+		impl From<$type> for OptionValue {
+			fn from(value: $type) -> Self {
+				Self::$variant(value.into())
+			}
+		}
+	};
+}
+
+impl_from!(i32, Int);
+impl_from!(u32, Int);
+impl_from!(i64, Int);
+impl_from!(f32, Float);
+impl_from!(f64, Float);
+impl_from!(bool, Bool);
+impl_from!(Rational, Rational);
+impl_from!(&str, String);
+impl_from!(String, String);
+
+/// Any libavfilter filter, by name, such as `"acompressor"`
+///
+/// Options are set by name, and checked against the options the
+/// filter has before the graph is built
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AnyFilter {
+	pub name: String,
+	pub options: Vec<(String, OptionValue)>
+}
+
+impl AnyFilter {
+	#[must_use]
+	pub fn new(name: &str) -> Self {
+		Self { name: name.to_string(), options: Vec::new() }
+	}
+
+	/// Add the option `name`, set to `value`
+	#[must_use]
+	pub fn option<V>(mut self, name: &str, value: V) -> Self
+	where
+		V: Into<OptionValue>
+	{
+		self.options.push((name.to_string(), value.into()));
+		self
+	}
+
+	fn set_option(
+		&self, filter: &mut av::FilterContext, option: &str, value: &OptionValue
+	) -> Result<()> {
+		let unknown = || FilterError::UnknownOption(self.name.clone(), option.to_string());
+		let name = CString::new(option).map_err(|_| unknown())?;
+		let mut options = filter.options();
+
+		/* Safety: the filter is valid */
+		let kind = unsafe { options.find(&name) }.ok_or_else(unknown)?;
+
+		let matches = match value {
+			OptionValue::Int(_) => matches!(kind, OptionKind::Int | OptionKind::Float),
+			OptionValue::Float(_) => matches!(kind, OptionKind::Float | OptionKind::Rational),
+			OptionValue::Bool(_) => kind == OptionKind::Bool,
+			OptionValue::Rational(_) => matches!(kind, OptionKind::Rational | OptionKind::Float),
+			OptionValue::String(_) => true
+		};
+
+		if !matches {
+			return Err(FilterError::OptionType(
+				self.name.clone(),
+				option.to_string(),
+				kind.name()
+			)
+			.into());
+		}
+
+		/* Safety: the filter is valid */
+		let result = unsafe {
+			match value {
+				OptionValue::Int(value) => options.set_c(&name, *value),
+				OptionValue::Float(value) => options.set_c(&name, *value),
+				OptionValue::Bool(value) => options.set_c(&name, *value),
+				OptionValue::Rational(value) => options.set_c(&name, *value),
+				OptionValue::String(value) => options.set_c(&name, value.as_str())
+			}
+		};
+
+		result.map_err(|_| {
+			FilterError::InvalidValue(self.name.clone(), option.to_string(), value.to_string())
+				.into()
+		})
+	}
+}
+
+impl Filter for AnyFilter {
+	fn create_filter(&self, graph: &mut av::FilterGraph) -> Result<av::FilterContext> {
+		let name = CString::new(self.name.as_str())
+			.map_err(|_| FilterError::NotFound(self.name.clone()))?;
+		let filter = av::Filters::find_by_name_c(&name)
+			.ok_or_else(|| FilterError::NotFound(self.name.clone()))?;
+		let mut context = graph.create_filter(filter, None);
+
+		for (option, value) in &self.options {
+			self.set_option(&mut context, option, value)?;
+		}
+
+		Ok(context)
+	}
+
+	/// Sends each option as a command. Only options the filter
+	/// accepts as commands can change
+	fn update(&self, filter: &mut av::FilterContext) -> Result<()> {
+		for (option, value) in &self.options {
+			let command = CString::new(option.as_str());
+			let arg = CString::new(value.to_string());
+
+			let (Ok(command), Ok(arg)) = (command, arg) else {
+				return Err(FilterError::UnknownOption(self.name.clone(), option.clone()).into());
+			};
+
+			filter.send_command_c(&command, &arg)?;
+		}
+
+		Ok(())
+	}
+}
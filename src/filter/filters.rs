@@ -1,3 +1,5 @@
+use std::ffi::CStr;
+
 use super::*;
 
 #[derive(Clone, Copy, PartialEq)]
@@ -18,6 +20,10 @@ impl Filter for Volume {
 
 		Ok(volume.into_filter())
 	}
+
+	fn update(&self, filter: &mut av::FilterContext) -> Result<()> {
+		av::Volume::send_volume(filter, self.0)
+	}
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -52,6 +58,10 @@ impl Filter for Tempo {
 
 		Ok(tempo.into_filter())
 	}
+
+	fn update(&self, filter: &mut av::FilterContext) -> Result<()> {
+		av::Tempo::send_tempo(filter, self.0)
+	}
 }
 
 #[derive(Clone, PartialEq)]
@@ -91,6 +101,12 @@ impl Filter for FirEqualizer {
 
 		Ok(eq.into_filter())
 	}
+
+	/// Only the gain entries are changed. The other settings
+	/// need a new graph
+	fn update(&self, filter: &mut av::FilterContext) -> Result<()> {
+		av::FirEqualizer::send_gain_entries(filter, &self.gain_entries)
+	}
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -181,3 +197,324 @@ impl Filter for Echo {
 		Ok(echo.into_filter())
 	}
 }
+
+/// Loudness normalization, as in EBU R128
+///
+/// For two passes, measure the audio with a [`LoudnessMeter`] first
+/// and pass the result as `measured`, so the gain can be linear.
+/// Without it, the gain follows the audio as it plays. The output
+/// is at 192 khz, and resampled to the rate of the graph output
+#[derive(Clone, Copy, PartialEq)]
+pub struct LoudNorm {
+	/// The target integrated loudness, in LUFS
+	pub integrated: f64,
+
+	/// The target loudness range, in LU
+	pub range: f64,
+
+	/// The highest true peak allowed, in dBTP
+	pub true_peak: f64,
+
+	/// The loudness measured in the first pass
+	pub measured: Option<Loudness>,
+
+	/// A gain applied after normalizing, in dB
+	pub offset: f64,
+
+	/// Apply one gain to everything, if the measured values allow
+	/// it without exceeding the true peak
+	pub linear: bool
+}
+
+impl Default for LoudNorm {
+	fn default() -> Self {
+		Self {
+			integrated: -24.0,
+			range: 7.0,
+			true_peak: -2.0,
+			measured: None,
+			offset: 0.0,
+			linear: true
+		}
+	}
+}
+
+impl Filter for LoudNorm {
+	#[allow(clippy::unwrap_used)]
+	fn create_filter(&self, graph: &mut av::FilterGraph) -> Result<av::FilterContext> {
+		let mut norm = av::LoudNorm::new(graph);
+
+		norm.set_integrated(self.integrated);
+		norm.set_range(self.range);
+		norm.set_true_peak(self.true_peak);
+		norm.set_offset(self.offset);
+		norm.set_linear(self.linear);
+
+		/* silence measures as infinitely quiet, out of the option ranges */
+		if let Some(measured) = &self.measured {
+			if let Some(integrated) = measured.integrated {
+				norm.set_measured_integrated(integrated.clamp(-99.0, 0.0));
+			}
+
+			if let Some(range) = measured.range {
+				norm.set_measured_range(range.clamp(0.0, 99.0));
+			}
+
+			if let Some(threshold) = measured.threshold {
+				norm.set_measured_threshold(threshold.clamp(-99.0, 0.0));
+			}
+
+			norm.set_measured_true_peak(measured.true_peak_db().clamp(-99.0, 99.0));
+		}
+
+		Ok(norm.into_filter())
+	}
+}
+
+/// How [`Scale`] treats the aspect ratio of the input
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum ScaleFit {
+	/// Scale to exactly the requested size
+	#[default]
+	Stretch,
+
+	/// Keep the aspect ratio, shrinking the size to fit inside the
+	/// requested size, such as for thumbnails
+	Decrease,
+
+	/// Keep the aspect ratio, growing the size to cover the requested size
+	Increase
+}
+
+/// The resampling algorithm of [`Scale`]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum ScaleAlgorithm {
+	FastBilinear,
+	Bilinear,
+	#[default]
+	Bicubic,
+	Neighbor,
+	Area,
+	Gauss,
+	Sinc,
+	Lanczos,
+	Spline
+}
+
+impl ScaleAlgorithm {
+	const fn name(self) -> &'static CStr {
+		match self {
+			Self::FastBilinear => c"fast_bilinear",
+			Self::Bilinear => c"bilinear",
+			Self::Bicubic => c"bicubic",
+			Self::Neighbor => c"neighbor",
+			Self::Area => c"area",
+			Self::Gauss => c"gauss",
+			Self::Sinc => c"sinc",
+			Self::Lanczos => c"lanczos",
+			Self::Spline => c"spline"
+		}
+	}
+}
+
+/// Resize the picture. A width or height of `None` keeps the aspect ratio
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Scale {
+	pub width: Option<u32>,
+	pub height: Option<u32>,
+	pub fit: ScaleFit,
+	pub algorithm: ScaleAlgorithm
+}
+
+impl Filter for Scale {
+	fn create_filter(&self, graph: &mut av::FilterGraph) -> Result<av::FilterContext> {
+		let mut scale = av::Scale::new(graph);
+
+		scale.set_size(self.width, self.height);
+		scale.set_flags_str(self.algorithm.name());
+		scale.set_fit_str(match self.fit {
+			ScaleFit::Stretch => c"disable",
+			ScaleFit::Decrease => c"decrease",
+			ScaleFit::Increase => c"increase"
+		});
+
+		Ok(scale.into_filter())
+	}
+}
+
+/// Cut out a `width` by `height` rectangle at `x`, `y`
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Crop {
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32
+}
+
+impl Filter for Crop {
+	fn create_filter(&self, graph: &mut av::FilterGraph) -> Result<av::FilterContext> {
+		let mut crop = av::Crop::new(graph);
+
+		crop.set_rect(self.x, self.y, self.width, self.height);
+
+		Ok(crop.into_filter())
+	}
+}
+
+/// Change the frame rate, dropping or duplicating frames
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Fps(pub Rational);
+
+impl Filter for Fps {
+	fn create_filter(&self, graph: &mut av::FilterGraph) -> Result<av::FilterContext> {
+		let mut fps = av::Fps::new(graph);
+
+		fps.set_fps(self.0);
+
+		Ok(fps.into_filter())
+	}
+}
+
+/// Convert the picture to one of the pixel formats, the first
+/// being preferred
+#[derive(Clone, PartialEq, Eq)]
+pub struct Format(pub Vec<PixelFormat>);
+
+impl Filter for Format {
+	fn create_filter(&self, graph: &mut av::FilterGraph) -> Result<av::FilterContext> {
+		if self.0.is_empty() {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let mut format = av::FormatFilter::new(graph);
+
+		format.set_pix_fmts(&self.0);
+
+		Ok(format.into_filter())
+	}
+}
+
+/// Which way [`Transpose`] rotates
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum TransposeDirection {
+	/// Rotate 90 degrees counter clockwise, and flip vertically
+	CounterClockwiseFlip = 0,
+
+	/// Rotate 90 degrees clockwise
+	#[default]
+	Clockwise            = 1,
+
+	/// Rotate 90 degrees counter clockwise
+	CounterClockwise     = 2,
+
+	/// Rotate 90 degrees clockwise, and flip vertically
+	ClockwiseFlip        = 3
+}
+
+/// Swap rows and columns, rotating the picture
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transpose(pub TransposeDirection);
+
+impl Filter for Transpose {
+	fn create_filter(&self, graph: &mut av::FilterGraph) -> Result<av::FilterContext> {
+		let mut transpose = av::Transpose::new(graph);
+
+		transpose.set_dir(self.0 as i32);
+
+		Ok(transpose.into_filter())
+	}
+}
+
+/// Place the picture on a `width` by `height` canvas of `color`. A
+/// size of 0 keeps the input size, and positions of `None` center the picture
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Pad {
+	pub width: u32,
+	pub height: u32,
+	pub x: Option<u32>,
+	pub y: Option<u32>,
+
+	/// Red, green, blue and alpha
+	pub color: [u8; 4]
+}
+
+impl Default for Pad {
+	fn default() -> Self {
+		Self {
+			width: 0,
+			height: 0,
+			x: None,
+			y: None,
+			color: [0, 0, 0, 255]
+		}
+	}
+}
+
+impl Filter for Pad {
+	fn create_filter(&self, graph: &mut av::FilterGraph) -> Result<av::FilterContext> {
+		let mut pad = av::Pad::new(graph);
+
+		pad.set_rect(self.x, self.y, self.width, self.height);
+		pad.set_color(self.color);
+
+		Ok(pad.into_filter())
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn update_filter() {
+		let input = AudioSrcOptions {
+			time_base: None,
+			sample_fmt: SampleFormat::F32,
+			ch_layout: ChannelLayout::LAYOUT_STEREO,
+			sample_rate: 48_000
+		};
+
+		let output = AudioSinkOptions {
+			ch_layout: None,
+			sample_fmt: SampleFormat::F32,
+			sample_rate: 48_000,
+			frame_size: None
+		};
+
+		let eq = FirEqualizer {
+			gain_entries: vec![(1000.0, -3.0)],
+			..Default::default()
+		};
+
+		let filters: [&dyn Filter; 5] = [
+			&Volume(0.5),
+			&Tempo(1.25),
+			&eq,
+			&Pulsator::default(),
+			&Echo::default()
+		];
+
+		let mut graph = AudioFilterGraph::new(&input, &output, &filters).unwrap();
+		let handles: Vec<_> = graph.filters().collect();
+
+		graph.update_filter(handles[0], &Volume(0.25)).unwrap();
+		graph.update_filter(handles[1], &Tempo(0.8)).unwrap();
+		graph
+			.update_filter(
+				handles[2],
+				&FirEqualizer {
+					gain_entries: vec![(1000.0, 3.0)],
+					..Default::default()
+				}
+			)
+			.unwrap();
+
+		/* these take no commands, and need a new graph */
+		let pulsator = Pulsator { frequency: 4.0, ..Default::default() };
+		let echo = Echo { delays: vec![500.0], ..Default::default() };
+
+		assert!(graph.update_filter(handles[3], &pulsator).unwrap_err() == ErrorKind::Unsupported);
+		assert!(graph.update_filter(handles[4], &echo).unwrap_err() == ErrorKind::Unsupported);
+	}
+}
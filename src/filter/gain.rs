@@ -0,0 +1,240 @@
+use super::*;
+
+/// A gain applied to audio frames. Changes move linearly to the new
+/// gain over a number of samples, so that they don't click
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GainRamp {
+	gain: f64,
+	target: f64,
+	remaining: u32
+}
+
+impl GainRamp {
+	#[must_use]
+	pub const fn new(gain: f64) -> Self {
+		Self { gain, target: gain, remaining: 0 }
+	}
+
+	/// The gain the next sample gets
+	#[must_use]
+	pub const fn gain(&self) -> f64 {
+		self.gain
+	}
+
+	/// The gain being moved to
+	#[must_use]
+	pub const fn target(&self) -> f64 {
+		self.target
+	}
+
+	/// Move to `gain` over the next `samples` samples,
+	/// starting from the current gain
+	pub fn set(&mut self, gain: f64, samples: u32) {
+		self.target = gain;
+		self.remaining = samples;
+
+		if samples == 0 {
+			self.gain = gain;
+		}
+	}
+
+	fn gain_at(&self, offset: u32) -> f64 {
+		if offset >= self.remaining {
+			return self.target;
+		}
+
+		let progress = f64::from(offset) / f64::from(self.remaining);
+
+		(self.target - self.gain).mul_add(progress, self.gain)
+	}
+
+	/// Apply the gain to an audio frame
+	#[allow(clippy::float_cmp)]
+	pub fn apply(&mut self, frame: &mut Frame) -> Result<()> {
+		if self.remaining == 0 && self.gain == 1.0 {
+			return Ok(());
+		}
+
+		match frame.sample_format {
+			SampleFormat::U8 | SampleFormat::U8P => self.apply_to::<u8>(frame),
+			SampleFormat::I16 | SampleFormat::I16P => self.apply_to::<i16>(frame),
+			SampleFormat::I32 | SampleFormat::I32P => self.apply_to::<i32>(frame),
+			SampleFormat::I64 | SampleFormat::I64P => self.apply_to::<i64>(frame),
+			SampleFormat::F32 | SampleFormat::F32P => self.apply_to::<f32>(frame),
+			SampleFormat::F64 | SampleFormat::F64P => self.apply_to::<f64>(frame),
+			SampleFormat::None => Err(ErrorKind::InvalidInput.into())
+		}
+	}
+
+	fn apply_to<T: Sample>(&mut self, frame: &mut Frame) -> Result<()> {
+		let planes = frame.sample_plane_count();
+
+		/* packed frames interleave the channels in one plane */
+		let channels = if planes == 1 {
+			usize::from(frame.ch_layout.channel_count()).max(1)
+		} else {
+			1
+		};
+
+		for plane in 0..planes {
+			for (index, value) in frame.sample_plane_mut::<T>(plane)?.iter_mut().enumerate() {
+				#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+				let gain = self.gain_at((index / channels) as u32);
+
+				*value = T::from_f64(value.to_f64() * gain);
+			}
+		}
+
+		if frame.samples >= self.remaining {
+			self.gain = self.target;
+			self.remaining = 0;
+		} else {
+			self.gain = self.gain_at(frame.samples);
+
+			#[allow(clippy::arithmetic_side_effects)]
+			(self.remaining -= frame.samples);
+		}
+
+		Ok(())
+	}
+}
+
+impl Default for GainRamp {
+	fn default() -> Self {
+		Self::new(1.0)
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::float_cmp)]
+mod tests {
+	use super::*;
+
+	const RATE: u32 = 1000;
+
+	/// A mono frame of `samples` samples of full scale
+	fn ones(samples: usize) -> Frame {
+		let values = vec![1.0f64; samples];
+
+		Frame::from_samples(
+			&[&values],
+			&ChannelLayout::LAYOUT_MONO,
+			RATE,
+			SampleFormat::F64,
+			0
+		)
+		.unwrap()
+	}
+
+	fn apply(ramp: &mut GainRamp, mut frame: Frame) -> Vec<f64> {
+		ramp.apply(&mut frame).unwrap();
+		frame.sample_plane::<f64>(0).unwrap().to_vec()
+	}
+
+	#[test]
+	fn unity() {
+		let mut ramp = GainRamp::default();
+
+		assert_eq!(apply(&mut ramp, ones(4)), [1.0; 4]);
+		assert_eq!(ramp, GainRamp::new(1.0));
+	}
+
+	#[test]
+	fn constant() {
+		let mut ramp = GainRamp::new(0.5);
+
+		assert_eq!(apply(&mut ramp, ones(4)), [0.5; 4]);
+		assert_eq!(ramp.gain(), 0.5);
+	}
+
+	#[test]
+	fn ramp() {
+		let mut ramp = GainRamp::default();
+
+		ramp.set(0.0, 4);
+
+		assert_eq!(ramp.gain(), 1.0);
+		assert_eq!(ramp.target(), 0.0);
+		assert_eq!(apply(&mut ramp, ones(6)), [1.0, 0.75, 0.5, 0.25, 0.0, 0.0]);
+		assert_eq!(ramp, GainRamp::new(0.0));
+	}
+
+	#[test]
+	fn ramp_across_frames() {
+		let mut ramp = GainRamp::default();
+
+		ramp.set(0.0, 8);
+
+		assert_eq!(apply(&mut ramp, ones(4)), [1.0, 0.875, 0.75, 0.625]);
+		assert_eq!(ramp.gain(), 0.5);
+		assert_eq!(apply(&mut ramp, ones(4)), [0.5, 0.375, 0.25, 0.125]);
+		assert_eq!(apply(&mut ramp, ones(2)), [0.0, 0.0]);
+	}
+
+	#[test]
+	fn retarget() {
+		let mut ramp = GainRamp::default();
+
+		ramp.set(0.0, 4);
+		apply(&mut ramp, ones(2));
+
+		/* the new ramp starts from where the old one was */
+		ramp.set(1.0, 2);
+
+		assert_eq!(apply(&mut ramp, ones(3)), [0.5, 0.75, 1.0]);
+	}
+
+	#[test]
+	fn immediate() {
+		let mut ramp = GainRamp::default();
+
+		ramp.set(0.25, 0);
+
+		assert_eq!(ramp.gain(), 0.25);
+		assert_eq!(apply(&mut ramp, ones(2)), [0.25; 2]);
+	}
+
+	#[test]
+	fn channels() {
+		let mut ramp = GainRamp::default();
+		let values = [1.0f64; 8];
+
+		/* interleaved channels share the gain of their sample */
+		ramp.set(0.0, 4);
+
+		let packed = Frame::from_samples(
+			&[&values[..]],
+			&ChannelLayout::LAYOUT_STEREO,
+			RATE,
+			SampleFormat::F64,
+			0
+		)
+		.unwrap();
+
+		assert_eq!(
+			apply(&mut ramp, packed),
+			[1.0, 1.0, 0.75, 0.75, 0.5, 0.5, 0.25, 0.25]
+		);
+
+		ramp.set(1.0, 0);
+		ramp.set(0.0, 4);
+
+		let mut planar = Frame::from_samples(
+			&[&values[..4], &values[4..]],
+			&ChannelLayout::LAYOUT_STEREO,
+			RATE,
+			SampleFormat::F64P,
+			0
+		)
+		.unwrap();
+
+		ramp.apply(&mut planar).unwrap();
+
+		for plane in 0..2 {
+			assert_eq!(
+				planar.sample_plane::<f64>(plane).unwrap(),
+				[1.0, 0.75, 0.5, 0.25]
+			);
+		}
+	}
+}
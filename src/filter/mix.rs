@@ -0,0 +1,383 @@
+use std::ffi::CString;
+use std::fmt::Write;
+use std::time::Duration;
+
+use super::*;
+
+/// An input of a [`MultiAudioFilterGraph`]
+#[derive(Clone)]
+pub struct NamedInput {
+	/// The label of the input in graph descriptions, and
+	/// the name to find it by
+	pub name: String,
+	pub options: AudioSrcOptions
+}
+
+/// When a mix ends
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum MixDuration {
+	/// When every input has ended
+	#[default]
+	Longest,
+
+	/// When any input ends
+	Shortest,
+
+	/// When the first input ends
+	First
+}
+
+impl MixDuration {
+	const fn name(self) -> &'static str {
+		match self {
+			Self::Longest => "longest",
+			Self::Shortest => "shortest",
+			Self::First => "first"
+		}
+	}
+}
+
+/// Lowers the main input while the voice input is loud,
+/// such as for a voice-over
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ducking {
+	/// The voice level above which the main input is lowered,
+	/// as a linear amplitude
+	pub threshold: f64,
+
+	/// How much the main input is lowered by, above the threshold
+	pub ratio: f64,
+	pub attack: Duration,
+	pub release: Duration,
+
+	/// The gain applied to the main input after lowering it
+	pub makeup: f64,
+
+	/// Mix the voice into the output, instead of
+	/// only using it to lower the main input
+	pub mix_voice: bool
+}
+
+impl Default for Ducking {
+	fn default() -> Self {
+		Self {
+			threshold: 0.05,
+			ratio: 8.0,
+			attack: Duration::from_millis(20),
+			release: Duration::from_millis(400),
+			makeup: 1.0,
+			mix_voice: true
+		}
+	}
+}
+
+/// The shape of a fade
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum FadeCurve {
+	#[default]
+	Linear,
+	QuarterSine,
+	HalfSine,
+	ExponentialSine,
+	Logarithmic,
+	Exponential,
+	Parabola,
+	Cubic,
+	SquareRoot,
+
+	/// Cut, without fading
+	None
+}
+
+impl FadeCurve {
+	const fn name(self) -> &'static str {
+		match self {
+			Self::Linear => "tri",
+			Self::QuarterSine => "qsin",
+			Self::HalfSine => "hsin",
+			Self::ExponentialSine => "esin",
+			Self::Logarithmic => "log",
+			Self::Exponential => "exp",
+			Self::Parabola => "par",
+			Self::Cubic => "cub",
+			Self::SquareRoot => "squ",
+			Self::None => "nofade"
+		}
+	}
+}
+
+/// Fades one input out while the next fades in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Crossfade {
+	pub duration: Duration,
+	pub curve_out: FadeCurve,
+	pub curve_in: FadeCurve,
+
+	/// Overlap the end of the first input with the start of the
+	/// second, instead of fading the first out before the second
+	pub overlap: bool
+}
+
+impl Default for Crossfade {
+	fn default() -> Self {
+		Self {
+			duration: Duration::from_secs(5),
+			curve_out: FadeCurve::Linear,
+			curve_in: FadeCurve::Linear,
+			overlap: true
+		}
+	}
+}
+
+/// A filter graph with several named inputs, and one output
+///
+/// Inputs are fed separately, and each is drained when it ends.
+/// The output ends when the graph is done with every input
+pub struct MultiAudioFilterGraph {
+	graph: av::MultiAudioFilterGraph,
+	inputs: Vec<String>,
+	filters: Vec<av::FilterContext>,
+
+	/// The filter with per input gains, if any
+	mixer: Option<usize>,
+	gain: GainRamp
+}
+
+impl MultiAudioFilterGraph {
+	/// Build the graph from `description`. Inputs are labelled by their
+	/// position, such as `in0`, if `positional` is set, so they can't
+	/// collide with labels inside the description
+	fn build(
+		inputs: &[NamedInput], output: &AudioSinkOptions, description: &str, positional: bool,
+		mixer: Option<usize>
+	) -> Result<Self> {
+		let options: Vec<av::AudioSrcOptions> =
+			inputs.iter().map(|input| (&input.options).into()).collect();
+		let mut labels = Vec::with_capacity(inputs.len());
+
+		for (index, input) in inputs.iter().enumerate() {
+			let label = if positional {
+				format!("in{}", index)
+			} else {
+				input.name.clone()
+			};
+
+			let label =
+				CString::new(label).map_err(|_| FilterError::InvalidGraph(input.name.clone()))?;
+
+			labels.push(label);
+		}
+
+		let labels: Vec<_> = labels.iter().map(CString::as_c_str).collect();
+		let mut graph = av::MultiAudioFilterGraph::new(1, &options, &output.into());
+		let filters = graph
+			.parse(&labels, &description_cstr(description)?)
+			.map_err(|err| map_parse_error(err, description))?;
+
+		if let Some(size) = output.frame_size {
+			graph.set_frame_size(size);
+		}
+
+		Ok(Self {
+			graph,
+			inputs: inputs.iter().map(|input| input.name.clone()).collect(),
+			filters,
+			mixer,
+			gain: GainRamp::default()
+		})
+	}
+
+	/// Build the graph from an ffmpeg filtergraph description, where
+	/// each input is labelled by its name and `[out]` is the output,
+	/// such as `"[music][voice]amix=inputs=2[out]"`
+	///
+	/// Handles refer to the filters of the description, in order
+	pub fn parse(
+		inputs: &[NamedInput], output: &AudioSinkOptions, description: &str
+	) -> Result<Self> {
+		Self::build(inputs, output, description, false, None)
+	}
+
+	/// Mix the inputs together, each multiplied by its gain in `gains`.
+	/// The gains can be changed later with [`Self::set_gains`]
+	pub fn mix(
+		inputs: &[NamedInput], output: &AudioSinkOptions, gains: &[f64], duration: MixDuration
+	) -> Result<Self> {
+		if inputs.is_empty() || gains.len() != inputs.len() {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let mut description = String::new();
+
+		for index in 0..inputs.len() {
+			let _ = write!(description, "[in{}]", index);
+		}
+
+		let _ = write!(
+			description,
+			"amix=inputs={}:duration={}:normalize=0:weights='{}'[out]",
+			inputs.len(),
+			duration.name(),
+			weights(gains)
+		);
+
+		Self::build(inputs, output, &description, true, Some(0))
+	}
+
+	/// Combine the channels of the inputs into one frame, in order
+	/// of the inputs, such as two mono inputs into stereo
+	pub fn merge(inputs: &[NamedInput], output: &AudioSinkOptions) -> Result<Self> {
+		if inputs.is_empty() {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let mut description = String::new();
+
+		for index in 0..inputs.len() {
+			let _ = write!(description, "[in{}]", index);
+		}
+
+		let _ = write!(description, "amerge=inputs={}[out]", inputs.len());
+
+		Self::build(inputs, output, &description, true, None)
+	}
+
+	/// Lower `main` while `voice` is loud. The output ends with `main`
+	pub fn duck(
+		main: &NamedInput, voice: &NamedInput, output: &AudioSinkOptions, ducking: &Ducking
+	) -> Result<Self> {
+		let compress = format!(
+			"sidechaincompress=threshold={}:ratio={}:attack={}:release={}:makeup={}",
+			ducking.threshold,
+			ducking.ratio,
+			ducking.attack.as_secs_f64() * 1000.0,
+			ducking.release.as_secs_f64() * 1000.0,
+			ducking.makeup
+		);
+
+		let description = if ducking.mix_voice {
+			format!(
+				"[in1]asplit=2[sc][voice];[in0][sc]{}[ducked];[ducked][voice]amix=inputs=2:\
+				 duration=first:normalize=0[out]",
+				compress
+			)
+		} else {
+			format!("[in0][in1]{}[out]", compress)
+		};
+
+		let inputs = [main.clone(), voice.clone()];
+
+		Self::build(&inputs, output, &description, true, None)
+	}
+
+	/// Fade from `from` to `to`. The fade starts once `from` is drained,
+	/// so `to` is buffered until then
+	pub fn crossfade(
+		from: &NamedInput, to: &NamedInput, output: &AudioSinkOptions, crossfade: &Crossfade
+	) -> Result<Self> {
+		let description = format!(
+			"[in0][in1]acrossfade=duration={}:curve1={}:curve2={}:overlap={}[out]",
+			crossfade.duration.as_secs_f64(),
+			crossfade.curve_out.name(),
+			crossfade.curve_in.name(),
+			u8::from(crossfade.overlap)
+		);
+
+		let inputs = [from.clone(), to.clone()];
+
+		Self::build(&inputs, output, &description, true, None)
+	}
+
+	/// The index of the input called `name`
+	#[must_use]
+	pub fn input(&self, name: &str) -> Option<usize> {
+		self.inputs.iter().position(|input| input == name)
+	}
+
+	#[must_use]
+	pub fn input_count(&self) -> usize {
+		self.inputs.len()
+	}
+
+	/// Change the gain of each input of a mix, without rebuilding
+	/// the graph. Fails with [`ErrorKind::Unsupported`] if the graph
+	/// is not a mix
+	pub fn set_gains(&mut self, gains: &[f64]) -> Result<()> {
+		let mixer = self
+			.mixer
+			.and_then(|index| self.filters.get_mut(index))
+			.ok_or(ErrorKind::Unsupported)?;
+
+		if gains.len() != self.inputs.len() {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		mixer.send_command("weights", &weights(gains))
+	}
+
+	#[must_use]
+	pub fn filter(&self, index: usize) -> Option<FilterHandle> {
+		(index < self.filters.len()).then_some(FilterHandle::new(index))
+	}
+
+	/// Change the settings of a running filter without rebuilding
+	/// the graph, as in [`AudioFilterGraph::update_filter`]
+	pub fn update_filter(&mut self, handle: FilterHandle, filter: &dyn Filter) -> Result<()> {
+		update_filter(&mut self.filters, handle, filter)
+	}
+
+	/// Move the gain of the output to `gain` over the next
+	/// `ramp_samples` output samples
+	pub fn set_gain(&mut self, gain: f64, ramp_samples: u32) {
+		self.gain.set(gain, ramp_samples);
+	}
+
+	/// Feed a frame to the input at `input`
+	pub fn send_frame(&mut self, input: usize, frame: Frame) -> Result<()> {
+		if input >= self.inputs.len() {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		self.graph.send_frame(input, frame.data)
+	}
+
+	pub fn receive_frame(&mut self) -> Result<Option<Frame>> {
+		let mut frame = Frame::new();
+
+		if !self.graph.receive_frame(&mut frame.data)? {
+			return Ok(None);
+		}
+
+		output_frame(&mut frame, &mut self.gain)?;
+
+		Ok(Some(frame))
+	}
+
+	pub fn set_frame_size(&mut self, frame_size: u32) {
+		self.graph.set_frame_size(frame_size);
+	}
+
+	/// Mark the input at `input` as ended
+	pub fn drain(&mut self, input: usize) -> Result<()> {
+		if input >= self.inputs.len() {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		self.graph.drain(input)
+	}
+}
+
+/// Gains as the weights of `amix`
+fn weights(gains: &[f64]) -> String {
+	let mut weights = String::new();
+
+	for (index, gain) in gains.iter().enumerate() {
+		if index > 0 {
+			weights.push(' ');
+		}
+
+		let _ = write!(weights, "{}", gain);
+	}
+
+	weights
+}
@@ -1,11 +1,77 @@
+use std::ffi::CString;
+
 use super::*;
 
+mod any;
 pub mod filters;
+mod gain;
+mod mix;
+mod video;
+
+pub use any::*;
+pub use gain::GainRamp;
+pub use mix::*;
+pub use video::*;
+
+#[errors]
+pub enum FilterError {
+	#[display("Filter {} not found", f0)]
+	#[kind = ErrorKind::NotFound]
+	NotFound(String),
+
+	#[display("Filter {} has no option {}", f0, f1)]
+	#[kind = ErrorKind::InvalidInput]
+	UnknownOption(String, String),
+
+	#[display("Option {} of filter {} takes a value of type {}", f1, f0, f2)]
+	#[kind = ErrorKind::InvalidInput]
+	OptionType(String, String, &'static str),
 
-pub struct AudioFilterGraph(av::AudioFilterGraph);
+	#[display("Invalid value {} for option {} of filter {}", f2, f1, f0)]
+	#[kind = ErrorKind::InvalidInput]
+	InvalidValue(String, String, String),
+
+	#[display("Invalid filter graph description: {}", f0)]
+	#[kind = ErrorKind::InvalidInput]
+	InvalidGraph(String)
+}
+
+pub struct AudioFilterGraph {
+	graph: av::AudioFilterGraph,
+	filters: Vec<av::FilterContext>,
+	gain: GainRamp
+}
 
 pub trait Filter {
 	fn create_filter(&self, graph: &mut av::FilterGraph) -> Result<av::FilterContext>;
+
+	/// Change `filter`, created by this kind of filter and already
+	/// running in a graph, to these settings
+	///
+	/// [`filters::Volume`], [`filters::Tempo`] and the gain entries of
+	/// [`filters::FirEqualizer`] change in place. Other filters fail
+	/// with [`ErrorKind::Unsupported`], and need a new graph
+	fn update(&self, filter: &mut av::FilterContext) -> Result<()> {
+		let _ = filter;
+
+		Err(ErrorKind::Unsupported.into())
+	}
+}
+
+/// A filter in a graph, by its position in the list the graph was
+/// created with. Stays valid for a graph created with the same filters
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FilterHandle(usize);
+
+impl FilterHandle {
+	pub(crate) const fn new(index: usize) -> Self {
+		Self(index)
+	}
+
+	#[must_use]
+	pub const fn index(self) -> usize {
+		self.0
+	}
 }
 
 #[derive(Clone)]
@@ -24,24 +90,79 @@ pub struct AudioSinkOptions {
 	pub frame_size: Option<u32>
 }
 
-impl AudioFilterGraph {
-	pub fn new(
-		input: &AudioSrcOptions, output: &AudioSinkOptions, filters: &[&dyn Filter]
-	) -> Result<Self> {
-		let av_in = av::AudioSrcOptions {
+impl From<&AudioSrcOptions> for av::AudioSrcOptions {
+	fn from(input: &AudioSrcOptions) -> Self {
+		Self {
 			time_base: input.time_base,
 			sample_fmt: Some(input.sample_fmt),
 			ch_layout: Some(input.ch_layout.clone()),
 			sample_rate: Some(input.sample_rate)
-		};
+		}
+	}
+}
 
-		let av_out = av::AudioSinkOptions {
+impl From<&AudioSinkOptions> for av::AudioSinkOptions {
+	fn from(output: &AudioSinkOptions) -> Self {
+		Self {
 			ch_layout: output.ch_layout.clone(),
 			sample_fmt: Some(output.sample_fmt),
 			sample_rate: Some(output.sample_rate)
-		};
+		}
+	}
+}
+
+/// The description as a C string, failing if it has a nul
+fn description_cstr(description: &str) -> Result<CString> {
+	/* an empty description passes audio through */
+	if description.trim().is_empty() {
+		return Ok(c"anull".into());
+	}
+
+	CString::new(description).map_err(|_| FilterError::InvalidGraph(description.to_string()).into())
+}
+
+/// Parse errors from ffmpeg don't say what went wrong
+fn map_parse_error(err: Error, description: &str) -> Error {
+	if err == ErrorKind::InvalidInput {
+		FilterError::InvalidGraph(description.to_string()).into()
+	} else {
+		err
+	}
+}
 
-		let mut graph = av::AudioFilterGraph::new(1, &av_in, &av_out);
+fn update_filter(
+	filters: &mut [av::FilterContext], handle: FilterHandle, filter: &dyn Filter
+) -> Result<()> {
+	let context = filters.get_mut(handle.0).ok_or(ErrorKind::InvalidInput)?;
+
+	filter.update(context)
+}
+
+/// Fill in the fields of a frame of `ty` out of a graph
+fn filtered_frame(frame: &mut Frame, ty: MediaType) {
+	frame.get_fields_from_inner(Some(ty));
+
+	/* filters retime frames, and the decoder's guess copied from the
+	 * input is in its time base, so the filtered timestamp is the best effort
+	 */
+	frame.best_effort_timestamp = frame.presentation_timestamp;
+}
+
+/// Fill in the fields of a frame out of an audio graph, and apply `gain`
+fn output_frame(frame: &mut Frame, gain: &mut GainRamp) -> Result<()> {
+	filtered_frame(frame, MediaType::Audio);
+	gain.apply(frame)
+}
+
+impl AudioFilterGraph {
+	fn create(input: &AudioSrcOptions, output: &AudioSinkOptions) -> av::AudioFilterGraph {
+		av::AudioFilterGraph::new(1, &input.into(), &output.into())
+	}
+
+	pub fn new(
+		input: &AudioSrcOptions, output: &AudioSinkOptions, filters: &[&dyn Filter]
+	) -> Result<Self> {
+		let mut graph = Self::create(input, output);
 		let mut filt = Vec::new();
 
 		for filter in filters {
@@ -54,27 +175,82 @@ impl AudioFilterGraph {
 			graph.set_frame_size(size);
 		}
 
-		Ok(Self(graph))
+		Ok(Self { graph, filters: filt, gain: GainRamp::default() })
+	}
+
+	/// Build a graph from an ffmpeg filtergraph description, such as
+	/// `"volume=0.5,atempo=1.25"`. The description may branch and merge,
+	/// with `[in]` and `[out]` labelling the input and output
+	///
+	/// Handles refer to the filters of the description, in order
+	pub fn parse(
+		input: &AudioSrcOptions, output: &AudioSinkOptions, description: &str
+	) -> Result<Self> {
+		let mut graph = Self::create(input, output);
+		let filters = graph
+			.parse(&description_cstr(description)?)
+			.map_err(|err| map_parse_error(err, description))?;
+
+		if let Some(size) = output.frame_size {
+			graph.set_frame_size(size);
+		}
+
+		Ok(Self { graph, filters, gain: GainRamp::default() })
+	}
+
+	/// The handles to the filters, in the order they were given
+	pub fn filters(&self) -> impl Iterator<Item = FilterHandle> {
+		(0..self.filters.len()).map(FilterHandle::new)
+	}
+
+	#[must_use]
+	pub fn filter(&self, index: usize) -> Option<FilterHandle> {
+		(index < self.filters.len()).then_some(FilterHandle::new(index))
+	}
+
+	/// Change the settings of a running filter without rebuilding the
+	/// graph. `filter` must be the same kind of filter `handle` was
+	/// created with. Audio already in the graph is kept
+	///
+	/// Fails with [`ErrorKind::Unsupported`] if the filter can't
+	/// change at runtime. See [`Filter::update`]
+	pub fn update_filter(&mut self, handle: FilterHandle, filter: &dyn Filter) -> Result<()> {
+		update_filter(&mut self.filters, handle, filter)
+	}
+
+	/// The gain applied to the output of the graph
+	#[must_use]
+	pub const fn gain(&self) -> &GainRamp {
+		&self.gain
+	}
+
+	/// Move the gain of the output to `gain` over the next
+	/// `ramp_samples` output samples
+	pub fn set_gain(&mut self, gain: f64, ramp_samples: u32) {
+		self.gain.set(gain, ramp_samples);
 	}
 
 	pub fn send_frame(&mut self, frame: Frame) -> Result<()> {
-		self.0.send_frame(frame.data)
+		self.graph.send_frame(frame.data)
 	}
 
 	pub fn receive_frame(&mut self) -> Result<Option<Frame>> {
 		let mut frame = Frame::new();
 
-		Ok(self.0.receive_frame(&mut frame.data)?.then(|| {
-			frame.get_fields_from_inner(Some(MediaType::Audio));
-			frame
-		}))
+		if !self.graph.receive_frame(&mut frame.data)? {
+			return Ok(None);
+		}
+
+		output_frame(&mut frame, &mut self.gain)?;
+
+		Ok(Some(frame))
 	}
 
 	pub fn set_frame_size(&mut self, frame_size: u32) {
-		self.0.set_frame_size(frame_size);
+		self.graph.set_frame_size(frame_size);
 	}
 
 	pub fn drain(&mut self) -> Result<()> {
-		self.0.drain()
+		self.graph.drain()
 	}
 }
@@ -0,0 +1,167 @@
+use super::*;
+
+#[derive(Clone)]
+pub struct VideoSrcOptions {
+	pub width: u32,
+	pub height: u32,
+	pub pix_fmt: PixelFormat,
+	pub time_base: Rational,
+	pub frame_rate: Option<Rational>,
+	pub sample_aspect_ratio: Option<Rational>
+}
+
+impl VideoSrcOptions {
+	/// Options matching the format of `frame`
+	#[must_use]
+	pub fn from_frame(frame: &Frame) -> Self {
+		Self {
+			width: frame.width,
+			height: frame.height,
+			pix_fmt: frame.pixel_format,
+			time_base: frame.time_base,
+			frame_rate: None,
+			sample_aspect_ratio: Some(frame.sample_aspect_ratio).filter(|sar| sar.num != 0)
+		}
+	}
+}
+
+/// The format of the frames out of a [`VideoFilterGraph`]. Fields
+/// of `None` are left as the filters output them
+#[derive(Clone, Default)]
+pub struct VideoSinkOptions {
+	/// Scaled to, keeping the aspect ratio if only one is set
+	pub width: Option<u32>,
+	pub height: Option<u32>,
+	pub pix_fmt: Option<PixelFormat>,
+	pub time_base: Option<Rational>,
+	pub sample_aspect_ratio: Option<Rational>,
+	pub frame_rate: Option<Rational>
+}
+
+pub struct VideoFilterGraph {
+	graph: av::VideoFilterGraph,
+	filters: Vec<av::FilterContext>
+}
+
+impl VideoFilterGraph {
+	fn create(input: &VideoSrcOptions, output: &VideoSinkOptions) -> av::VideoFilterGraph {
+		let av_out = av::VideoSinkOptions { pix_fmt: output.pix_fmt };
+		let av_in = av::VideoSrcOptions {
+			width: input.width,
+			height: input.height,
+			pix_fmt: input.pix_fmt,
+			time_base: input.time_base,
+			frame_rate: input.frame_rate,
+			sample_aspect_ratio: input.sample_aspect_ratio
+		};
+
+		av::VideoFilterGraph::new(1, &av_in, &av_out)
+	}
+
+	/// The filters that bring the output to `output`, after the others
+	fn output_filters(
+		graph: &mut av::FilterGraph, output: &VideoSinkOptions
+	) -> Result<Vec<av::FilterContext>> {
+		let mut filters = Vec::new();
+
+		if output.width.is_some() || output.height.is_some() {
+			let scale = Scale {
+				width: output.width,
+				height: output.height,
+				..Default::default()
+			};
+
+			filters.push(scale.create_filter(graph)?);
+		}
+
+		if let Some(frame_rate) = output.frame_rate {
+			filters.push(Fps(frame_rate).create_filter(graph)?);
+		}
+
+		if let Some(sample_aspect_ratio) = output.sample_aspect_ratio {
+			let mut sar = av::SetSar::new(graph);
+
+			sar.set_sar(sample_aspect_ratio);
+			filters.push(sar.into_filter());
+		}
+
+		if let Some(time_base) = output.time_base {
+			let mut tb = av::SetTimeBase::new(graph);
+
+			tb.set_time_base(time_base);
+			filters.push(tb.into_filter());
+		}
+
+		Ok(filters)
+	}
+
+	pub fn new(
+		input: &VideoSrcOptions, output: &VideoSinkOptions, filters: &[&dyn Filter]
+	) -> Result<Self> {
+		let mut graph = Self::create(input, output);
+		let mut filt = Vec::new();
+
+		for filter in filters {
+			filt.push(filter.create_filter(&mut graph)?);
+		}
+
+		let mut out = Self::output_filters(&mut graph, output)?;
+
+		graph.set_filters(filt.iter_mut().chain(&mut out))?;
+
+		Ok(Self { graph, filters: filt })
+	}
+
+	/// Build a graph from an ffmpeg filtergraph description, such as
+	/// `"crop=640:360,hflip"`, as in [`AudioFilterGraph::parse`]. The
+	/// output options are not applied, other than the pixel format
+	pub fn parse(
+		input: &VideoSrcOptions, output: &VideoSinkOptions, description: &str
+	) -> Result<Self> {
+		let mut graph = Self::create(input, output);
+
+		/* an empty description passes video through */
+		let description = if description.trim().is_empty() {
+			"null"
+		} else {
+			description
+		};
+
+		let filters = graph
+			.parse(&description_cstr(description)?)
+			.map_err(|err| map_parse_error(err, description))?;
+
+		Ok(Self { graph, filters })
+	}
+
+	#[must_use]
+	pub fn filter(&self, index: usize) -> Option<FilterHandle> {
+		(index < self.filters.len()).then_some(FilterHandle::new(index))
+	}
+
+	/// Change the settings of a running filter without rebuilding
+	/// the graph, as in [`AudioFilterGraph::update_filter`]
+	pub fn update_filter(&mut self, handle: FilterHandle, filter: &dyn Filter) -> Result<()> {
+		update_filter(&mut self.filters, handle, filter)
+	}
+
+	pub fn send_frame(&mut self, frame: Frame) -> Result<()> {
+		self.graph.send_frame(frame.data)
+	}
+
+	pub fn receive_frame(&mut self) -> Result<Option<Frame>> {
+		let mut frame = Frame::new();
+
+		if !self.graph.receive_frame(&mut frame.data)? {
+			return Ok(None);
+		}
+
+		filtered_frame(&mut frame, MediaType::Video);
+
+		Ok(Some(frame))
+	}
+
+	pub fn drain(&mut self) -> Result<()> {
+		self.graph.drain()
+	}
+}
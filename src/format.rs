@@ -1,15 +1,105 @@
+use std::collections::VecDeque;
 use std::ops::{Deref, DerefMut};
 
 use demuxer::av::AVFormatClass;
-use xx_core::debug;
+use xx_core::{debug, trace};
 
 use super::*;
+use crate::av::ProbeResult;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 #[bitflags]
 #[repr(u32)]
 pub enum SeekFlag {
-	Any = 0x1
+	/// Land on any packet, not only keyframes
+	Any      = 0x1,
+
+	/// Never land after the target. Seeks are backward unless
+	/// [`SeekFlag::Forward`] is set
+	Backward = 0x2,
+
+	/// Never land before the target
+	Forward  = 0x4,
+
+	/// The target is a byte offset in the stream, for formats
+	/// without an index
+	Byte     = 0x8,
+
+	/// The target is a sample index for audio tracks,
+	/// or a frame index for video tracks
+	Frame    = 0x10
+}
+
+/// How closely a seek lands on the requested time
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SeekMode {
+	/// Land on the nearest cue point or keyframe before the time
+	#[default]
+	Fast,
+
+	/// Land far enough before the time for the decoder to converge,
+	/// covering the codec's pre-roll and delay. Decoded frames are
+	/// then trimmed to the target with a [`FrameTrimmer`]
+	Accurate
+}
+
+/// The exact time a seek was asked to reach
+#[derive(Clone, Copy, Debug)]
+pub struct SeekTarget {
+	pub track_index: u32,
+
+	/// The requested time, in `time_base`
+	pub timestamp: i64,
+	pub time_base: Rational
+}
+
+/// Tags such as title and artist, in the order the container stores them
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct Metadata(pub Vec<(String, String)>);
+
+impl Metadata {
+	/// Get the first value of `key`, ignoring case
+	#[must_use]
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.0
+			.iter()
+			.find(|(name, _)| name.eq_ignore_ascii_case(key))
+			.map(|(_, value)| value.as_str())
+	}
+
+	/// Replace every value of `key` with `value`
+	#[allow(clippy::impl_trait_in_params)]
+	pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+		let key = key.into();
+
+		self.0.retain(|(name, _)| !name.eq_ignore_ascii_case(&key));
+		self.0.push((key, value.into()));
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+}
+
+/// A change reported by the demuxer in the middle of the stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatEvent {
+	/// A track was added at this index
+	TrackAdded(u32),
+
+	/// The codec parameters of the track at this index changed.
+	/// Decoders for the track need to be recreated
+	CodecParamsChanged(u32),
+
+	/// Metadata of the track at this index changed, or of the
+	/// format itself if `None`
+	MetadataUpdated(Option<u32>)
+}
+
+pub enum FormatItem {
+	Packet(Packet),
+	Event(FormatEvent)
 }
 
 #[derive(Default)]
@@ -25,18 +115,37 @@ pub struct Track {
 	pub start_time: i64,
 	pub duration: u64,
 
-	pub discard: Discard
+	pub discard: Discard,
+	pub metadata: Metadata
 }
 
 #[derive(Default)]
+#[allow(clippy::partial_pub_fields)]
 pub struct FormatData {
 	pub tracks: Vec<Track>,
 	pub start_time: i64,
 	pub duration: u64,
-	pub time_base: Rational
+	pub time_base: Rational,
+	pub metadata: Metadata,
+
+	events: VecDeque<FormatEvent>
 }
 
 impl FormatData {
+	/// Report a change, delivered before the packet being read
+	pub fn push_event(&mut self, event: FormatEvent) {
+		self.events.push_back(event);
+	}
+
+	/// Add a track found after the format was opened
+	pub fn add_track(&mut self, track: Track) {
+		#[allow(clippy::cast_possible_truncation)]
+		let index = self.tracks.len() as u32;
+
+		self.tracks.push(track);
+		self.push_event(FormatEvent::TrackAdded(index));
+	}
+
 	fn get_track_by_id(&mut self, id: u64) -> Result<(u32, &mut Track)> {
 		let track_index = self
 			.tracks
@@ -58,171 +167,488 @@ impl FormatData {
 	}
 }
 
+/// Hints and overrides used when opening a format
+#[derive(Clone, Default, Debug)]
+pub struct OpenOptions {
+	/// The file name extension, overriding the one reported by the stream
+	pub extension: Option<String>,
+
+	/// The MIME type, overriding the one reported by the stream
+	pub mime_type: Option<String>,
+
+	/// Open this format without probing. Either the name of a
+	/// registered demuxer, or of any avformat demuxer
+	pub format: Option<String>
+}
+
 pub struct Format {
 	demuxer: Demuxer,
-	data: FormatData
-}
+	data: FormatData,
 
-static DEMUXERS: &[DemuxerClass<'static>] = &[&mkv::MatroskaClass];
+	/// A packet read along with events, held until they are delivered
+	pending: Option<Packet>
+}
 
 #[asynchronous]
 impl Format {
-	async fn av_open(mut reader: Reader) -> Result<Self> {
-		const AV_SUPPORTED_FORMATS: &[&str] = &[
-			"aac", "matroska", "webm", "mp3", "ogg", "mpegts", "wav", "mov", "mp4", "m4a", "3gp",
-			"flac"
-		];
-
+	/// Probe with avformat, returning the result only if the
+	/// registry allows the probed format
+	async fn av_probe(
+		reader: &mut Reader, registry: &FormatRegistry, hints: &ProbeHints
+	) -> Result<Option<ProbeResult>> {
 		reader.set_peeking(true).await;
 
-		let Some(probe) = AVFormatClass::probe(&mut reader).await? else {
-			return Err(FormatError::UnknownFormat.into());
-		};
+		let probe = AVFormatClass::probe(reader, hints).await;
 
 		reader.set_peeking(false).await;
 
-		let mut supported = false;
+		let probe = match probe {
+			Ok(Some(probe)) => probe,
+			Ok(None) => return Ok(None),
+			Err(err) if err == ReaderError::PeekBufferExhausted => return Ok(None),
+			Err(err) => return Err(err)
+		};
 
-		for format in probe.name.split(',') {
-			if AV_SUPPORTED_FORMATS.contains(&format) {
-				supported = true;
+		debug!(
+			"== Probed format {} (avformat) with a score of {:.2}%",
+			probe.long_name,
+			probe.score * 100.0
+		);
 
-				break;
-			}
+		if !registry.av_format_enabled(&probe.name) {
+			debug!("== Ignoring as this format is not enabled in the registry");
+
+			return Ok(None);
 		}
 
-		if !supported {
-			debug!(
-				"== Probed format {} (avformat) with a score of {:.2}%",
-				probe.long_name,
-				probe.score * 100.0
-			);
-			debug!("== Exiting as this is not on the list of allowed formats");
+		Ok(Some(probe))
+	}
 
+	/// Open the format named in `options`, skipping the probe
+	async fn open_override(reader: Reader, registry: &FormatRegistry, name: &str) -> Result<Self> {
+		let demuxer = if let Some(class) = registry.find(name) {
+			class.create(reader).await?
+		} else if let Some(format) = AVFormatClass::find(name) {
+			AVFormatClass::create(reader, Some(format))
+		} else {
 			return Err(FormatError::UnknownFormat.into());
-		}
+		};
 
 		let this = Self {
-			demuxer: AVFormatClass::create(reader, Some(probe.format)),
-			data: FormatData::default()
+			demuxer,
+			data: FormatData::default(),
+			pending: None
 		};
 
-		debug!(target: &this, "== Probed format {} (avformat) with a score of {:.2}%", probe.long_name, probe.score * 100.0);
+		debug!(target: &this, "== Opened format {} by override", name);
 
 		Ok(this)
 	}
 
+	/// Open a format with the default registry
 	pub async fn open(stream: Stream) -> Result<Self> {
+		Self::open_with(stream, &OpenOptions::default()).await
+	}
+
+	/// Open a format with the default registry and hints from `options`
+	pub async fn open_with(stream: Stream, options: &OpenOptions) -> Result<Self> {
+		Self::open_in(stream, &FormatRegistry::default(), options).await
+	}
+
+	/// Open a format with the demuxers in `registry`
+	///
+	/// Hints in `options` take precedence over those reported by the stream
+	pub async fn open_in(
+		stream: Stream, registry: &FormatRegistry, options: &OpenOptions
+	) -> Result<Self> {
+		let hints = ProbeHints {
+			extension: options
+				.extension
+				.clone()
+				.or_else(|| stream.extension().map(ToString::to_string)),
+			mime_type: options
+				.mime_type
+				.clone()
+				.or_else(|| stream.content_type().map(ToString::to_string))
+		};
+
 		let mut reader = Reader::new(stream);
-		let mut demuxer = None;
-		let mut score = 0.0;
 
-		for demuxer_class in DEMUXERS {
+		let mut this = if let Some(name) = &options.format {
+			Self::open_override(reader, registry, name).await?
+		} else {
+			Self::probe_and_create(reader, registry, &hints).await?
+		};
+
+		this.demuxer.open(&mut this.data).await?;
+		this.data.events.clear();
+
+		for track in &mut this.data.tracks {
+			Self::init_track(track, &this.data.metadata);
+		}
+
+		Ok(this)
+	}
+
+	async fn probe_and_create(
+		mut reader: Reader, registry: &FormatRegistry, hints: &ProbeHints
+	) -> Result<Self> {
+		let mut best: Option<(DemuxerClass<'_>, f32, f32)> = None;
+
+		for (demuxer_class, _) in registry.demuxers() {
+			let hint_score = hints.score(demuxer_class.extensions(), demuxer_class.mime_types());
+
 			reader.set_peeking(true).await;
 
-			score = match demuxer_class.probe(&mut reader).await {
+			/* the hints only count when the bytes are inconclusive, or to break a tie */
+			let score = match demuxer_class.probe(&mut reader, hints).await {
 				Ok(score) => score,
-				Err(err) if err == ReaderError::PeekBufferExhausted => 0.0,
+				Err(err) if err == ReaderError::PeekBufferExhausted => hint_score,
 				Err(err) => return Err(err)
 			};
 
 			reader.set_peeking(false).await;
 
-			if score > 0.0 {
-				demuxer = Some(demuxer_class);
+			trace!(
+				"== Probed format {} with a score of {:.2}%",
+				demuxer_class.name(),
+				score * 100.0
+			);
+
+			let best_scores = best.map(|(_, score, hint)| (score, hint));
 
-				break;
+			if outscores(score, hint_score, best_scores) {
+				best = Some((demuxer_class, score, hint_score));
 			}
 		}
 
-		let mut this = match demuxer {
-			Some(demuxer) => {
+		let av_probe = if registry.av_fallback() && best.is_none_or(|(_, score, _)| score < 1.0) {
+			Self::av_probe(&mut reader, registry, hints).await?
+		} else {
+			None
+		};
+
+		let best_score = best.map_or(0.0, |(_, score, _)| score);
+
+		if let Some(probe) = av_probe.as_ref().filter(|probe| probe.score > best_score) {
+			let this = Self {
+				demuxer: AVFormatClass::create(reader, Some(probe.format)),
+				data: FormatData::default(),
+				pending: None
+			};
+
+			debug!(target: &this, "== Opened format {} (avformat) with a score of {:.2}%", probe.long_name, probe.score * 100.0);
+
+			return Ok(this);
+		}
+
+		/* nothing matched the bytes, so trust the hints */
+		if let Some((name, score)) = registry
+			.av_format_for(hints)
+			.filter(|_| best.is_none() && av_probe.is_none())
+		{
+			if let Some(format) = AVFormatClass::find(name) {
 				let this = Self {
-					demuxer: demuxer.create(reader).await?,
-					data: FormatData::default()
+					demuxer: AVFormatClass::create(reader, Some(format)),
+					data: FormatData::default(),
+					pending: None
 				};
 
-				debug!(target: &this, "== Probed format {} with a score of {:.2}%", demuxer.name(), score * 100.0);
+				debug!(target: &this, "== Opened format {} (avformat) from hints with a score of {:.2}%", name, score * 100.0);
 
-				this
+				return Ok(this);
 			}
+		}
 
-			None => Self::av_open(reader).await?
+		let Some((demuxer, score, _)) = best else {
+			return Err(FormatError::UnknownFormat.into());
 		};
 
-		this.demuxer.open(&mut this.data).await?;
+		let this = Self {
+			demuxer: demuxer.create(reader).await?,
+			data: FormatData::default(),
+			pending: None
+		};
 
-		for track in &mut this.data.tracks {
-			track.codec_params.ty = track.ty;
-
-			if track.start_time == 0 {
-				track.start_time = track.time_base.rescale(
-					#[allow(clippy::arithmetic_side_effects)]
-					-(track.codec_params.delay as i64),
-					track.codec_params.time_base
-				);
+		debug!(target: &this, "== Opened format {} with a score of {:.2}%", demuxer.name(), score * 100.0);
+
+		Ok(this)
+	}
+
+	fn init_track(track: &mut Track, metadata: &Metadata) {
+		track.codec_params.ty = track.ty;
+
+		Self::init_codec_params(track);
+
+		if track.ty == MediaType::Audio {
+			Self::init_gapless(track, metadata);
+		}
+
+		/* after the gapless tags, which may replace the delay */
+		if track.start_time == 0 {
+			track.start_time = track.time_base.rescale(
+				#[allow(clippy::arithmetic_side_effects)]
+				-(track.codec_params.delay as i64),
+				track.codec_params.time_base
+			);
+		}
+	}
+
+	/// Fill in the delay and padding from an iTunes gapless tag, which
+	/// counts samples at the output sample rate
+	fn init_gapless(track: &mut Track, metadata: &Metadata) {
+		let Some(tag) = track
+			.metadata
+			.get("iTunSMPB")
+			.or_else(|| metadata.get("iTunSMPB"))
+		else {
+			return;
+		};
+
+		let Some((delay, padding, valid_samples)) = parse_itunsmpb(tag) else {
+			return;
+		};
+
+		let params = &mut track.codec_params;
+
+		params.delay = delay;
+		params.padding = padding;
+		params.valid_samples = Some(valid_samples).filter(|samples| *samples != 0);
+	}
+
+	fn init_codec_params(track: &mut Track) {
+		#[allow(clippy::single_match)]
+		match track.codec_params.ty {
+			MediaType::Audio => {
+				track
+					.codec_params
+					.change_time_base(Rational::inverse(track.codec_params.sample_rate));
 			}
 
-			#[allow(clippy::single_match)]
-			match track.codec_params.ty {
-				MediaType::Audio => {
-					track
-						.codec_params
-						.change_time_base(Rational::inverse(track.codec_params.sample_rate));
-				}
+			_ => ()
+		}
+	}
+
+	fn apply_event(&mut self, event: FormatEvent) {
+		match event {
+			FormatEvent::TrackAdded(index) => {
+				Self::init_track(&mut self.data.tracks[index as usize], &self.data.metadata);
+			}
+
+			FormatEvent::CodecParamsChanged(index) => {
+				let track = &mut self.data.tracks[index as usize];
 
-				_ => ()
+				track.codec_params.ty = track.ty;
+				track.parser = None;
+
+				Self::init_codec_params(track);
 			}
+
+			FormatEvent::MetadataUpdated(_) => ()
 		}
+	}
 
-		Ok(this)
+	/// Returns `false` if the packet is discarded
+	fn process_packet(&mut self, packet: &mut Packet) -> Result<bool> {
+		let track = &mut self.data.tracks[packet.track_index as usize];
+
+		match track.discard {
+			Discard::All => return Ok(false),
+			Discard::NonKey if !packet.flags.intersects(PacketFlag::Keyframe) => return Ok(false),
+			_ => ()
+		}
+
+		if track.parse != CodecParse::None {
+			let parser = match &mut track.parser {
+				Some(parser) => parser,
+				None => track
+					.parser
+					.insert(CodecParser::new(track.parse, &mut track.codec_params)?)
+			};
+
+			parser.parse(packet)?;
+		}
+
+		Ok(true)
 	}
 
+	/// Read the next packet or event. Events are delivered before
+	/// any packet that depends on them
+	pub async fn read(&mut self) -> Result<Option<FormatItem>> {
+		loop {
+			if let Some(event) = self.data.events.pop_front() {
+				self.apply_event(event);
+
+				return Ok(Some(FormatItem::Event(event)));
+			}
+
+			let mut packet = match self.pending.take() {
+				Some(packet) => packet,
+				None => {
+					let mut packet = Packet::new();
+
+					let read = self
+						.demuxer
+						.read_packet(&mut self.data, &mut packet)
+						.await?;
+
+					if !self.data.events.is_empty() {
+						if read {
+							self.pending = Some(packet);
+						}
+
+						continue;
+					}
+
+					if !read {
+						return Ok(None);
+					}
+
+					packet
+				}
+			};
+
+			if self.process_packet(&mut packet)? {
+				return Ok(Some(FormatItem::Packet(packet)));
+			}
+		}
+	}
+
+	/// Read the next packet, skipping any events
 	#[inline]
 	pub async fn read_packet(&mut self) -> Result<Option<Packet>> {
 		loop {
-			let mut packet = Packet::new();
-
-			if !self
-				.demuxer
-				.read_packet(&mut self.data, &mut packet)
-				.await?
-			{
-				return Ok(None);
+			match self.read().await? {
+				Some(FormatItem::Packet(packet)) => break Ok(Some(packet)),
+				Some(FormatItem::Event(_)) => (),
+				None => break Ok(None)
 			}
+		}
+	}
 
-			let track = &mut self.data.tracks[packet.track_index as usize];
+	/// Convert a sample or frame index into the time base of the track
+	fn frame_to_timecode(&self, track_index: u32, index: u64) -> Result<u64> {
+		let track = &self.data.tracks[track_index as usize];
+		let params = &track.codec_params;
 
-			match track.discard {
-				Discard::All => continue,
-				Discard::NonKey if !packet.flags.intersects(PacketFlag::Keyframe) => continue,
-				_ => ()
-			}
+		if track.time_base.num == 0 {
+			return Err(FormatError::CannotSeek.into());
+		}
+
+		let unit = match track.ty {
+			MediaType::Audio if params.sample_rate != 0 => Rational::inverse(params.sample_rate),
+			MediaType::Video if params.framerate.num != 0 => params.framerate.invert(),
+			_ => return Err(FormatError::CannotSeek.into())
+		};
 
-			if track.parse != CodecParse::None {
-				let parser = match &mut track.parser {
-					Some(parser) => parser,
-					None => track
-						.parser
-						.insert(CodecParser::new(track.parse, &mut track.codec_params)?)
+		Ok(track.time_base.rescale(index, unit))
+	}
+
+	/// Seek so that decoding can resume at `timecode`, in the time base
+	/// of the track. Returns the requested time, which frames decoded
+	/// after an accurate seek should be trimmed to
+	///
+	/// Byte seeks have no known time, so they are never accurate and
+	/// the target is [`UNKNOWN_TIMESTAMP`]
+	pub async fn seek_with(
+		&mut self, track_index: u32, mut timecode: u64, mut flags: BitFlags<SeekFlag>,
+		mode: SeekMode
+	) -> Result<SeekTarget> {
+		if flags.intersects(SeekFlag::Byte) {
+			self.seek(track_index, timecode, flags).await?;
+
+			return Ok(SeekTarget {
+				track_index,
+				timestamp: UNKNOWN_TIMESTAMP,
+				time_base: self.data.tracks[track_index as usize].time_base
+			});
+		}
+
+		if flags.intersects(SeekFlag::Frame) {
+			timecode = self.frame_to_timecode(track_index, timecode)?;
+			flags.remove(SeekFlag::Frame);
+		}
+
+		if mode == SeekMode::Accurate {
+			flags.remove(SeekFlag::Forward);
+			flags.insert(SeekFlag::Backward);
+		}
+
+		let track = &self.data.tracks[track_index as usize];
+		let target = SeekTarget {
+			track_index,
+			timestamp: timecode.try_into().map_err(|_| ErrorKind::Overflow)?,
+			time_base: track.time_base
+		};
+
+		let seek_to = match mode {
+			SeekMode::Fast => timecode,
+			SeekMode::Accurate => {
+				let params = &track.codec_params;
+				let preroll = u64::from(params.seek_preroll.saturating_add(params.delay));
+				let preroll = if params.time_base.num == 0 || track.time_base.num == 0 {
+					0
+				} else {
+					track.time_base.rescale(preroll, params.time_base)
 				};
 
-				parser.parse(&mut packet)?;
+				timecode.saturating_sub(preroll)
 			}
+		};
 
-			break Ok(Some(packet));
-		}
+		trace!(target: &*self, "== Seeking to {} for target {} ({:?})", seek_to, timecode, mode);
+
+		self.seek(track_index, seek_to, flags).await?;
+
+		Ok(target)
 	}
 
+	/// Seek to `timecode`, in the time base of the track, or to the
+	/// byte offset or frame index if `flags` say so. Without
+	/// [`SeekFlag::Forward`], the seek lands at or before the target
 	pub async fn seek(
-		&mut self, track_index: u32, timecode: u64, flags: BitFlags<SeekFlag>
+		&mut self, track_index: u32, mut timecode: u64, mut flags: BitFlags<SeekFlag>
 	) -> Result<()> {
+		if flags.contains(SeekFlag::Backward | SeekFlag::Forward) {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		/* demuxers only deal with times and bytes */
+		if flags.intersects(SeekFlag::Frame) && !flags.intersects(SeekFlag::Byte) {
+			timecode = self.frame_to_timecode(track_index, timecode)?;
+		}
+
+		flags.remove(SeekFlag::Frame);
+
+		self.pending = None;
 		self.demuxer
 			.seek(&mut self.data, track_index, timecode, flags)
 			.await
 	}
 }
 
+/// Whether a probe `score` with `hint_score` from the hints beats the best
+/// so far. Earlier demuxers have a higher priority, so they win full ties
+#[allow(clippy::float_cmp)]
+fn outscores(score: f32, hint_score: f32, best: Option<(f32, f32)>) -> bool {
+	let (best_score, best_hint) = best.unwrap_or((0.0, 0.0));
+
+	score > best_score || (score > 0.0 && score == best_score && hint_score > best_hint)
+}
+
+/// Parse an iTunSMPB tag, such as
+/// ` 00000000 00000840 000001CA 00000000003F31F6 ...`,
+/// into the delay, padding and valid sample count
+fn parse_itunsmpb(tag: &str) -> Option<(u32, u32, u64)> {
+	let mut fields = tag.split_whitespace().skip(1);
+
+	let delay = u32::from_str_radix(fields.next()?, 16).ok()?;
+	let padding = u32::from_str_radix(fields.next()?, 16).ok()?;
+	let valid_samples = u64::from_str_radix(fields.next()?, 16).ok()?;
+
+	Some((delay, padding, valid_samples))
+}
+
 impl Deref for Format {
 	type Target = FormatData;
 
@@ -236,3 +662,30 @@ impl DerefMut for Format {
 		&mut self.data
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn probe_ties() {
+		assert!(outscores(0.5, 0.0, None));
+		assert!(!outscores(0.0, 0.75, None));
+		assert!(outscores(0.6, 0.0, Some((0.5, 0.75))));
+
+		/* hints only break ties between equal probe scores */
+		assert!(!outscores(0.4, 0.75, Some((0.5, 0.0))));
+		assert!(outscores(0.5, 0.75, Some((0.5, 0.5))));
+		assert!(!outscores(0.5, 0.5, Some((0.5, 0.5))));
+		assert!(!outscores(0.5, 0.0, Some((0.5, 0.5))));
+	}
+
+	#[test]
+	fn itunsmpb() {
+		let tag = " 00000000 00000840 000001CA 00000000003F31F6 00000000 00000000";
+
+		assert_eq!(parse_itunsmpb(tag), Some((0x840, 0x1ca, 0x003f_31f6)));
+		assert_eq!(parse_itunsmpb(" 00000000 00000840"), None);
+		assert_eq!(parse_itunsmpb(" 00000000 0000084G 000001CA 0"), None);
+	}
+}
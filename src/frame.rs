@@ -10,6 +10,11 @@ pub struct Frame {
 	pub time_base: Rational,
 	pub decode_timestamp: i64,
 	pub presentation_timestamp: i64,
+
+	/// The presentation timestamp if known, or one guessed by the decoder
+	/// from the packet timestamps. Use this to place decoded frames.
+	/// Frames out of a filter graph carry the filtered timestamp
+	pub best_effort_timestamp: i64,
 	pub duration: u64,
 	pub flags: BitFlags<FrameFlag>,
 
@@ -32,6 +37,312 @@ impl Frame {
 		Self::default()
 	}
 
+	/// An audio frame with buffers for `samples` samples
+	///
+	/// # Panics
+	/// if the sample rate or count is out of range
+	#[allow(clippy::unwrap_used)]
+	pub fn audio(
+		sample_format: SampleFormat, ch_layout: &ChannelLayout, sample_rate: u32, samples: u32
+	) -> Result<Self> {
+		if sample_format == SampleFormat::None || ch_layout.channel_count() == 0 {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let mut frame = Self::new();
+
+		frame.data.format = sample_format as i32;
+		frame.data.ch_layout = ch_layout.into();
+		frame.data.sample_rate = sample_rate.try_into().unwrap();
+		frame.data.nb_samples = samples.try_into().unwrap();
+		frame.data.get_buffer()?;
+		frame.get_fields_from_inner(Some(MediaType::Audio));
+
+		Ok(frame)
+	}
+
+	/// A video frame with buffers for a `width` by `height` picture
+	///
+	/// # Panics
+	/// if the size is out of range
+	#[allow(clippy::unwrap_used)]
+	pub fn video(pixel_format: PixelFormat, width: u32, height: u32) -> Result<Self> {
+		if pixel_format == PixelFormat::None || width == 0 || height == 0 {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let mut frame = Self::new();
+
+		frame.data.format = pixel_format as i32;
+		frame.data.width = width.try_into().unwrap();
+		frame.data.height = height.try_into().unwrap();
+		frame.data.get_buffer()?;
+		frame.get_fields_from_inner(Some(MediaType::Video));
+
+		Ok(frame)
+	}
+
+	/// The number of sample planes. One for packed formats,
+	/// or one per channel for planar formats
+	#[must_use]
+	pub fn sample_plane_count(&self) -> usize {
+		self.data.sample_planes().map_or(0, |(planes, _)| planes)
+	}
+
+	/// The length of sample plane `index` as `T`
+	fn sample_plane_len<T: Sample>(&self, index: usize) -> Result<usize> {
+		let format = SampleFormat::from(self.data.format);
+
+		if format != T::PACKED && format != T::PLANAR {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		match self.data.sample_planes() {
+			Some((planes, len)) if index < planes => Ok(len),
+			_ => Err(ErrorKind::InvalidInput.into())
+		}
+	}
+
+	/// The samples in plane `index`. Packed formats have one plane of
+	/// interleaved channels, and planar formats have one per channel
+	///
+	/// `T` must match the sample format, such as `f32` for `F32` or `F32P`
+	pub fn sample_plane<T: Sample>(&self, index: usize) -> Result<&[T]> {
+		let len = self.sample_plane_len::<T>(index)?;
+
+		/* Safety: the plane exists and holds `len` values of `T` */
+		Ok(unsafe { self.data.plane(index, len) })
+	}
+
+	/// The samples in plane `index`, for writing. Buffers shared
+	/// with other frames are copied first
+	pub fn sample_plane_mut<T: Sample>(&mut self, index: usize) -> Result<&mut [T]> {
+		let len = self.sample_plane_len::<T>(index)?;
+
+		self.data.make_writable()?;
+
+		/* Safety: the plane exists, holds `len` values of `T`, and is writable */
+		Ok(unsafe { self.data.plane_mut(index, len) })
+	}
+
+	/// The format `T` is stored in, for `planes` buffers of `channels`
+	/// channels. One buffer is interleaved, and one per channel is planar
+	fn pcm_format<T: Sample>(planes: usize, channels: usize) -> Result<SampleFormat> {
+		match planes {
+			0 => Err(ErrorKind::InvalidInput.into()),
+			1 => Ok(T::PACKED),
+			planes if planes == channels => Ok(T::PLANAR),
+			_ => Err(ErrorKind::InvalidInput.into())
+		}
+	}
+
+	/// An audio frame in `sample_format` from PCM samples, which are
+	/// either one buffer of interleaved channels or one buffer per channel.
+	/// Samples are converted with swresample if the formats differ
+	///
+	/// The frame's time base is set to `1 / sample_rate`, and
+	/// `timestamp` is the position of the first sample in it
+	pub fn from_samples<T: Sample>(
+		planes: &[&[T]], ch_layout: &ChannelLayout, sample_rate: u32, sample_format: SampleFormat,
+		timestamp: i64
+	) -> Result<Self> {
+		let channels = usize::from(ch_layout.channel_count());
+		let format = Self::pcm_format::<T>(planes.len(), channels)?;
+		let values = planes[0].len();
+
+		if sample_rate == 0 || channels == 0 || planes.iter().any(|plane| plane.len() != values) {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let samples = if format.is_planar() {
+			values
+		} else if values % channels == 0 {
+			values / channels
+		} else {
+			return Err(ErrorKind::InvalidInput.into());
+		};
+
+		let samples: u32 = samples.try_into().map_err(|_| ErrorKind::Overflow)?;
+
+		let mut frame = Self::audio(format, ch_layout, sample_rate, samples)?;
+
+		for (index, plane) in planes.iter().enumerate() {
+			let output = frame.sample_plane_mut::<T>(index)?;
+			let len = output.len();
+
+			output.copy_from_slice(&plane[..len]);
+		}
+
+		frame.time_base = Rational::inverse(sample_rate);
+		frame.presentation_timestamp = timestamp;
+		frame.best_effort_timestamp = timestamp;
+		frame.decode_timestamp = timestamp;
+		frame.duration = samples.into();
+
+		if format == sample_format {
+			Ok(frame)
+		} else {
+			frame.convert_samples(sample_format)
+		}
+	}
+
+	/// Copy the samples of an audio frame into PCM buffers as `T`, either
+	/// one buffer of interleaved channels or one buffer per channel.
+	/// Samples are converted with swresample if the formats differ
+	///
+	/// Returns the number of samples copied per channel
+	#[allow(clippy::cast_sign_loss)]
+	pub fn copy_into<T: Sample>(&self, planes: &mut [&mut [T]]) -> Result<u32> {
+		let channels = self.data.ch_layout.nb_channels.max(0) as usize;
+		let format = Self::pcm_format::<T>(planes.len(), channels)?;
+		let converted;
+		let frame = if SampleFormat::from(self.data.format) == format {
+			self
+		} else {
+			converted = self.convert_samples(format)?;
+
+			&converted
+		};
+
+		for (index, plane) in planes.iter_mut().enumerate() {
+			let samples = frame.sample_plane::<T>(index)?;
+
+			plane
+				.get_mut(..samples.len())
+				.ok_or(ErrorKind::InvalidInput)?
+				.copy_from_slice(samples);
+		}
+
+		Ok(frame.data.nb_samples.max(0) as u32)
+	}
+
+	/// A copy of an audio frame converted to `sample_format`, keeping
+	/// the channel layout, sample rate and timestamps
+	pub fn convert_samples(&self, sample_format: SampleFormat) -> Result<Self> {
+		if sample_format == SampleFormat::None || self.data.sample_planes().is_none() {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let mut frame = Self::new();
+		let mut context = av::SwrContext::new();
+
+		frame.data.format = sample_format as i32;
+		frame.data.ch_layout = (&ChannelLayout::from(&self.data.ch_layout)).into();
+		frame.data.sample_rate = self.data.sample_rate;
+
+		context.convert_frame(&mut frame.data, &self.data)?;
+		frame.get_fields_from_inner(Some(MediaType::Audio));
+
+		frame.time_base = self.time_base;
+		frame.presentation_timestamp = self.presentation_timestamp;
+		frame.best_effort_timestamp = self.best_effort_timestamp;
+		frame.decode_timestamp = self.decode_timestamp;
+		frame.duration = self.duration;
+		frame.flags = self.flags;
+
+		Ok(frame)
+	}
+
+	/// The bytes of picture plane `index`, such as the Y, U or V plane
+	/// of a YUV picture. Returns `None` if there is no such plane
+	pub fn picture_plane(&self, index: usize) -> Result<Option<PicturePlane<'_>>> {
+		let Some((stride, width, height)) = self.data.picture_plane(index)? else {
+			return Ok(None);
+		};
+
+		let len = picture_plane_len(stride, width, height)?;
+
+		Ok(Some(PicturePlane {
+			/* Safety: the plane exists and holds `height` rows */
+			data: unsafe { self.data.plane(index, len) },
+			stride,
+			width,
+			height
+		}))
+	}
+
+	/// The bytes of picture plane `index`, for writing. Buffers shared
+	/// with other frames are copied first
+	pub fn picture_plane_mut(&mut self, index: usize) -> Result<Option<PicturePlaneMut<'_>>> {
+		let Some((stride, width, height)) = self.data.picture_plane(index)? else {
+			return Ok(None);
+		};
+
+		let len = picture_plane_len(stride, width, height)?;
+
+		self.data.make_writable()?;
+
+		Ok(Some(PicturePlaneMut {
+			/* Safety: the plane exists, holds `height` rows, and is writable */
+			data: unsafe { self.data.plane_mut(index, len) },
+			stride,
+			width,
+			height
+		}))
+	}
+
+	/// Remove the first `count` samples of an audio frame, moving
+	/// its timestamp forward to match
+	///
+	/// # Panics
+	/// if `count` is out of range
+	#[allow(clippy::unwrap_used, clippy::cast_possible_wrap)]
+	pub fn skip_samples(&mut self, count: u32) -> Result<()> {
+		let count = count.min(self.samples);
+
+		if count == 0 {
+			return Ok(());
+		}
+
+		self.data.skip_samples(count.try_into().unwrap())?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.samples -= count);
+
+		if self.time_base.num == 0 || self.sample_rate == 0 {
+			return Ok(());
+		}
+
+		let skipped = self
+			.time_base
+			.rescale(u64::from(count), Rational::inverse(self.sample_rate));
+
+		for timestamp in [
+			&mut self.presentation_timestamp,
+			&mut self.best_effort_timestamp
+		] {
+			if *timestamp != UNKNOWN_TIMESTAMP {
+				*timestamp = timestamp.saturating_add(skipped as i64);
+			}
+		}
+
+		self.duration = self.duration.saturating_sub(skipped);
+
+		Ok(())
+	}
+
+	/// Keep only the first `count` samples of an audio frame
+	///
+	/// # Panics
+	/// if `count` is out of range
+	#[allow(clippy::unwrap_used)]
+	pub fn truncate_samples(&mut self, count: u32) {
+		if count >= self.samples {
+			return;
+		}
+
+		self.data.truncate_samples(count.try_into().unwrap());
+
+		if self.time_base.num != 0 && self.sample_rate != 0 {
+			self.duration = self
+				.time_base
+				.rescale(u64::from(count), Rational::inverse(self.sample_rate));
+		}
+
+		self.samples = count;
+	}
+
 	/// # Panics
 	/// if some of the fields in the frame are out of range
 	#[allow(clippy::unwrap_used)]
@@ -56,6 +367,7 @@ impl Frame {
 		self.time_base = self.data.time_base.into();
 		self.decode_timestamp = self.data.pkt_dts;
 		self.presentation_timestamp = self.data.pts;
+		self.best_effort_timestamp = self.data.best_effort_timestamp;
 		self.duration = self.data.duration.try_into().unwrap();
 		self.flags = BitFlags::from_bits_truncate(self.data.flags as u32);
 
@@ -78,6 +390,163 @@ impl Frame {
 	}
 }
 
+/// A type stored in audio samples
+///
+/// # Safety
+/// `PACKED` and `PLANAR` must store samples as `Self`
+pub unsafe trait Sample: Copy + Send + Sync + 'static {
+	const PACKED: SampleFormat;
+	const PLANAR: SampleFormat;
+
+	/// The sample as a float, where full scale is -1.0 to 1.0
+	fn to_f64(self) -> f64;
+
+	/// The sample closest to `value`, where full scale is -1.0 to 1.0.
+	/// Integer samples saturate
+	fn from_f64(value: f64) -> Self;
+}
+
+macro_rules! impl_sample {
+	($type:ty, $packed:ident, $planar:ident, $zero:literal, $scale:literal) => {
+		/* Safety: the formats store samples as this type */
+		unsafe impl Sample for $type {
+			const PACKED: SampleFormat = SampleFormat::$packed;
+			const PLANAR: SampleFormat = SampleFormat::$planar;
+
+			#[allow(clippy::cast_precision_loss, clippy::cast_lossless)]
+			fn to_f64(self) -> f64 {
+				(self as f64 - $zero) / $scale
+			}
+
+			#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+			fn from_f64(value: f64) -> Self {
+				/* float to int casts saturate */
+				value.mul_add($scale, $zero).round() as $type
+			}
+		}
+	};
+}
+
+impl_sample!(u8, U8, U8P, 128.0, 128.0);
+impl_sample!(i16, I16, I16P, 0.0, 32768.0);
+impl_sample!(i32, I32, I32P, 0.0, 2_147_483_648.0);
+impl_sample!(i64, I64, I64P, 0.0, 9_223_372_036_854_775_808.0);
+
+/* Safety: the formats store samples as f32 */
+unsafe impl Sample for f32 {
+	const PACKED: SampleFormat = SampleFormat::F32;
+	const PLANAR: SampleFormat = SampleFormat::F32P;
+
+	fn to_f64(self) -> f64 {
+		self.into()
+	}
+
+	#[allow(clippy::cast_possible_truncation)]
+	fn from_f64(value: f64) -> Self {
+		value as Self
+	}
+}
+
+/* Safety: the formats store samples as f64 */
+unsafe impl Sample for f64 {
+	const PACKED: SampleFormat = SampleFormat::F64;
+	const PLANAR: SampleFormat = SampleFormat::F64P;
+
+	fn to_f64(self) -> f64 {
+		self
+	}
+
+	fn from_f64(value: f64) -> Self {
+		value
+	}
+}
+
+/// The bytes a picture plane spans. The last row may
+/// end before the stride does
+fn picture_plane_len(stride: usize, width: usize, height: usize) -> Result<usize> {
+	if height == 0 {
+		return Ok(0);
+	}
+
+	if width > stride {
+		return Err(ErrorKind::InvalidData.into());
+	}
+
+	#[allow(clippy::arithmetic_side_effects)]
+	stride
+		.checked_mul(height - 1)
+		.and_then(|len| len.checked_add(width))
+		.ok_or_else(|| ErrorKind::Overflow.into())
+}
+
+macro_rules! picture_plane_rows {
+	() => {
+		/// Row `y` of the plane, without the padding at the end
+		#[must_use]
+		pub fn row(&self, y: usize) -> Option<&[u8]> {
+			if y >= self.height {
+				return None;
+			}
+
+			#[allow(clippy::arithmetic_side_effects)]
+			let start = y * self.stride;
+
+			#[allow(clippy::arithmetic_side_effects)]
+			Some(&self.data[start..start + self.width])
+		}
+
+		/// The rows of the plane, from top to bottom
+		pub fn rows(&self) -> impl Iterator<Item = &[u8]> + '_ {
+			(0..self.height).filter_map(|y| self.row(y))
+		}
+	};
+}
+
+/// A view of one plane of a picture
+///
+/// Rows are `stride` bytes apart, of which the first
+/// `width` are part of the picture
+pub struct PicturePlane<'a> {
+	pub data: &'a [u8],
+	pub stride: usize,
+
+	/// Bytes per row
+	pub width: usize,
+	pub height: usize
+}
+
+impl PicturePlane<'_> {
+	picture_plane_rows!();
+}
+
+/// A writable view of one plane of a picture
+pub struct PicturePlaneMut<'a> {
+	pub data: &'a mut [u8],
+	pub stride: usize,
+
+	/// Bytes per row
+	pub width: usize,
+	pub height: usize
+}
+
+impl PicturePlaneMut<'_> {
+	picture_plane_rows!();
+
+	/// Row `y` of the plane for writing, without the padding at the end
+	#[must_use]
+	pub fn row_mut(&mut self, y: usize) -> Option<&mut [u8]> {
+		if y >= self.height {
+			return None;
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let start = y * self.stride;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		Some(&mut self.data[start..start + self.width])
+	}
+}
+
 impl Default for Frame {
 	fn default() -> Self {
 		Self {
@@ -86,6 +555,7 @@ impl Default for Frame {
 			time_base: Rational::default(),
 			decode_timestamp: UNKNOWN_TIMESTAMP,
 			presentation_timestamp: UNKNOWN_TIMESTAMP,
+			best_effort_timestamp: UNKNOWN_TIMESTAMP,
 			duration: 0,
 			flags: BitFlags::default(),
 
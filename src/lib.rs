@@ -10,22 +10,34 @@ pub mod errors;
 pub mod filter;
 pub mod format;
 pub mod frame;
+pub mod loudness;
 mod macros;
+pub mod muxer;
 pub mod packet;
 pub mod rational;
 mod reader;
+pub mod resample;
+pub mod source;
+pub mod transcode;
+pub mod trim;
 
 use self::demuxer::*;
-use self::reader::*;
+pub use self::reader::*;
 pub mod resource;
-pub use av::UNKNOWN_TIMESTAMP;
+pub use av::{INPUT_BUFFER_PADDING, UNKNOWN_TIMESTAMP};
 pub use codec::*;
 pub use errors::*;
 pub use format::*;
 pub use frame::*;
+pub use loudness::*;
+pub use muxer::*;
 pub use packet::*;
 pub use rational::*;
+pub use resample::*;
 pub use resource::*;
+pub use source::*;
+pub use transcode::*;
+pub use trim::*;
 
 extern crate self as xx_mpeg;
 
@@ -0,0 +1,649 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+use super::*;
+
+/// The loudness ReplayGain 2.0 normalizes to, in LUFS
+pub const REPLAY_GAIN_REFERENCE: f64 = -18.0;
+
+/// Blocks quieter than this are never measured, in LUFS
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Blocks this far below the mean are left out of the integrated loudness
+const INTEGRATED_RELATIVE_GATE: f64 = -10.0;
+
+/// Blocks this far below the mean are left out of the loudness range
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+
+/// Sub-blocks of 100 ms in the momentary and short-term windows
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+/// Samples of history per phase of the true peak interpolator
+const PHASE_TAPS: usize = 12;
+
+fn energy_to_lufs(energy: f64) -> f64 {
+	10.0f64.mul_add(energy.log10(), -0.691)
+}
+
+fn lufs_to_energy(lufs: f64) -> f64 {
+	10.0f64.powf((lufs + 0.691) / 10.0)
+}
+
+fn amplitude_to_db(amplitude: f64) -> f64 {
+	20.0 * amplitude.log10()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn mean<I>(values: I) -> Option<f64>
+where
+	I: Iterator<Item = f64>
+{
+	let (sum, count) = values.fold((0.0, 0usize), |(sum, count), value| {
+		#[allow(clippy::arithmetic_side_effects)]
+		(sum + value, count + 1)
+	});
+
+	(count != 0).then(|| sum / count as f64)
+}
+
+/// A second order IIR filter, in transposed direct form II
+#[derive(Clone, Copy)]
+struct Biquad {
+	b: [f64; 3],
+	a: [f64; 2],
+	z: [f64; 2]
+}
+
+impl Biquad {
+	fn process(&mut self, x: f64) -> f64 {
+		let y = self.b[0].mul_add(x, self.z[0]);
+
+		self.z[0] = self.b[1].mul_add(x, self.z[1]) - self.a[0] * y;
+		self.z[1] = self.b[2].mul_add(x, -self.a[1] * y);
+
+		y
+	}
+}
+
+/// The two stages of the K-weighting filter of ITU-R BS.1770,
+/// for any sample rate
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+	let rate = f64::from(sample_rate);
+
+	/* a high shelf, modelling the head */
+	let (f0, gain, q) = (
+		1_681.974_450_955_533,
+		3.999_843_853_973_347,
+		0.707_175_236_955_419_6
+	);
+	let k = (PI * f0 / rate).tan();
+	let vh = 10.0f64.powf(gain / 20.0);
+	let vb = vh.powf(0.499_666_774_154_541_6);
+	let a0 = 1.0 + k / q + k * k;
+
+	let shelf = Biquad {
+		b: [
+			(vh + vb * k / q + k * k) / a0,
+			2.0 * (k * k - vh) / a0,
+			(vh - vb * k / q + k * k) / a0
+		],
+		a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+		z: [0.0; 2]
+	};
+
+	/* a high pass */
+	let (f0, q) = (38.135_470_876_024_44, 0.500_327_037_323_877_3);
+	let k = (PI * f0 / rate).tan();
+	let a0 = 1.0 + k / q + k * k;
+
+	let high_pass = Biquad {
+		b: [1.0, -2.0, 1.0],
+		a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+		z: [0.0; 2]
+	};
+
+	[shelf, high_pass]
+}
+
+/// The coefficients of a windowed sinc interpolator, oversampling
+/// by `factor`. Phase `p` uses every `factor`th coefficient from `p`
+#[allow(clippy::cast_precision_loss, clippy::arithmetic_side_effects)]
+fn interpolator(factor: usize) -> Vec<f64> {
+	let len = factor * PHASE_TAPS;
+	let center = (len - 1) as f64 / 2.0;
+
+	(0..len)
+		.map(|i| {
+			let t = (i as f64 - center) / factor as f64;
+			let sinc = if t == 0.0 {
+				1.0
+			} else {
+				(PI * t).sin() / (PI * t)
+			};
+			let window = 0.5 - 0.5 * (2.0 * PI * (i as f64 + 0.5) / len as f64).cos();
+
+			sinc * window
+		})
+		.collect()
+}
+
+/// How much a channel counts towards loudness, as in ITU-R BS.1770
+const fn channel_weight(channel: Channel) -> f64 {
+	match channel {
+		Channel::LowFrequency | Channel::LowFrequency2 => 0.0,
+		Channel::SideLeft |
+		Channel::SideRight |
+		Channel::BackLeft |
+		Channel::BackRight |
+		Channel::SurroundDirectLeft |
+		Channel::SurroundDirectRight => 1.41,
+		_ => 1.0
+	}
+}
+
+struct ChannelMeter {
+	weight: f64,
+	filter: [Biquad; 2],
+
+	/// The latest samples, newest first, for true peak interpolation
+	history: [f64; PHASE_TAPS]
+}
+
+/// The results of a loudness measurement
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Loudness {
+	/// The gated loudness of everything measured, in LUFS.
+	/// `None` if everything was silent
+	pub integrated: Option<f64>,
+
+	/// The relative gate of the integrated loudness, in LUFS.
+	/// Quieter blocks are left out of the measurement
+	pub threshold: Option<f64>,
+
+	/// The spread between quiet and loud parts, in LU
+	pub range: Option<f64>,
+
+	/// The highest peak between samples, as a linear
+	/// amplitude where 1.0 is full scale
+	pub true_peak: f64,
+
+	/// The highest sample, as a linear amplitude
+	pub sample_peak: f64
+}
+
+impl Loudness {
+	/// The true peak, in dBTP
+	#[must_use]
+	pub fn true_peak_db(&self) -> f64 {
+		amplitude_to_db(self.true_peak)
+	}
+
+	/// The gain in dB that brings the integrated loudness to `target` LUFS
+	#[must_use]
+	pub fn gain_to(&self, target: f64) -> Option<f64> {
+		self.integrated.map(|integrated| target - integrated)
+	}
+}
+
+/// Measures loudness as in EBU R128 and ITU-R BS.1770, over a stream of
+/// audio frames
+///
+/// Momentary loudness is over the last 400 ms, and short-term loudness
+/// over the last 3 s. True peaks are found by oversampling
+pub struct LoudnessMeter {
+	sample_rate: u32,
+	channels: Vec<ChannelMeter>,
+	interpolator: Vec<f64>,
+	oversample: usize,
+
+	/// The weighted energy of the current 100 ms sub-block
+	energy: f64,
+	block_samples: u32,
+	block_len: u32,
+
+	/// The energy of the latest sub-blocks, newest last
+	sub_blocks: VecDeque<f64>,
+
+	/// The mean energy of each momentary and short-term window,
+	/// every 100 ms
+	momentary_blocks: Vec<f64>,
+	short_term_blocks: Vec<f64>,
+
+	true_peak: f64,
+	sample_peak: f64
+}
+
+impl LoudnessMeter {
+	/// A meter for audio at `sample_rate` with the channels of `ch_layout`
+	#[must_use]
+	pub fn new(sample_rate: u32, ch_layout: &ChannelLayout) -> Self {
+		let channels = (0..ch_layout.channel_count())
+			.map(|index| ChannelMeter {
+				weight: channel_weight(ch_layout.channel(index)),
+				filter: k_weighting(sample_rate),
+				history: [0.0; PHASE_TAPS]
+			})
+			.collect();
+
+		/* true peaks need a rate of at least 192 khz */
+		let oversample = if sample_rate < 96_000 {
+			4
+		} else if sample_rate < 192_000 {
+			2
+		} else {
+			1
+		};
+
+		Self {
+			sample_rate,
+			channels,
+			interpolator: interpolator(oversample),
+			oversample,
+			energy: 0.0,
+			block_samples: 0,
+			block_len: (sample_rate / 10).max(1),
+			sub_blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS),
+			momentary_blocks: Vec::new(),
+			short_term_blocks: Vec::new(),
+			true_peak: 0.0,
+			sample_peak: 0.0
+		}
+	}
+
+	/// Measure the samples of `frame`, which must have the sample rate
+	/// and channel count of the meter
+	pub fn add_frame(&mut self, frame: &Frame) -> Result<()> {
+		if frame.sample_rate != self.sample_rate ||
+			usize::from(frame.ch_layout.channel_count()) != self.channels.len()
+		{
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		match frame.sample_format {
+			SampleFormat::U8 | SampleFormat::U8P => self.add_samples::<u8>(frame),
+			SampleFormat::I16 | SampleFormat::I16P => self.add_samples::<i16>(frame),
+			SampleFormat::I32 | SampleFormat::I32P => self.add_samples::<i32>(frame),
+			SampleFormat::I64 | SampleFormat::I64P => self.add_samples::<i64>(frame),
+			SampleFormat::F32 | SampleFormat::F32P => self.add_samples::<f32>(frame),
+			SampleFormat::F64 | SampleFormat::F64P => self.add_samples::<f64>(frame),
+			SampleFormat::None => Err(ErrorKind::InvalidInput.into())
+		}
+	}
+
+	#[allow(clippy::arithmetic_side_effects)]
+	fn add_samples<T: Sample>(&mut self, frame: &Frame) -> Result<()> {
+		let planes: Vec<&[T]> = (0..frame.sample_plane_count())
+			.map(|plane| frame.sample_plane::<T>(plane))
+			.collect::<Result<_>>()?;
+
+		let channels = self.channels.len();
+		let planar = planes.len() > 1 || channels == 1;
+
+		for sample in 0..frame.samples as usize {
+			for channel in 0..channels {
+				let value = if planar {
+					planes.get(channel).and_then(|plane| plane.get(sample))
+				} else {
+					planes
+						.first()
+						.and_then(|plane| plane.get(sample * channels + channel))
+				};
+
+				let Some(value) = value else {
+					return Err(ErrorKind::InvalidData.into());
+				};
+
+				self.add_sample(channel, value.to_f64());
+			}
+
+			self.block_samples += 1;
+
+			if self.block_samples == self.block_len {
+				self.end_block();
+			}
+		}
+
+		Ok(())
+	}
+
+	#[allow(clippy::arithmetic_side_effects)]
+	fn add_sample(&mut self, channel: usize, value: f64) {
+		let meter = &mut self.channels[channel];
+
+		self.sample_peak = self.sample_peak.max(value.abs());
+
+		meter.history.copy_within(0..PHASE_TAPS - 1, 1);
+		meter.history[0] = value;
+
+		for phase in 0..self.oversample {
+			let interpolated: f64 = meter
+				.history
+				.iter()
+				.enumerate()
+				.map(|(tap, sample)| sample * self.interpolator[tap * self.oversample + phase])
+				.sum();
+
+			self.true_peak = self.true_peak.max(interpolated.abs());
+		}
+
+		if meter.weight == 0.0 {
+			return;
+		}
+
+		let weighted = meter
+			.filter
+			.iter_mut()
+			.fold(value, |value, stage| stage.process(value));
+
+		self.energy += meter.weight * weighted * weighted;
+	}
+
+	#[allow(clippy::arithmetic_side_effects, clippy::cast_precision_loss)]
+	fn end_block(&mut self) {
+		if self.sub_blocks.len() == SHORT_TERM_BLOCKS {
+			self.sub_blocks.pop_front();
+		}
+
+		self.sub_blocks.push_back(self.energy);
+		self.energy = 0.0;
+		self.block_samples = 0;
+
+		let window = |blocks: usize| {
+			let energy: f64 = self.sub_blocks.iter().rev().take(blocks).sum();
+
+			energy / (blocks as f64 * f64::from(self.block_len))
+		};
+
+		if self.sub_blocks.len() >= MOMENTARY_BLOCKS {
+			self.momentary_blocks.push(window(MOMENTARY_BLOCKS));
+		}
+
+		if self.sub_blocks.len() >= SHORT_TERM_BLOCKS {
+			self.short_term_blocks.push(window(SHORT_TERM_BLOCKS));
+		}
+	}
+
+	/// The loudness of the last 400 ms, in LUFS
+	#[must_use]
+	pub fn momentary(&self) -> Option<f64> {
+		self.momentary_blocks.last().copied().map(energy_to_lufs)
+	}
+
+	/// The loudness of the last 3 s, in LUFS
+	#[must_use]
+	pub fn short_term(&self) -> Option<f64> {
+		self.short_term_blocks.last().copied().map(energy_to_lufs)
+	}
+
+	/// The loudness of everything measured so far
+	#[must_use]
+	pub fn loudness(&self) -> Loudness {
+		Self::album(std::slice::from_ref(self))
+	}
+
+	/// The loudness of several tracks measured as one, such as
+	/// for album gain
+	#[must_use]
+	pub fn album(meters: &[Self]) -> Loudness {
+		let momentary = || {
+			meters
+				.iter()
+				.flat_map(|meter| meter.momentary_blocks.iter().copied())
+		};
+		let short_term = meters
+			.iter()
+			.flat_map(|meter| meter.short_term_blocks.iter().copied());
+		let (integrated, threshold) = integrated(momentary);
+
+		Loudness {
+			integrated,
+			threshold,
+			range: range(short_term),
+			true_peak: meters
+				.iter()
+				.map(|meter| meter.true_peak)
+				.fold(0.0, f64::max),
+			sample_peak: meters
+				.iter()
+				.map(|meter| meter.sample_peak)
+				.fold(0.0, f64::max)
+		}
+	}
+}
+
+/// The gated loudness of momentary blocks, and the relative gate
+fn integrated<I, F>(blocks: F) -> (Option<f64>, Option<f64>)
+where
+	I: Iterator<Item = f64>,
+	F: Fn() -> I
+{
+	let absolute = lufs_to_energy(ABSOLUTE_GATE);
+	let Some(mean_energy) = mean(blocks().filter(|energy| *energy > absolute)) else {
+		return (None, None);
+	};
+
+	let relative = mean_energy * 10.0f64.powf(INTEGRATED_RELATIVE_GATE / 10.0);
+	let gate = relative.max(absolute);
+	let integrated = mean(blocks().filter(|energy| *energy > gate));
+
+	(
+		integrated.map(energy_to_lufs),
+		Some(energy_to_lufs(relative))
+	)
+}
+
+/// The loudness range of short-term blocks, as in EBU Tech 3342
+#[allow(
+	clippy::arithmetic_side_effects,
+	clippy::cast_possible_truncation,
+	clippy::cast_precision_loss,
+	clippy::cast_sign_loss
+)]
+fn range<I>(blocks: I) -> Option<f64>
+where
+	I: Iterator<Item = f64>
+{
+	let absolute = lufs_to_energy(ABSOLUTE_GATE);
+	let blocks: Vec<_> = blocks.filter(|energy| *energy > absolute).collect();
+	let mean_energy = mean(blocks.iter().copied())?;
+	let gate = mean_energy * 10.0f64.powf(RANGE_RELATIVE_GATE / 10.0);
+
+	let mut loudness: Vec<_> = blocks
+		.into_iter()
+		.filter(|energy| *energy > gate)
+		.map(energy_to_lufs)
+		.collect();
+
+	if loudness.is_empty() {
+		return None;
+	}
+
+	loudness.sort_by(f64::total_cmp);
+
+	let percentile = |p: f64| loudness[((loudness.len() - 1) as f64 * p).round() as usize];
+
+	Some(percentile(0.95) - percentile(0.10))
+}
+
+impl ReplayGain {
+	/// ReplayGain 2.0 values from measured loudness, relative to
+	/// [`REPLAY_GAIN_REFERENCE`]
+	#[must_use]
+	#[allow(clippy::cast_possible_truncation)]
+	pub fn from_loudness(track: &Loudness, album: Option<&Loudness>) -> Self {
+		let gain = |loudness: &Loudness| {
+			loudness
+				.gain_to(REPLAY_GAIN_REFERENCE)
+				.map(|gain| gain as f32)
+		};
+
+		Self {
+			track_gain: gain(track),
+			track_peak: Some(track.true_peak as f32),
+			album_gain: album.and_then(gain),
+			album_peak: album.map(|album| album.true_peak as f32)
+		}
+	}
+}
+
+#[cfg(test)]
+#[allow(
+	clippy::unwrap_used,
+	clippy::arithmetic_side_effects,
+	clippy::cast_precision_loss,
+	clippy::cast_possible_truncation
+)]
+mod tests {
+	use super::*;
+
+	const RATE: u32 = 48_000;
+
+	/// A meter fed a 997 hz mono sine, with each segment
+	/// lasting `seconds` at a peak of `amplitude`
+	fn measure(segments: &[(f64, u32)]) -> LoudnessMeter {
+		let mut meter = LoudnessMeter::new(RATE, &ChannelLayout::LAYOUT_MONO);
+		let mut position = 0u64;
+
+		for &(amplitude, seconds) in segments {
+			/* 100 ms at a time */
+			for _ in 0..seconds * 10 {
+				let values: Vec<f32> = (position..position + u64::from(RATE / 10))
+					.map(|index| {
+						let t = index as f64 / f64::from(RATE);
+
+						(amplitude * (2.0 * PI * 997.0 * t).sin()) as f32
+					})
+					.collect();
+
+				let frame = Frame::from_samples(
+					&[&values],
+					&ChannelLayout::LAYOUT_MONO,
+					RATE,
+					SampleFormat::F32,
+					position as i64
+				)
+				.unwrap();
+
+				meter.add_frame(&frame).unwrap();
+				position += u64::from(RATE / 10);
+			}
+		}
+
+		meter
+	}
+
+	fn assert_near(value: Option<f64>, expected: f64, tolerance: f64) {
+		let value = value.unwrap();
+
+		assert!(
+			(value - expected).abs() <= tolerance,
+			"{value} != {expected}"
+		);
+	}
+
+	#[test]
+	fn sine() {
+		/* a full scale sine reads -3.01 LUFS in one channel */
+		let meter = measure(&[(0.1, 10)]);
+		let loudness = meter.loudness();
+
+		assert_near(loudness.integrated, -23.01, 0.05);
+		assert_near(loudness.threshold, -33.01, 0.05);
+		assert_near(meter.momentary(), -23.01, 0.05);
+		assert_near(meter.short_term(), -23.01, 0.05);
+		assert_near(loudness.range, 0.0, 0.05);
+		assert_near(loudness.gain_to(REPLAY_GAIN_REFERENCE), 5.01, 0.05);
+
+		assert!((loudness.sample_peak - 0.1).abs() < 0.001);
+		assert!((loudness.true_peak - 0.1).abs() < 0.002);
+	}
+
+	#[test]
+	fn silence() {
+		let meter = measure(&[(0.0, 5)]);
+		let loudness = meter.loudness();
+
+		assert_eq!(loudness.integrated, None);
+		assert_eq!(loudness.threshold, None);
+		assert_eq!(loudness.range, None);
+		assert!(loudness.true_peak.abs() < f64::EPSILON);
+		assert_eq!(ReplayGain::from_loudness(&loudness, None).track_gain, None);
+	}
+
+	#[test]
+	fn short() {
+		/* too short for a short-term block */
+		let meter = measure(&[(0.1, 1)]);
+
+		assert_near(meter.loudness().integrated, -23.01, 0.05);
+		assert_eq!(meter.short_term(), None);
+		assert_eq!(meter.loudness().range, None);
+	}
+
+	#[test]
+	fn absolute_gate() {
+		/* -83 LUFS is below the absolute gate, and is not averaged in */
+		let meter = measure(&[(0.1, 10), (0.000_1, 10)]);
+
+		assert_near(meter.loudness().integrated, -23.01, 0.1);
+	}
+
+	#[test]
+	fn relative_gate() {
+		/* -63 LUFS passes the absolute gate, but not the relative one */
+		let meter = measure(&[(0.1, 10), (0.001, 10)]);
+		let loudness = meter.loudness();
+
+		assert_near(loudness.integrated, -23.01, 0.1);
+
+		/* ungated, half of the time at -63 would pull it down by 3 LU */
+		assert_near(loudness.threshold, -36.0, 0.1);
+	}
+
+	#[test]
+	fn range_spread() {
+		/* 10 LU between the halves, both within the range gate */
+		let meter = measure(&[(0.1, 20), (0.031_622_776, 20)]);
+
+		assert_near(meter.loudness().range, 10.0, 0.1);
+	}
+
+	#[test]
+	fn range_gate() {
+		/* the quiet half is 30 LU down, so the range gate drops it */
+		let meter = measure(&[(0.1, 20), (0.003_162_277_7, 20)]);
+
+		assert!(meter.loudness().range.unwrap() < 5.0);
+	}
+
+	#[test]
+	fn album() {
+		let loud = measure(&[(0.1, 5)]);
+		let quiet = measure(&[(0.031_622_776, 5)]);
+		let album = LoudnessMeter::album(&[loud, quiet]);
+
+		/* the mean energy of -23 and -33 LUFS */
+		assert_near(album.integrated, -25.6, 0.1);
+		assert!((album.true_peak - 0.1).abs() < 0.002);
+
+		let gain = ReplayGain::from_loudness(&album, Some(&album));
+
+		assert!((gain.album_gain.unwrap() - 7.6).abs() < 0.1);
+	}
+
+	#[test]
+	fn mismatched_frame() {
+		let mut meter = LoudnessMeter::new(RATE, &ChannelLayout::LAYOUT_STEREO);
+		let frame = Frame::from_samples(
+			&[&[0.0f32; 16][..]],
+			&ChannelLayout::LAYOUT_MONO,
+			RATE,
+			SampleFormat::F32,
+			0
+		)
+		.unwrap();
+
+		assert!(meter.add_frame(&frame).is_err());
+	}
+}
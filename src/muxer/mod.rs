@@ -0,0 +1,163 @@
+use xx_core::async_std::io::*;
+
+use super::*;
+
+pub mod sink;
+
+pub use sink::*;
+
+struct OutputTrack {
+	/// The time base of packets written to the track
+	time_base: Rational,
+	last_timestamp: Option<i64>
+}
+
+/// Writes packets into a container format, using avformat
+///
+/// Tracks are added before the first packet is written, which
+/// writes the header. [`Muxer::finish`] must be called to write
+/// the trailer, as some formats are unplayable without it
+pub struct Muxer {
+	context: av::MuxContext,
+	sink: Sink,
+	tracks: Vec<OutputTrack>,
+	packet: av::AVPacket,
+	header_written: bool
+}
+
+#[asynchronous]
+impl Muxer {
+	/// Create a muxer for the avformat output format `name`,
+	/// such as `webm`, `ogg` or `mp4`
+	pub fn new(name: &str, sink: Sink) -> Result<Self> {
+		Ok(Self {
+			context: av::MuxContext::new(name)?,
+			sink,
+			tracks: Vec::new(),
+			packet: av::AVPacket::new(),
+			header_written: false
+		})
+	}
+
+	/// Add a track described by `params`. Packets written to it have
+	/// timestamps in `time_base`, unless they carry their own
+	///
+	/// Returns the index of the new track
+	pub fn add_track(&mut self, params: &CodecParams, time_base: Rational) -> Result<u32> {
+		if self.header_written || time_base.num == 0 {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let index = self.context.add_stream(params, time_base)?;
+
+		self.tracks
+			.push(OutputTrack { time_base, last_timestamp: None });
+
+		Ok(index)
+	}
+
+	/// Set a metadata entry of the output, such as `title`.
+	/// Must be called before the first packet
+	pub fn set_metadata(&mut self, key: &str, value: &str) -> Result<()> {
+		if self.header_written {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		self.context.set_metadata(key, value)
+	}
+
+	async fn write_header(&mut self) -> Result<()> {
+		if self.tracks.is_empty() {
+			return Err(FormatError::NoTracks.into());
+		}
+
+		self.context.write_header(&mut self.sink).await?;
+		self.header_written = true;
+
+		Ok(())
+	}
+
+	/// Write `packet` to the track at `packet.track_index`. Timestamps
+	/// are rescaled to the time base the format uses
+	///
+	/// Packets of a track must be written in decode order
+	#[allow(clippy::unwrap_used, clippy::missing_panics_doc)]
+	pub async fn write_packet(&mut self, packet: &Packet) -> Result<()> {
+		if !self.header_written {
+			self.write_header().await?;
+		}
+
+		let index = packet.track_index;
+		let track = self
+			.tracks
+			.get_mut(index as usize)
+			.ok_or(FormatError::TrackNotFound)?;
+
+		let time_base = if packet.time_base.num != 0 {
+			packet.time_base
+		} else {
+			track.time_base
+		};
+
+		let stream_time_base = self.context.stream_time_base(index);
+		let rescale =
+			|value: i64| stream_time_base.rescale_timestamp(value, time_base, Rounding::NearInf);
+
+		let mut pts = rescale(packet.presentation_timestamp);
+		let mut dts = match rescale(packet.decode_timestamp) {
+			/* without reordering, packets are decoded when presented */
+			UNKNOWN_TIMESTAMP => pts,
+			dts => dts
+		};
+
+		/* rounding into a coarser time base can make timestamps collide */
+		if let Some(last) = track.last_timestamp {
+			if dts != UNKNOWN_TIMESTAMP && dts <= last {
+				dts = last.checked_add(1).ok_or(ErrorKind::Overflow)?;
+			}
+		}
+
+		if dts != UNKNOWN_TIMESTAMP {
+			track.last_timestamp = Some(dts);
+
+			/* a packet cannot be presented before it is decoded */
+			if pts != UNKNOWN_TIMESTAMP {
+				pts = pts.max(dts);
+			}
+		}
+
+		let duration = stream_time_base.rescale_rnd(packet.duration, time_base, Rounding::NearInf);
+
+		self.packet.set_data(packet.data.buffer(), &packet.data);
+		self.packet.stream_index = index.try_into().unwrap();
+		self.packet.time_base = stream_time_base.into();
+		self.packet.dts = dts;
+		self.packet.pts = pts;
+		self.packet.duration = duration.try_into().unwrap();
+
+		#[allow(clippy::cast_possible_wrap)]
+		(self.packet.flags = packet.flags.bits() as i32);
+
+		/* the muxer takes its own reference to the data if kept */
+		let result = self
+			.context
+			.write_packet(&mut self.packet, &mut self.sink)
+			.await;
+
+		self.packet.unref();
+
+		result
+	}
+
+	/// Write any buffered packets and the trailer, returning the sink
+	pub async fn finish(mut self) -> Result<Sink> {
+		if !self.header_written {
+			self.write_header().await?;
+		}
+
+		self.context.write_trailer(&mut self.sink).await?;
+		self.sink.flush().await?;
+
+		Ok(self.sink)
+	}
+}
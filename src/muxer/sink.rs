@@ -0,0 +1,200 @@
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use xx_pulse::fs::File;
+
+use super::*;
+
+pub trait SinkImpl: Write + Seek {
+	/// Returns `true` if the sink may be seekable. Some formats
+	/// go back to fill in sizes and indexes once muxing finishes
+	fn seekable(&self) -> bool {
+		false
+	}
+}
+
+pub type Sink = Box<dyn SinkImpl + Send + Sync>;
+
+/// A sink writing to a local file
+pub struct FileSink {
+	file: File,
+	position: u64,
+	length: u64
+}
+
+#[asynchronous]
+impl FileSink {
+	/// Create or truncate the file at `path`
+	pub async fn create(path: &Path) -> Result<Self> {
+		let file = File::create(path).await?;
+
+		Ok(Self { file, position: 0, length: 0 })
+	}
+}
+
+#[asynchronous]
+impl Write for FileSink {
+	async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+		let wrote = self.file.write(buf).await?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += wrote as u64);
+
+		self.length = self.length.max(self.position);
+
+		Ok(wrote)
+	}
+
+	async fn flush(&mut self) -> Result<()> {
+		self.file.flush().await
+	}
+}
+
+#[asynchronous]
+impl Seek for FileSink {
+	async fn seek(&mut self, seek: SeekFrom) -> Result<u64> {
+		self.position = self.file.seek(seek).await?;
+
+		Ok(self.position)
+	}
+
+	fn stream_position_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_position(&mut self) -> Result<u64> {
+		Ok(self.position)
+	}
+
+	fn stream_len_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_len(&mut self) -> Result<u64> {
+		Ok(self.length)
+	}
+}
+
+impl SinkImpl for FileSink {
+	fn seekable(&self) -> bool {
+		true
+	}
+}
+
+struct MemoryData {
+	data: Vec<u8>,
+	position: u64
+}
+
+/// A sink that collects the output in memory
+///
+/// Clones share the same data, so the output can be taken
+/// after the muxer is done with its sink
+#[derive(Clone)]
+pub struct MemorySink {
+	inner: Arc<Mutex<MemoryData>>
+}
+
+impl MemorySink {
+	#[must_use]
+	pub fn new() -> Self {
+		Self {
+			inner: Arc::new(Mutex::new(MemoryData { data: Vec::new(), position: 0 }))
+		}
+	}
+
+	fn lock(&self) -> MutexGuard<'_, MemoryData> {
+		match self.inner.lock() {
+			Ok(inner) => inner,
+			Err(err) => err.into_inner()
+		}
+	}
+
+	/// Take the bytes written so far, leaving the sink empty
+	#[must_use]
+	pub fn take(&self) -> Vec<u8> {
+		let mut inner = self.lock();
+
+		inner.position = 0;
+
+		std::mem::take(&mut inner.data)
+	}
+
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.lock().data.len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+impl Default for MemorySink {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[asynchronous]
+impl Write for MemorySink {
+	#[allow(clippy::arithmetic_side_effects)]
+	async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+		let mut inner = self.lock();
+		let start: usize = inner.position.try_into().map_err(|_| ErrorKind::Overflow)?;
+		let end = start.checked_add(buf.len()).ok_or(ErrorKind::Overflow)?;
+
+		if inner.data.len() < end {
+			inner.data.resize(end, 0);
+		}
+
+		inner.data[start..end].copy_from_slice(buf);
+		inner.position += buf.len() as u64;
+
+		Ok(buf.len())
+	}
+
+	async fn flush(&mut self) -> Result<()> {
+		Ok(())
+	}
+}
+
+#[asynchronous]
+impl Seek for MemorySink {
+	async fn seek(&mut self, seek: SeekFrom) -> Result<u64> {
+		let mut inner = self.lock();
+		let pos = match seek {
+			SeekFrom::Current(pos) => inner.position.checked_add_signed(pos),
+			SeekFrom::Start(pos) => Some(pos),
+			SeekFrom::End(pos) => (inner.data.len() as u64).checked_add_signed(pos)
+		};
+
+		inner.position = pos.ok_or(ErrorKind::InvalidInput)?;
+
+		Ok(inner.position)
+	}
+
+	fn stream_position_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_position(&mut self) -> Result<u64> {
+		Ok(self.lock().position)
+	}
+
+	fn stream_len_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_len(&mut self) -> Result<u64> {
+		Ok(self.lock().data.len() as u64)
+	}
+}
+
+impl SinkImpl for MemorySink {
+	fn seekable(&self) -> bool {
+		true
+	}
+}
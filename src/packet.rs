@@ -1,13 +1,246 @@
+use std::ops::{Deref, Range};
+
 use super::*;
 
+/// The payload of a packet, in a reference counted buffer
+///
+/// Clones and slices share the buffer without copying. The data
+/// is always followed by at least [`INPUT_BUFFER_PADDING`] readable
+/// bytes, as decoders may read past the end. They are zeroed unless
+/// the data is a slice, where they are the rest of the buffer
+#[derive(Clone, Default)]
+pub struct PacketData {
+	buffer: Option<av::AVBufferRef>,
+	offset: usize,
+	len: usize
+}
+
+impl PacketData {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self { buffer: None, offset: 0, len: 0 }
+	}
+
+	/// `len` zeroed bytes
+	#[must_use]
+	pub fn alloc(len: usize) -> Self {
+		if len == 0 {
+			return Self::new();
+		}
+
+		Self {
+			buffer: Some(av::AVBufferRef::alloc(len)),
+			offset: 0,
+			len
+		}
+	}
+
+	/// Share the data of a packet from ffmpeg, copying it only
+	/// if it is not ref counted
+	pub(crate) fn from_av(packet: &av::AVPacket) -> Self {
+		/* Safety: the packet data is valid */
+		let data = unsafe { packet.data().as_ref() };
+
+		let Some(buffer) = packet.buffer() else {
+			return Self::from(data);
+		};
+
+		/* Safety: the buffer is valid */
+		let start = unsafe { buffer.data().as_ref() }.as_ptr() as usize;
+		let offset = (data.as_ptr() as usize).saturating_sub(start);
+
+		Self { buffer: Some(buffer), offset, len: data.len() }
+	}
+
+	pub(crate) const fn buffer(&self) -> Option<&av::AVBufferRef> {
+		self.buffer.as_ref()
+	}
+
+	#[must_use]
+	pub const fn len(&self) -> usize {
+		self.len
+	}
+
+	#[must_use]
+	pub const fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// The bytes in `range`, sharing the buffer. Returns `None`
+	/// if the range is out of bounds
+	#[must_use]
+	pub fn slice(&self, range: Range<usize>) -> Option<Self> {
+		if range.start > range.end || range.end > self.len {
+			return None;
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		Some(Self {
+			buffer: self.buffer.clone(),
+			offset: self.offset + range.start,
+			len: range.end - range.start
+		})
+	}
+
+	/// Keep only the first `len` bytes, zeroing the padding after them.
+	/// The buffer is copied if it is shared
+	pub fn truncate(&mut self, len: usize) -> Result<()> {
+		if len >= self.len {
+			return Ok(());
+		}
+
+		self.resize(len)
+	}
+
+	/// Grow or shrink to `len` bytes, zeroing any new bytes and the
+	/// padding. The buffer is copied if it is shared, or can't be
+	/// resized in place
+	pub fn resize(&mut self, len: usize) -> Result<()> {
+		let Some(buffer) = &mut self.buffer else {
+			*self = Self::alloc(len);
+
+			return Ok(());
+		};
+
+		buffer.realloc(self.offset.saturating_add(len))?;
+
+		/* Safety: the buffer was just reallocated, and is not shared */
+		let bytes = unsafe { buffer.data_mut().as_mut() };
+
+		/* bytes past the old length may be stale, after a truncate */
+		#[allow(clippy::arithmetic_side_effects)]
+		bytes[self.offset + self.len.min(len)..].fill(0);
+
+		self.len = len;
+
+		Ok(())
+	}
+
+	/// The bytes for writing. The buffer is copied first if it is shared
+	pub fn make_mut(&mut self) -> Result<&mut [u8]> {
+		let Some(buffer) = &mut self.buffer else {
+			return Ok(&mut []);
+		};
+
+		buffer.make_writable()?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let range = self.offset..self.offset + self.len;
+
+		/* Safety: the buffer is writable and not shared */
+		Ok(unsafe { &mut buffer.data_mut().as_mut()[range] })
+	}
+}
+
+impl Deref for PacketData {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		let Some(buffer) = &self.buffer else {
+			return &[];
+		};
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let range = self.offset..self.offset + self.len;
+
+		/* Safety: the buffer is valid while referenced */
+		unsafe { &buffer.data().as_ref()[range] }
+	}
+}
+
+impl From<&[u8]> for PacketData {
+	fn from(value: &[u8]) -> Self {
+		let mut data = Self::alloc(value.len());
+
+		if let Some(buffer) = &mut data.buffer {
+			/* Safety: the buffer was just allocated, and is not shared */
+			let bytes = unsafe { buffer.data_mut().as_mut() };
+
+			bytes[..value.len()].copy_from_slice(value);
+		}
+
+		data
+	}
+}
+
+impl From<Vec<u8>> for PacketData {
+	fn from(value: Vec<u8>) -> Self {
+		Self::from(value.as_slice())
+	}
+}
+
+/// ReplayGain values. Gains are in dB, and peaks are
+/// linear amplitudes where 1.0 is full scale
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReplayGain {
+	pub track_gain: Option<f32>,
+	pub track_peak: Option<f32>,
+	pub album_gain: Option<f32>,
+	pub album_peak: Option<f32>
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Subsample {
+	pub clear_bytes: u32,
+	pub protected_bytes: u32
+}
+
+/// What is needed to decrypt a packet, as in ISO common encryption
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncryptionInfo {
+	/// The fourcc of the scheme, such as `cenc` or `cbcs`
+	pub scheme: u32,
+	pub crypt_byte_block: u32,
+	pub skip_byte_block: u32,
+	pub key_id: Vec<u8>,
+	pub iv: Vec<u8>,
+
+	/// The clear and encrypted ranges of the packet, in order.
+	/// The whole packet is encrypted if empty
+	pub subsamples: Vec<Subsample>
+}
+
+/// Extra data attached to a packet
+///
+/// Samples to skip are in [`Packet::trim_start`] and [`Packet::trim_end`]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SideData {
+	/// The codec config changes to this, starting with the packet
+	NewExtradata(Vec<u8>),
+	ReplayGain(ReplayGain),
+
+	/// A matroska `BlockAdditional`, such as an alpha
+	/// channel or HDR metadata
+	BlockAdditional {
+		id: u64,
+		data: Vec<u8>
+	},
+	Encryption(Box<EncryptionInfo>)
+}
+
 #[allow(clippy::partial_pub_fields)]
 pub struct Packet {
-	pub data: Vec<u8>,
+	pub data: PacketData,
 	pub time_base: Rational,
 	pub duration: u64,
-	pub timestamp: i64,
+
+	/// When the packet is presented, once decoded
+	pub presentation_timestamp: i64,
+
+	/// When the packet is decoded. Differs from the presentation
+	/// timestamp when frames are reordered, such as video with B-frames
+	pub decode_timestamp: i64,
 	pub track_index: u32,
-	pub flags: BitFlags<PacketFlag>
+	pub flags: BitFlags<PacketFlag>,
+
+	/// Decoded samples to drop from the start of this packet
+	pub trim_start: u32,
+
+	/// Decoded samples to drop from the end of this packet
+	pub trim_end: u32,
+
+	pub side_data: Vec<SideData>
 }
 
 impl Packet {
@@ -15,17 +248,125 @@ impl Packet {
 	pub fn new() -> Self {
 		Self::default()
 	}
+
+	/// Copy the payload, duration, flags and side data of `packet`,
+	/// sharing its buffer
+	#[allow(clippy::cast_sign_loss)]
+	pub(crate) fn read_from_av(&mut self, packet: &av::AVPacket) {
+		/* skip samples are set from encoder delay and padding, such as LAME headers */
+		(self.trim_start, self.trim_end) = packet.skip_samples();
+
+		self.data = PacketData::from_av(packet);
+		self.duration = packet.duration.try_into().unwrap_or(0);
+		self.flags = BitFlags::from_bits_truncate(packet.flags as u32);
+		self.side_data = packet.read_side_data();
+	}
 }
 
 impl Default for Packet {
 	fn default() -> Self {
 		Self {
-			data: Vec::new(),
+			data: PacketData::new(),
 			time_base: Rational::default(),
 			duration: 0,
-			timestamp: UNKNOWN_TIMESTAMP,
+			presentation_timestamp: UNKNOWN_TIMESTAMP,
+			decode_timestamp: UNKNOWN_TIMESTAMP,
 			track_index: 0,
-			flags: Default::default()
+			flags: Default::default(),
+			trim_start: 0,
+			trim_end: 0,
+			side_data: Vec::new()
 		}
 	}
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn resize_keeps_data() {
+		let mut data = PacketData::from(&[1, 2, 3][..]);
+
+		data.resize(5).unwrap();
+
+		assert_eq!(&*data, [1, 2, 3, 0, 0]);
+
+		data.resize(2).unwrap();
+
+		assert_eq!(&*data, [1, 2]);
+
+		data.resize(4).unwrap();
+
+		assert_eq!(&*data, [1, 2, 0, 0]);
+	}
+
+	#[test]
+	fn resize_zeroes_padding() {
+		let mut data = PacketData::from(&[0xff; 64][..]);
+
+		data.resize(8).unwrap();
+
+		let buffer = data.buffer().unwrap();
+
+		/* Safety: the buffer is valid */
+		let bytes = unsafe { buffer.data().as_ref() };
+
+		assert!(bytes.len() >= 8 + INPUT_BUFFER_PADDING);
+		assert!(bytes[8..].iter().all(|byte| *byte == 0));
+	}
+
+	#[test]
+	fn resize_shared() {
+		let data = PacketData::from(&[1, 2, 3][..]);
+		let mut copy = data.clone();
+
+		copy.resize(4).unwrap();
+		copy.make_mut().unwrap()[0] = 9;
+
+		assert_eq!(&*data, [1, 2, 3]);
+		assert_eq!(&*copy, [9, 2, 3, 0]);
+	}
+
+	#[test]
+	fn truncate_then_resize() {
+		let mut data = PacketData::from(&[1, 2, 3, 4][..]);
+
+		data.truncate(2).unwrap();
+
+		let buffer = data.buffer().unwrap();
+
+		/* Safety: the buffer is valid */
+		let bytes = unsafe { buffer.data().as_ref() };
+
+		assert_eq!(&*data, [1, 2]);
+		assert!(bytes[2..2 + INPUT_BUFFER_PADDING]
+			.iter()
+			.all(|byte| *byte == 0));
+
+		data.resize(4).unwrap();
+
+		assert_eq!(&*data, [1, 2, 0, 0]);
+	}
+
+	#[test]
+	fn truncate_shared() {
+		let data = PacketData::from(&[1, 2, 3, 4][..]);
+		let mut slice = data.slice(1..3).unwrap();
+
+		slice.truncate(1).unwrap();
+
+		assert_eq!(&*data, [1, 2, 3, 4]);
+		assert_eq!(&*slice, [2]);
+	}
+
+	#[test]
+	fn resize_empty() {
+		let mut data = PacketData::new();
+
+		data.resize(3).unwrap();
+
+		assert_eq!(&*data, [0, 0, 0]);
+	}
+}
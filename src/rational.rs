@@ -1,22 +1,109 @@
+use std::cmp::Ordering;
 use std::ops::{Div, Mul};
 
-use num_traits::{Num, NumCast, PrimInt};
+use num_traits::PrimInt;
+
+use crate::UNKNOWN_TIMESTAMP;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Rational {
-	pub num: u32,
-	pub den: u32
+	pub num: i32,
+	pub den: i32
 }
 
 mod private {
-	use super::*;
+	pub trait Scalar: Copy {
+		/// `self * num / den`
+		fn mul_ratio(self, num: i32, den: i32) -> Self;
+	}
 
-	pub trait Scalar: Num + NumCast {}
+	/// Integers no wider than 64 bits, so that scaling by the product
+	/// of two `i32`s can't overflow an `i128`
+	pub trait Integer: num_traits::PrimInt {
+		fn widen(self) -> i128;
+	}
 }
 
 use self::private::*;
 
-impl<T: Num + NumCast> Scalar for T {}
+macro_rules! impl_int_scalar {
+	($($type:ty),*) => {
+		$(
+			impl Scalar for $type {
+				/// Rounded toward zero, saturating if it doesn't fit
+				#[allow(clippy::cast_lossless, clippy::cast_possible_wrap)]
+				fn mul_ratio(self, num: i32, den: i32) -> Self {
+					let value = mul_div(self.widen(), num.into(), den.into(), Rounding::Zero);
+
+					value
+						.try_into()
+						.unwrap_or(if value < 0 { Self::MIN } else { Self::MAX })
+				}
+			}
+
+			impl Integer for $type {
+				#[allow(clippy::cast_lossless, clippy::cast_possible_wrap)]
+				fn widen(self) -> i128 {
+					self as i128
+				}
+			}
+		)*
+	}
+}
+
+impl_int_scalar!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl Scalar for f32 {
+	#[allow(clippy::cast_possible_truncation)]
+	fn mul_ratio(self, num: i32, den: i32) -> Self {
+		(f64::from(self) * f64::from(num) / f64::from(den)) as Self
+	}
+}
+
+impl Scalar for f64 {
+	fn mul_ratio(self, num: i32, den: i32) -> Self {
+		self * f64::from(num) / f64::from(den)
+	}
+}
+
+/// How a rescaled value is rounded
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Rounding {
+	/// Toward zero
+	Zero,
+
+	/// Toward negative infinity
+	Down,
+
+	/// Toward positive infinity
+	Up,
+
+	/// To the nearest value, with halfway values away from zero
+	#[default]
+	NearInf
+}
+
+/// `value * mul / div`, rounded by `rounding`
+///
+/// # Panics
+/// if `div` is zero
+#[allow(clippy::arithmetic_side_effects)]
+fn mul_div(value: i128, mut mul: i128, mut div: i128, rounding: Rounding) -> i128 {
+	if div < 0 {
+		mul = -mul;
+		div = -div;
+	}
+
+	let value = value * mul;
+
+	match rounding {
+		Rounding::Zero => value / div,
+		Rounding::Down => value.div_euclid(div),
+		Rounding::Up => -(-value).div_euclid(div),
+		Rounding::NearInf if value < 0 => -((-value + div / 2) / div),
+		Rounding::NearInf => (value + div / 2) / div
+	}
+}
 
 impl Rational {
 	pub fn gcd<T>(mut a: T, mut b: T) -> T
@@ -59,7 +146,7 @@ impl Rational {
 	}
 
 	#[must_use]
-	pub const fn new(num: u32, den: u32) -> Self {
+	pub const fn new(num: i32, den: i32) -> Self {
 		Self { num, den }
 	}
 
@@ -83,9 +170,14 @@ impl Rational {
 		Self { num: 1, den: 1_000_000_000 }
 	}
 
+	/// # Panics
+	/// if `den` is larger than `i32::MAX`
 	#[must_use]
+	#[allow(clippy::cast_possible_wrap)]
 	pub const fn inverse(den: u32) -> Self {
-		Self { num: 1, den }
+		assert!(den <= i32::MAX as u32, "Denominator out of range");
+
+		Self { num: 1, den: den as i32 }
 	}
 
 	#[must_use]
@@ -94,24 +186,140 @@ impl Rational {
 	}
 
 	#[must_use]
+	#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
 	pub fn reduce(mut self) -> Self {
-		let gcd = Self::gcd(self.num, self.den);
+		let gcd = i64::from(Self::gcd(self.num.unsigned_abs(), self.den.unsigned_abs()));
 
-		#[allow(clippy::arithmetic_side_effects)]
+		/* dividing never grows the magnitude */
 		if gcd != 0 {
-			self.num /= gcd;
-			self.den /= gcd;
+			self.num = (i64::from(self.num) / gcd) as i32;
+			self.den = (i64::from(self.den) / gcd) as i32;
 		}
 
 		self
 	}
 
-	#[allow(clippy::arithmetic_side_effects)]
+	/// The closest ratio to `value` with a numerator and
+	/// denominator no larger than `max`
+	#[must_use]
+	#[allow(
+		clippy::arithmetic_side_effects,
+		clippy::cast_possible_truncation,
+		clippy::cast_precision_loss
+	)]
+	pub fn from_f64(value: f64, max: i32) -> Self {
+		if value.is_nan() {
+			return Self { num: 0, den: 0 };
+		}
+
+		let max = i64::from(max.max(1));
+		let sign = if value < 0.0 { -1 } else { 1 };
+		let target = value.abs();
+
+		/* the last two convergents of the continued fraction */
+		let (mut prev_num, mut prev_den) = (0_i64, 1_i64);
+		let (mut num, mut den) = (1_i64, 0_i64);
+		let mut rest = target;
+
+		for _ in 0..64 {
+			/* terms past `max` can't fit, but still pick the closest ratio */
+			let term = rest.min(max as f64 + 1.0).floor() as i64;
+			let next_num = term * num + prev_num;
+			let next_den = term * den + prev_den;
+
+			if next_num > max || next_den > max {
+				/* the closest ratio between the convergents that fits */
+				let fit = |prev: i64, cur: i64| {
+					if cur == 0 {
+						i64::MAX
+					} else {
+						(max - prev) / cur
+					}
+				};
+
+				let fit = fit(prev_num, num).min(fit(prev_den, den));
+				let (semi_num, semi_den) = (fit * num + prev_num, fit * den + prev_den);
+				let error = |n: i64, d: i64| (target - n as f64 / d as f64).abs();
+
+				if semi_den != 0 && error(semi_num, semi_den) < error(num, den) {
+					(num, den) = (semi_num, semi_den);
+				}
+
+				break;
+			}
+
+			(prev_num, prev_den, num, den) = (num, den, next_num, next_den);
+
+			let fract = rest - rest.floor();
+
+			if fract <= f64::EPSILON * rest.max(1.0) {
+				break;
+			}
+
+			rest = 1.0 / fract;
+		}
+
+		/* values too large to approximate are clamped */
+		if den == 0 {
+			return Self { num: (sign * max) as i32, den: 1 };
+		}
+
+		Self { num: (sign * num) as i32, den: den as i32 }
+	}
+
+	/// Convert `value` from `base` into this time base, rounded to
+	/// the nearest value. Results that don't fit `T` saturate
+	///
+	/// # Panics
+	/// if either ratio is zero
 	pub fn rescale<T>(self, value: T, base: Self) -> T
 	where
-		T: Scalar
+		T: Integer
+	{
+		self.rescale_rnd(value, base, Rounding::NearInf)
+	}
+
+	/// Convert `value` from `base` into this time base, rounded by
+	/// `rounding`. Results that don't fit `T` saturate
+	///
+	/// # Panics
+	/// if either ratio is zero
+	pub fn rescale_rnd<T>(self, value: T, base: Self, rounding: Rounding) -> T
+	where
+		T: Integer
 	{
-		base * (self.invert() * value)
+		let mul = i128::from(base.num) * i128::from(self.den);
+		let div = i128::from(base.den) * i128::from(self.num);
+		let value = mul_div(value.widen(), mul, div, rounding);
+
+		T::from(value).unwrap_or_else(|| {
+			if value < 0 {
+				T::min_value()
+			} else {
+				T::max_value()
+			}
+		})
+	}
+
+	/// Convert `timestamp` from `base` into this time base, rounded
+	/// by `rounding`. [`UNKNOWN_TIMESTAMP`] and `i64::MAX` are passed
+	/// through unchanged
+	///
+	/// # Panics
+	/// if either ratio is zero
+	#[must_use]
+	pub fn rescale_timestamp(self, timestamp: i64, base: Self, rounding: Rounding) -> i64 {
+		if timestamp == UNKNOWN_TIMESTAMP || timestamp == i64::MAX {
+			return timestamp;
+		}
+
+		self.rescale_rnd(timestamp, base, rounding)
+	}
+
+	/// The ratio as a float
+	#[must_use]
+	pub fn to_f64(self) -> f64 {
+		f64::from(self.num) / f64::from(self.den)
 	}
 }
 
@@ -121,26 +329,67 @@ impl Default for Rational {
 	}
 }
 
+/// Ratios are compared by value, so `1/2` equals `2/4`. A zero denominator
+/// is infinity with the sign of the numerator, and `0/0` is zero
+impl Ord for Rational {
+	#[allow(clippy::arithmetic_side_effects)]
+	fn cmp(&self, other: &Self) -> Ordering {
+		let normalize = |value: &Self| match (i64::from(value.num), i64::from(value.den)) {
+			(0, 0) => (0, 1),
+			(num, den) if den < 0 => (-num, -den),
+			ratio => ratio
+		};
+
+		let (a_num, a_den) = normalize(self);
+		let (b_num, b_den) = normalize(other);
+
+		if a_den == 0 && b_den == 0 {
+			return a_num.signum().cmp(&b_num.signum());
+		}
+
+		(a_num * b_den).cmp(&(b_num * a_den))
+	}
+}
+
+impl PartialOrd for Rational {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl PartialEq for Rational {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Rational {}
+
+/// Integers are rounded toward zero, and saturate if the result
+/// doesn't fit
+///
+/// # Panics
+/// if the denominator of an integer product is zero
 impl<T: Scalar> Mul<T> for Rational {
 	type Output = T;
 
-	#[allow(clippy::arithmetic_side_effects, clippy::unwrap_used)]
 	fn mul(self, rhs: T) -> T {
-		T::from(self.num).unwrap() * rhs / T::from(self.den).unwrap()
+		rhs.mul_ratio(self.num, self.den)
 	}
 }
 
 /// # Panics
-/// if the ratio cannot be reduced into u32 pairs
-fn maybe_reduce(mut num: u64, mut den: u64) -> (u32, u32) {
+/// if the ratio cannot be reduced into i32 pairs
+#[allow(clippy::cast_possible_wrap)]
+fn maybe_reduce(mut num: i64, mut den: i64) -> (i32, i32) {
 	if let (Ok(num), Ok(den)) = (num.try_into(), den.try_into()) {
 		return (num, den);
 	}
 
-	let gcd = Rational::gcd(num, den);
+	let gcd = Rational::gcd(num.unsigned_abs(), den.unsigned_abs()) as i64;
 
 	#[allow(clippy::arithmetic_side_effects)]
-	if gcd != 0 {
+	if gcd > 0 {
 		num /= gcd;
 		den /= gcd;
 	}
@@ -148,7 +397,7 @@ fn maybe_reduce(mut num: u64, mut den: u64) -> (u32, u32) {
 	#[allow(clippy::panic)]
 	let (Ok(num), Ok(den)) = (num.try_into(), den.try_into()) else {
 		panic!(
-			"Failed to reduce rational to within u32 bounds: num = {}, den = {}",
+			"Failed to reduce rational to within i32 bounds: num = {}, den = {}",
 			num, den
 		);
 	};
@@ -161,8 +410,8 @@ impl Mul for Rational {
 
 	#[allow(clippy::arithmetic_side_effects)]
 	fn mul(self, rhs: Self) -> Self {
-		let num = self.num as u64 * rhs.num as u64;
-		let den = self.den as u64 * rhs.den as u64;
+		let num = i64::from(self.num) * i64::from(rhs.num);
+		let den = i64::from(self.den) * i64::from(rhs.den);
 
 		let (num, den) = maybe_reduce(num, den);
 
@@ -175,11 +424,160 @@ impl Div for Rational {
 
 	#[allow(clippy::arithmetic_side_effects)]
 	fn div(self, rhs: Self) -> Self {
-		let num = self.num as u64 * rhs.den as u64;
-		let den = self.den as u64 * rhs.num as u64;
+		let num = i64::from(self.num) * i64::from(rhs.den);
+		let den = i64::from(self.den) * i64::from(rhs.num);
 
 		let (num, den) = maybe_reduce(num, den);
 
 		Self { num, den }
 	}
 }
+
+#[cfg(test)]
+#[allow(clippy::arithmetic_side_effects)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rounding() {
+		let tenths = Rational::new(1, 10);
+		let round = |value: i64, rounding| Rational::seconds().rescale_rnd(value, tenths, rounding);
+
+		assert_eq!(round(15, Rounding::Zero), 1);
+		assert_eq!(round(15, Rounding::Down), 1);
+		assert_eq!(round(15, Rounding::Up), 2);
+		assert_eq!(round(15, Rounding::NearInf), 2);
+		assert_eq!(round(14, Rounding::NearInf), 1);
+
+		assert_eq!(round(-15, Rounding::Zero), -1);
+		assert_eq!(round(-15, Rounding::Down), -2);
+		assert_eq!(round(-15, Rounding::Up), -1);
+		assert_eq!(round(-15, Rounding::NearInf), -2);
+		assert_eq!(round(-14, Rounding::NearInf), -1);
+
+		assert_eq!(round(20, Rounding::Up), 2);
+		assert_eq!(round(-20, Rounding::Down), -2);
+	}
+
+	#[test]
+	fn rescale_negative_base() {
+		let base = Rational::new(1, -1000);
+
+		assert_eq!(Rational::seconds().rescale(1500_i64, base), -2);
+		assert_eq!(
+			Rational::seconds().rescale_rnd(1500_i64, base, Rounding::Zero),
+			-1
+		);
+	}
+
+	#[test]
+	fn rescale_saturates() {
+		let nanos = Rational::nanos();
+
+		assert_eq!(nanos.rescale(i64::MAX, Rational::seconds()), i64::MAX);
+		assert_eq!(nanos.rescale(i64::MIN, Rational::seconds()), i64::MIN);
+		assert_eq!(nanos.rescale(u32::MAX, Rational::seconds()), u32::MAX);
+		assert_eq!(nanos.rescale(u64::MAX, Rational::seconds()), u64::MAX);
+		assert_eq!(nanos.rescale(5_u64, Rational::seconds()), 5_000_000_000);
+	}
+
+	#[test]
+	fn rescale_timestamp() {
+		let millis = Rational::millis();
+		let rescale =
+			|timestamp| Rational::seconds().rescale_timestamp(timestamp, millis, Rounding::NearInf);
+
+		assert_eq!(rescale(UNKNOWN_TIMESTAMP), UNKNOWN_TIMESTAMP);
+		assert_eq!(rescale(i64::MAX), i64::MAX);
+		assert_eq!(rescale(1499), 1);
+		assert_eq!(rescale(1500), 2);
+		assert_eq!(rescale(-1500), -2);
+	}
+
+	#[test]
+	fn mul_scalar() {
+		let ratio = Rational::new(3, 2);
+
+		assert_eq!(ratio * 5_i32, 7);
+		assert_eq!(ratio * -5_i32, -7);
+		assert_eq!(ratio * 5_u64, 7);
+		assert!((ratio * 5.0_f64 - 7.5).abs() < f64::EPSILON);
+		assert!((ratio * 5.0_f32 - 7.5).abs() < f32::EPSILON);
+	}
+
+	#[test]
+	fn mul_scalar_overflow() {
+		let ratio = Rational::new(3, 2);
+
+		/* the product is computed in 128 bits, so only the result saturates */
+		assert_eq!(Rational::new(2, 4) * i64::MAX, i64::MAX / 2);
+		assert_eq!(ratio * i64::MAX, i64::MAX);
+		assert_eq!(ratio * i64::MIN, i64::MIN);
+		assert_eq!(ratio * u8::MAX, u8::MAX);
+		assert_eq!(Rational::new(-1, 1) * 5_u32, 0);
+	}
+
+	#[test]
+	fn ordering() {
+		assert_eq!(Rational::new(1, 2), Rational::new(2, 4));
+		assert_eq!(Rational::new(-1, 2), Rational::new(1, -2));
+		assert_eq!(Rational::new(0, 0), Rational::new(0, 5));
+		assert!(Rational::new(1, 3) < Rational::new(1, 2));
+		assert!(Rational::new(-1, 2) < Rational::new(1, -3));
+		assert!(Rational::new(1, 0) > Rational::new(i32::MAX, 1));
+		assert!(Rational::new(-1, 0) < Rational::new(i32::MIN, 1));
+		assert_eq!(Rational::new(2, 0), Rational::new(1, 0));
+		assert!(Rational::new(i32::MAX, 1) > Rational::new(i32::MAX - 1, 1));
+	}
+
+	#[test]
+	fn from_f64() {
+		let check = |value: f64, max: i32, num: i32, den: i32| {
+			let ratio = Rational::from_f64(value, max);
+
+			assert_eq!(
+				(ratio.num, ratio.den),
+				(num, den),
+				"{} within {}",
+				value,
+				max
+			);
+		};
+
+		check(0.5, 100, 1, 2);
+		check(-0.75, 100, -3, 4);
+		check(29.97, 100_000, 2997, 100);
+		check(30_000.0 / 1001.0, 100_000, 30_000, 1001);
+		check(std::f64::consts::PI, 1000, 355, 113);
+		check(std::f64::consts::PI, 100, 22, 7);
+		check(3.0, 10, 3, 1);
+		check(1e12, 1000, 1000, 1);
+		check(f64::NAN, 10, 0, 0);
+	}
+
+	#[test]
+	fn reduce_and_gcd() {
+		assert_eq!(Rational::gcd(12_u32, 18), 6);
+		assert_eq!(Rational::gcd(0_u32, 7), 7);
+
+		let ratio = Rational::new(-6, 8).reduce();
+
+		assert_eq!((ratio.num, ratio.den), (-3, 4));
+
+		let ratio = Rational::new(i32::MIN, 2).reduce();
+
+		assert_eq!((ratio.num, ratio.den), (i32::MIN / 2, 1));
+	}
+
+	#[test]
+	fn mul_div_ratios() {
+		/* reduced only when the product doesn't fit */
+		let product = Rational::new(1 << 20, 3) * Rational::new(1 << 12, 1 << 20);
+
+		assert_eq!((product.num, product.den), (1 << 12, 3));
+
+		let quotient = Rational::new(2, 3) / Rational::new(4, 9);
+
+		assert_eq!((quotient.num, quotient.den), (18, 12));
+	}
+}
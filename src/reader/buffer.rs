@@ -0,0 +1,134 @@
+use super::*;
+
+/// A read buffer over a stream, held in a packet buffer so that
+/// packets can share it rather than copy out of it
+pub struct StreamBuffer {
+	stream: Stream,
+	data: PacketData,
+	start: usize,
+	end: usize,
+
+	/// Whether slices of the buffer were handed out. It is never
+	/// written to once shared, and is replaced on the next fill
+	shared: bool
+}
+
+#[asynchronous]
+impl StreamBuffer {
+	pub fn new(stream: Stream) -> Self {
+		Self {
+			stream,
+			data: PacketData::alloc(DEFAULT_BUFFER_SIZE),
+			start: 0,
+			end: 0,
+			shared: false
+		}
+	}
+
+	pub const fn capacity(&self) -> usize {
+		self.data.len()
+	}
+
+	/// The buffered bytes not yet consumed
+	pub fn buffer(&self) -> &[u8] {
+		&self.data[self.start..self.end]
+	}
+
+	/// The offset of the next unconsumed byte in the buffer
+	pub const fn position(&self) -> usize {
+		self.start
+	}
+
+	pub fn consume(&mut self, len: usize) {
+		self.start = self.start.saturating_add(len).min(self.end);
+	}
+
+	pub fn unconsume(&mut self, len: usize) {
+		self.start = self.start.saturating_sub(len);
+	}
+
+	pub fn discard(&mut self) {
+		self.start = 0;
+		self.end = 0;
+	}
+
+	/// Consume the next `len` bytes, sharing the buffer rather than
+	/// copying them. Returns `None` if fewer are buffered
+	pub fn share(&mut self, len: usize) -> Option<PacketData> {
+		let end = self.start.checked_add(len).filter(|end| *end <= self.end)?;
+		let data = self.data.slice(self.start..end)?;
+
+		self.start = end;
+		self.shared = true;
+
+		Some(data)
+	}
+
+	/// Move the bytes from `keep` on to the start of the buffer,
+	/// copying them to a new buffer if the current one is shared
+	fn compact(&mut self, keep: usize) -> Result<()> {
+		let keep = keep.min(self.start);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let kept = self.end - keep;
+
+		if self.shared {
+			let mut data = PacketData::alloc(self.capacity());
+
+			data.make_mut()?[..kept].copy_from_slice(&self.data[keep..self.end]);
+
+			self.data = data;
+			self.shared = false;
+		} else {
+			self.data.make_mut()?.copy_within(keep..self.end, 0);
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.start -= keep);
+		self.end = kept;
+
+		Ok(())
+	}
+
+	/// Read more of the stream into the buffer, dropping the bytes
+	/// before `keep` if out of room. Returns zero at the end of the
+	/// stream, or if the bytes kept fill the buffer
+	pub async fn fill(&mut self, keep: usize) -> Result<usize> {
+		if self.shared || self.end == self.capacity() {
+			self.compact(keep)?;
+		}
+
+		let spare = &mut self.data.make_mut()?[self.end..];
+
+		if spare.is_empty() {
+			return Ok(0);
+		}
+
+		let read = self.stream.read(spare).await?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.end += read);
+
+		Ok(read)
+	}
+
+	/// Read from the stream directly, dropping the buffered bytes
+	pub async fn read_direct(&mut self, buf: &mut [u8]) -> Result<usize> {
+		self.discard();
+		self.stream.read(buf).await
+	}
+
+	/// Seek the stream, dropping the buffered bytes
+	pub async fn seek(&mut self, pos: u64) -> Result<u64> {
+		self.discard();
+		self.stream.seek(SeekFrom::Start(pos)).await
+	}
+
+	pub fn stream_len_fast(&self) -> bool {
+		self.stream.stream_len_fast()
+	}
+
+	pub async fn stream_len(&mut self) -> Result<u64> {
+		self.stream.stream_len().await
+	}
+}
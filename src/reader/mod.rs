@@ -0,0 +1,460 @@
+#![allow(unreachable_pub)]
+
+use std::io::SeekFrom;
+
+use xx_core::async_std::io::*;
+use xx_core::impls::UintExt;
+use xx_core::macros::{macro_each, paste};
+use xx_core::opt::hint::*;
+
+use super::*;
+use crate::resource::*;
+use crate::FormatError;
+
+mod buffer;
+
+use self::buffer::*;
+
+macro_rules! read_num_type_endian {
+	($type:ty, $name:ident, $from_bytes:ident) => {
+		paste! {
+			#[asynchronous]
+			#[inline]
+			pub async fn [<read_ $name>](&mut self) -> Result<$type> {
+				self.read_array().await.map(<$type>::$from_bytes)
+			}
+		}
+	};
+}
+
+macro_rules! read_num_type {
+	($type:ty) => {
+		paste! {
+			read_num_type_endian!($type, [<$type _le>], from_le_bytes);
+			read_num_type_endian!($type, [<$type _be>], from_be_bytes);
+		}
+	};
+}
+
+macro_rules! read_int {
+	($bits:literal) => {
+		paste! {
+			read_num_type!([<i $bits>]);
+			read_num_type!([<u $bits>]);
+		}
+	};
+}
+
+#[errors]
+pub enum ReaderError {
+	#[display("Peek buffer exhausted")]
+	PeekBufferExhausted
+}
+
+/// The most to allocate at once for reads of untrusted sizes
+const READ_LIMIT: usize = 1024 * 1024;
+
+pub struct Reader {
+	buffer: StreamBuffer,
+	position: u64,
+	seek_threshold: u64,
+	peeking: Option<u64>,
+	seekable: bool
+}
+
+#[asynchronous]
+impl Reader {
+	read_num_type_endian!(i8, i8, from_le_bytes);
+
+	read_num_type_endian!(u8, u8, from_le_bytes);
+
+	macro_each!(read_int, 16, 32, 64, 128);
+
+	macro_each!(read_num_type, f32, f64);
+
+	/// Where the bytes to keep in the buffer start. While peeking,
+	/// that is where peeking started
+	fn keep(&self) -> usize {
+		let Some(start) = self.peeking else {
+			return self.buffer.position();
+		};
+
+		#[allow(clippy::cast_possible_truncation)]
+		let peeked = self.position.saturating_sub(start) as usize;
+
+		self.buffer.position().saturating_sub(peeked)
+	}
+
+	/// Read more of the stream into the buffer. Returns zero at the
+	/// end of the stream
+	async fn fill(&mut self) -> Result<usize> {
+		let keep = self.keep();
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let kept = self.buffer.position() - keep + self.buffer.buffer().len();
+
+		if unlikely(kept >= self.buffer.capacity()) {
+			return Err(ReaderError::PeekBufferExhausted.into());
+		}
+
+		self.buffer.fill(keep).await
+	}
+
+	/// Buffer at least `len` bytes
+	async fn fill_to(&mut self, len: usize) -> Result<()> {
+		while self.buffer.buffer().len() < len {
+			if unlikely(self.fill().await? == 0) {
+				return Err(ErrorKind::UnexpectedEof.into());
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Fill `buf` with the buffered bytes, then read the rest from
+	/// the stream directly
+	async fn read_through(&mut self, buf: &mut [u8]) -> Result<()> {
+		let mut filled = self.buffer.buffer().len().min(buf.len());
+
+		buf[..filled].copy_from_slice(&self.buffer.buffer()[..filled]);
+		self.buffer.consume(filled);
+
+		while filled < buf.len() {
+			let read = self.buffer.read_direct(&mut buf[filled..]).await?;
+
+			if unlikely(read == 0) {
+				return Err(ErrorKind::UnexpectedEof.into());
+			}
+
+			#[allow(clippy::arithmetic_side_effects)]
+			(filled += read);
+		}
+
+		Ok(())
+	}
+
+	#[inline]
+	async fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+		let mut bytes = [0; N];
+
+		self.read(&mut bytes).await?;
+
+		Ok(bytes)
+	}
+
+	pub fn new(stream: Stream) -> Self {
+		let seek_threshold = stream.suggested_seek_threshold();
+		let seekable = stream.seekable();
+
+		Self {
+			buffer: StreamBuffer::new(stream),
+			position: 0,
+			seek_threshold,
+			peeking: None,
+			seekable
+		}
+	}
+
+	/// If doing a relative seek forwards on a stream with
+	/// an expensive seek operation
+	///
+	/// Prefer to read until that offset rather than seek if
+	/// the difference <= threshold
+	pub fn set_seek_threshold(&mut self, threshold: u64) {
+		self.seek_threshold = threshold;
+	}
+
+	/// Skip bytes without seeking
+	async fn consume(&mut self, mut left: u64) -> Result<()> {
+		loop {
+			let available = self.buffer.buffer().len();
+
+			if left > available as u64 {
+				self.buffer.consume(available);
+
+				#[allow(clippy::arithmetic_side_effects)]
+				{
+					left -= available as u64;
+					self.position += available as u64;
+				}
+
+				if self.fill().await? == 0 {
+					return Err(ErrorKind::UnexpectedEof.into());
+				}
+			} else {
+				#[allow(clippy::cast_possible_truncation)]
+				self.buffer.consume(left as usize);
+
+				#[allow(clippy::arithmetic_side_effects)]
+				(self.position += left);
+
+				break;
+			}
+		}
+
+		Ok(())
+	}
+
+	async fn seek_relative(&mut self, rel: i64) -> Result<()> {
+		#[allow(clippy::cast_sign_loss)]
+		if rel >= 0 && (rel as u64 <= self.seek_threshold || !self.seekable) {
+			self.consume(rel as u64).await?;
+		} else {
+			let new_pos = (self.buffer.position() as u64).checked_add_signed(rel);
+
+			if rel <= 0 && new_pos.is_some() {
+				#[allow(clippy::cast_possible_truncation, clippy::arithmetic_side_effects)]
+				self.buffer.unconsume(-rel as usize);
+				self.position = self.position.wrapping_add_signed(rel);
+			} else {
+				#[allow(clippy::unwrap_used)]
+				let pos = self.position.checked_add_signed(rel).unwrap();
+
+				self.position = self.buffer.seek(pos).await?;
+			}
+		}
+
+		Ok(())
+	}
+
+	pub async fn seek(&mut self, seek: SeekFrom) -> Result<()> {
+		#[allow(clippy::unwrap_used, unstable_name_collisions)]
+		let (rel, abs) = match seek {
+			SeekFrom::Current(pos) => (Some(pos), self.position.checked_add_signed(pos).unwrap()),
+
+			SeekFrom::Start(pos) => (pos.checked_signed_diff(self.position), pos),
+
+			SeekFrom::End(pos) => {
+				let pos = self.len().await?.checked_add_signed(pos).unwrap();
+
+				(pos.checked_signed_diff(self.position), pos)
+			}
+		};
+
+		if rel.is_some_and(|rel| rel >= 0 || self.seekable) {
+			#[allow(clippy::unwrap_used)]
+			self.seek_relative(rel.unwrap()).await?;
+		} else {
+			self.position = self.buffer.seek(abs).await?;
+		};
+
+		if unlikely(self.position != abs) {
+			self.seekable = false;
+
+			/* on non seekable streams, permit seeking to zero, but not past the
+			 * requested position */
+			if let Some(amt) = abs.checked_sub(self.position) {
+				self.consume(amt).await?;
+			} else {
+				return Err(FormatError::InvalidSeek(abs, self.position).into());
+			}
+		}
+
+		Ok(())
+	}
+
+	pub async fn skip(&mut self, amount: u64) -> Result<()> {
+		if let Ok(amount) = i64::try_from(amount) {
+			return self.seek(SeekFrom::Current(amount)).await;
+		}
+
+		self.consume(amount).await
+	}
+
+	pub async fn read(&mut self, buf: &mut [u8]) -> Result<()> {
+		if buf.len() <= self.buffer.capacity() || self.peeking.is_some() {
+			self.fill_to(buf.len()).await?;
+
+			buf.copy_from_slice(&self.buffer.buffer()[..buf.len()]);
+			self.buffer.consume(buf.len());
+		} else {
+			self.read_through(buf).await?;
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += buf.len() as u64);
+
+		Ok(())
+	}
+
+	pub async fn read_partial(&mut self, buf: &mut [u8]) -> Result<usize> {
+		if self.buffer.buffer().is_empty() {
+			if self.peeking.is_none() && buf.len() >= self.buffer.capacity() {
+				let read = self.buffer.read_direct(buf).await?;
+
+				#[allow(clippy::arithmetic_side_effects)]
+				(self.position += read as u64);
+
+				return Ok(read);
+			}
+
+			if self.fill().await? == 0 {
+				return Ok(0);
+			}
+		}
+
+		let read = self.buffer.buffer().len().min(buf.len());
+
+		buf[..read].copy_from_slice(&self.buffer.buffer()[..read]);
+		self.buffer.consume(read);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += read as u64);
+
+		Ok(read)
+	}
+
+	/// Read a little endian unsigned integer of `size` bytes, at most 8
+	pub async fn read_vint_le(&mut self, size: usize) -> Result<u64> {
+		let mut bytes = [0; 8];
+
+		let buf = bytes.get_mut(..size).ok_or(ErrorKind::InvalidInput)?;
+
+		self.read(buf).await?;
+
+		Ok(u64::from_le_bytes(bytes))
+	}
+
+	/// Read a big endian unsigned integer of `size` bytes, at most 8
+	pub async fn read_vint_be(&mut self, size: usize) -> Result<u64> {
+		let mut bytes = [0; 8];
+		let start = bytes
+			.len()
+			.checked_sub(size)
+			.ok_or(ErrorKind::InvalidInput)?;
+
+		self.read(&mut bytes[start..]).await?;
+
+		Ok(u64::from_be_bytes(bytes))
+	}
+
+	/// Read a little endian float of `size` bytes, which is 0, 4 or 8
+	pub async fn read_vfloat_le(&mut self, size: usize) -> Result<f64> {
+		match size {
+			0 => Ok(0.0),
+			4 => self.read_f32_le().await.map(f64::from),
+			8 => self.read_f64_le().await,
+			_ => Err(ErrorKind::InvalidInput.into())
+		}
+	}
+
+	/// Read a big endian float of `size` bytes, which is 0, 4 or 8
+	pub async fn read_vfloat_be(&mut self, size: usize) -> Result<f64> {
+		match size {
+			0 => Ok(0.0),
+			4 => self.read_f32_be().await.map(f64::from),
+			8 => self.read_f64_be().await,
+			_ => Err(ErrorKind::InvalidInput.into())
+		}
+	}
+
+	pub async fn read_bytes(&mut self, size: usize) -> Result<Vec<u8>> {
+		if size <= READ_LIMIT {
+			let mut buf = vec![0; size];
+
+			self.read(&mut buf).await?;
+
+			return Ok(buf);
+		}
+
+		/* don't trust large sizes until the data is actually there */
+		let mut buf = Vec::new();
+
+		while buf.len() < size {
+			let offset = buf.len();
+
+			#[allow(clippy::arithmetic_side_effects)]
+			buf.resize(offset + (size - offset).min(READ_LIMIT), 0);
+
+			self.read(&mut buf[offset..]).await?;
+		}
+
+		Ok(buf)
+	}
+
+	/// Read `size` bytes into a padded packet buffer. Data that fits
+	/// in the read buffer shares it rather than being copied
+	pub async fn read_data(&mut self, size: usize) -> Result<PacketData> {
+		if size <= self.buffer.capacity() {
+			self.fill_to(size).await?;
+
+			let data = self.buffer.share(size).ok_or(ErrorKind::UnexpectedEof)?;
+
+			#[allow(clippy::arithmetic_side_effects)]
+			(self.position += size as u64);
+
+			return Ok(data);
+		}
+
+		if unlikely(self.peeking.is_some()) {
+			return Err(ReaderError::PeekBufferExhausted.into());
+		}
+
+		/* don't trust large sizes until the data is actually there */
+		self.fill_to(self.buffer.capacity()).await?;
+
+		if self.buffer.stream_len_fast() {
+			let len = self.buffer.stream_len().await?;
+
+			if len.saturating_sub(self.position) < size as u64 {
+				return Err(ErrorKind::UnexpectedEof.into());
+			}
+		}
+
+		let mut data = PacketData::alloc(size);
+
+		self.read_through(data.make_mut()?).await?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += size as u64);
+
+		Ok(data)
+	}
+
+	pub async fn read_string(&mut self, size: usize) -> Result<String> {
+		let buf = self.read_bytes(size).await?;
+
+		String::from_utf8(buf).map_err(Into::into)
+	}
+
+	pub const fn position(&self) -> u64 {
+		self.position
+	}
+
+	pub const fn seekable(&self) -> bool {
+		self.seekable
+	}
+
+	pub async fn set_peeking(&mut self, peeking: bool) {
+		if peeking == self.peeking.is_some() {
+			return;
+		}
+
+		if peeking {
+			self.peeking = Some(self.position);
+		} else {
+			#[allow(clippy::unwrap_used, clippy::arithmetic_side_effects)]
+			let rel = self.position - self.peeking.take().unwrap();
+
+			/* seek should be within our buffer, no errors should occur */
+			#[allow(
+				clippy::unwrap_used,
+				clippy::arithmetic_side_effects,
+				clippy::cast_possible_wrap
+			)]
+			self.seek_relative(-(rel as i64)).await.unwrap();
+		}
+	}
+
+	pub async fn len(&mut self) -> Result<u64> {
+		self.buffer.stream_len().await
+	}
+
+	pub async fn eof(&mut self) -> Result<bool> {
+		if !self.buffer.buffer().is_empty() {
+			Ok(false)
+		} else {
+			Ok(self.fill().await? != 0)
+		}
+	}
+}
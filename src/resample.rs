@@ -0,0 +1,259 @@
+use super::*;
+use crate::filter::{AudioSinkOptions, AudioSrcOptions};
+
+/// The noise added when reducing the bit depth of samples
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Dither {
+	#[default]
+	None,
+	Rectangular,
+	Triangular,
+	TriangularHighPass,
+
+	/// Noise shaped dithers, for 44.1 and 48 khz
+	Lipshitz,
+	Shibata,
+	LowShibata,
+	HighShibata,
+	FWeighted,
+	EWeighted,
+	ModifiedEWeighted
+}
+
+impl Dither {
+	const fn method(self) -> i32 {
+		match self {
+			Self::None => 0,
+			Self::Rectangular => 1,
+			Self::Triangular => 2,
+			Self::TriangularHighPass => 3,
+			Self::Lipshitz => 64,
+			Self::Shibata => 65,
+			Self::LowShibata => 66,
+			Self::HighShibata => 67,
+			Self::FWeighted => 68,
+			Self::EWeighted => 69,
+			Self::ModifiedEWeighted => 70
+		}
+	}
+}
+
+/// The tradeoff between speed and accuracy of sample rate conversion
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum ResampleQuality {
+	Fast,
+	#[default]
+	Default,
+	High,
+	Best
+}
+
+impl ResampleQuality {
+	/// The filter size, phase shift and whether to interpolate
+	const fn params(self) -> (i32, i32, bool) {
+		match self {
+			Self::Fast => (16, 8, false),
+			Self::Default => (32, 10, false),
+			Self::High => (64, 12, true),
+			Self::Best => (128, 14, true)
+		}
+	}
+}
+
+#[derive(Clone, Default)]
+pub struct ResamplerOptions {
+	pub quality: ResampleQuality,
+	pub dither: Dither,
+
+	/// The cutoff frequency of the resampling filter, as a
+	/// fraction of the lower nyquist frequency
+	pub cutoff: Option<f64>,
+
+	/// Replaces the default remixing, with one row per output
+	/// channel of the gain of each input channel
+	pub matrix: Option<Vec<Vec<f64>>>
+}
+
+/// Converts audio frames to another sample format, channel layout
+/// or sample rate
+///
+/// Frames out of the resampler are timed in `1 / sample_rate`. Samples
+/// are held back while resampling, and [`Self::flush`] returns the
+/// rest at the end of the stream
+pub struct Resampler {
+	context: av::SwrContext,
+	input: AudioSrcOptions,
+	ch_layout: ChannelLayout,
+	sample_fmt: SampleFormat,
+	sample_rate: u32,
+
+	/// The timestamp of the next output sample
+	next_timestamp: i64
+}
+
+impl Resampler {
+	/// A resampler from `input` to `output`. The frame size of `output`
+	/// is ignored, as frames are converted one at a time
+	pub fn new(
+		input: &AudioSrcOptions, output: &AudioSinkOptions, options: &ResamplerOptions
+	) -> Result<Self> {
+		let ch_layout = output
+			.ch_layout
+			.clone()
+			.unwrap_or_else(|| input.ch_layout.clone());
+
+		if input.sample_rate == 0 ||
+			output.sample_rate == 0 ||
+			output.sample_fmt == SampleFormat::None
+		{
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let mut context = av::SwrContext::new();
+		let (filter_size, phase_shift, linear_interp) = options.quality.params();
+
+		context.set_in_ch_layout(&input.ch_layout);
+		context.set_in_sample_fmt(input.sample_fmt);
+		context.set_in_sample_rate(input.sample_rate.into());
+		context.set_out_ch_layout(&ch_layout);
+		context.set_out_sample_fmt(output.sample_fmt);
+		context.set_out_sample_rate(output.sample_rate.into());
+		context.set_dither_method(options.dither.method());
+		context.set_filter_size(filter_size);
+		context.set_phase_shift(phase_shift);
+		context.set_linear_interp(linear_interp);
+
+		if let Some(cutoff) = options.cutoff {
+			context.set_cutoff(cutoff);
+		}
+
+		if let Some(matrix) = &options.matrix {
+			let inputs = usize::from(input.ch_layout.channel_count());
+
+			if matrix.len() != usize::from(ch_layout.channel_count()) ||
+				matrix.iter().any(|row| row.len() != inputs)
+			{
+				return Err(ErrorKind::InvalidInput.into());
+			}
+
+			context.set_matrix(&matrix.concat(), inputs)?;
+		}
+
+		context.init()?;
+
+		Ok(Self {
+			context,
+			input: input.clone(),
+			ch_layout,
+			sample_fmt: output.sample_fmt,
+			sample_rate: output.sample_rate,
+			next_timestamp: UNKNOWN_TIMESTAMP
+		})
+	}
+
+	/// The samples held back, in output samples
+	#[must_use]
+	pub fn delay(&self) -> u64 {
+		self.context.delay(self.sample_rate)
+	}
+
+	/// Drop the next `samples` output samples, such as to
+	/// remove the delay from the start of the output
+	pub fn drop_output(&mut self, samples: u32) -> Result<()> {
+		self.context.drop_output(samples)
+	}
+
+	/// Add `samples` input samples of silence before the next frame
+	pub fn inject_silence(&mut self, samples: u32) -> Result<()> {
+		self.context.inject_silence(samples)
+	}
+
+	fn output_frame(&self) -> Frame {
+		let mut frame = Frame::new();
+
+		frame.data.format = self.sample_fmt as i32;
+		frame.data.ch_layout = (&self.ch_layout).into();
+
+		#[allow(clippy::cast_possible_wrap)]
+		(frame.data.sample_rate = self.sample_rate as i32);
+
+		frame
+	}
+
+	/// Fill in the fields of a converted frame starting at `timestamp`.
+	/// Returns `None` if it has no samples
+	fn finish_frame(&mut self, mut frame: Frame, timestamp: i64) -> Option<Frame> {
+		frame.get_fields_from_inner(Some(MediaType::Audio));
+
+		if frame.samples == 0 {
+			return None;
+		}
+
+		frame.time_base = Rational::inverse(self.sample_rate);
+		frame.presentation_timestamp = timestamp;
+		frame.best_effort_timestamp = timestamp;
+		frame.decode_timestamp = timestamp;
+		frame.duration = frame.samples.into();
+
+		if timestamp != UNKNOWN_TIMESTAMP {
+			self.next_timestamp = timestamp.saturating_add(frame.samples.into());
+		}
+
+		Some(frame)
+	}
+
+	/// Convert `frame`, which must be in the input format. Returns
+	/// `None` if all of its samples were held back
+	pub fn convert(&mut self, frame: &Frame) -> Result<Option<Frame>> {
+		if frame.sample_format != self.input.sample_fmt ||
+			frame.sample_rate != self.input.sample_rate ||
+			frame.ch_layout != self.input.ch_layout
+		{
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let time_base = if frame.time_base.num != 0 {
+			Some(frame.time_base)
+		} else {
+			self.input.time_base
+		};
+
+		let timestamp = time_base.map_or(UNKNOWN_TIMESTAMP, |time_base| {
+			Rational::inverse(self.sample_rate).rescale_timestamp(
+				frame.presentation_timestamp,
+				time_base,
+				Rounding::NearInf
+			)
+		});
+
+		/* the output starts with the samples held back */
+		#[allow(clippy::cast_possible_wrap)]
+		let timestamp = if timestamp != UNKNOWN_TIMESTAMP {
+			timestamp.saturating_sub(self.delay() as i64)
+		} else {
+			self.next_timestamp
+		};
+
+		let mut output = self.output_frame();
+
+		self.context.convert_frame(&mut output.data, &frame.data)?;
+
+		let mut output = self.finish_frame(output, timestamp);
+
+		if let Some(output) = &mut output {
+			output.flags = frame.flags;
+		}
+
+		Ok(output)
+	}
+
+	/// Convert the samples held back, at the end of the stream.
+	/// Returns `None` if there are none
+	pub fn flush(&mut self) -> Result<Option<Frame>> {
+		let mut output = self.output_frame();
+
+		self.context.flush_frame(&mut output.data)?;
+
+		Ok(self.finish_frame(output, self.next_timestamp))
+	}
+}
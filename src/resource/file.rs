@@ -0,0 +1,108 @@
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use xx_pulse::fs::File;
+
+use super::*;
+
+/// Seeking a local file is cheap, so only read through small gaps
+pub const FILE_SEEK_THRESHOLD: u64 = 32 * 1024;
+
+struct FileStream {
+	file: File,
+	position: u64,
+	length: u64,
+	extension: Option<String>
+}
+
+#[asynchronous]
+impl FileStream {
+	async fn new(path: &Path) -> Result<Self> {
+		let mut file = File::open(path).await?;
+		let length = file.seek(SeekFrom::End(0)).await?;
+
+		file.seek(SeekFrom::Start(0)).await?;
+
+		let extension = path
+			.extension()
+			.and_then(|extension| extension.to_str())
+			.map(ToString::to_string);
+
+		Ok(Self { file, position: 0, length, extension })
+	}
+}
+
+#[asynchronous]
+impl Read for FileStream {
+	async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let read = self.file.read(buf).await?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += read as u64);
+
+		Ok(read)
+	}
+}
+
+#[asynchronous]
+impl Seek for FileStream {
+	async fn seek(&mut self, seek: SeekFrom) -> Result<u64> {
+		self.position = self.file.seek(seek).await?;
+
+		Ok(self.position)
+	}
+
+	fn stream_position_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_position(&mut self) -> Result<u64> {
+		Ok(self.position)
+	}
+
+	fn stream_len_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_len(&mut self) -> Result<u64> {
+		Ok(self.length)
+	}
+}
+
+impl StreamImpl for FileStream {
+	fn suggested_seek_threshold(&self) -> u64 {
+		FILE_SEEK_THRESHOLD
+	}
+
+	fn seekable(&self) -> bool {
+		true
+	}
+
+	fn extension(&self) -> Option<&str> {
+		self.extension.as_deref()
+	}
+}
+
+pub struct FileResource {
+	path: PathBuf
+}
+
+impl FileResource {
+	#[must_use]
+	#[allow(clippy::impl_trait_in_params)]
+	pub fn new(path: impl Into<PathBuf>) -> Self {
+		Self { path: path.into() }
+	}
+
+	#[must_use]
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+#[asynchronous]
+impl ResourceImpl for FileResource {
+	async fn create_stream(&self) -> Result<Stream> {
+		Ok(Box::new(FileStream::new(&self.path).await?))
+	}
+}
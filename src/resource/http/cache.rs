@@ -0,0 +1,532 @@
+#![allow(unreachable_pub)]
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use xx_core::{debug, warn};
+use xx_pulse::fs::File;
+use xx_pulse::sync::Notify;
+
+use super::*;
+
+#[derive(Clone, Debug)]
+pub struct CacheOptions {
+	/// The size of each cached block. Requests are aligned to this size
+	pub block_size: u32,
+
+	/// Least recently used blocks are evicted past this limit
+	pub memory_limit: u64,
+
+	/// If set, full blocks are also stored in this directory
+	///
+	/// Blocks are only stored for resources with an `ETag` or
+	/// `Last-Modified` header, so a changed resource is never
+	/// served from stale blocks
+	pub disk_path: Option<PathBuf>,
+
+	/// The number of blocks to fetch in the background
+	/// ahead of the current read position
+	pub read_ahead: u32,
+
+	/// The maximum number of ranges kept open at once, and the number
+	/// of ranges the read ahead window is split into and fetched at once
+	///
+	/// Keeping more than one open lets the demuxer jump between
+	/// the index and the data without refetching either
+	pub max_connections: u32
+}
+
+impl Default for CacheOptions {
+	fn default() -> Self {
+		Self {
+			block_size: 256 * 1024,
+			memory_limit: 64 * 1024 * 1024,
+			disk_path: None,
+			read_ahead: 4,
+			max_connections: 2
+		}
+	}
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct CacheStats {
+	/// Reads served from memory
+	pub hits: u64,
+
+	/// Reads served from the disk cache
+	pub disk_hits: u64,
+
+	/// Reads that had to be fetched
+	pub misses: u64,
+
+	/// Blocks fetched by read ahead
+	pub prefetched: u64,
+
+	/// Blocks evicted from memory
+	pub evicted: u64,
+
+	/// Read aheads that failed. Their blocks are fetched
+	/// again when read
+	pub read_ahead_failures: u64
+}
+
+struct Entry {
+	data: Arc<[u8]>,
+	tick: u64
+}
+
+#[derive(Default)]
+struct Blocks {
+	entries: HashMap<u64, Entry>,
+	lru: BTreeMap<u64, u64>,
+	tick: u64,
+	used: u64,
+	pending: HashSet<u64>,
+	stats: CacheStats,
+
+	/// The number of read ahead ranges still being fetched
+	prefetching: usize,
+
+	/// The version of the resource the blocks belong to
+	validator: Option<Validator>,
+
+	/// Unset without a validator, as there is no way to tell
+	/// if blocks on disk are from the current version
+	disk_key: Option<u64>
+}
+
+impl Blocks {
+	#[allow(clippy::arithmetic_side_effects)]
+	fn next_tick(&mut self) -> u64 {
+		self.tick += 1;
+		self.tick
+	}
+
+	fn missing(&self, index: u64) -> bool {
+		!self.entries.contains_key(&index) && !self.pending.contains(&index)
+	}
+
+	fn clear(&mut self) {
+		self.entries.clear();
+		self.lru.clear();
+		self.used = 0;
+	}
+
+	fn touch(&mut self, index: u64) -> Option<Arc<[u8]>> {
+		let tick = self.next_tick();
+		let entry = self.entries.get_mut(&index)?;
+
+		self.lru.remove(&entry.tick);
+		self.lru.insert(tick, index);
+
+		entry.tick = tick;
+
+		Some(entry.data.clone())
+	}
+
+	#[allow(clippy::arithmetic_side_effects)]
+	fn insert(&mut self, index: u64, data: Arc<[u8]>, limit: u64) {
+		self.pending.remove(&index);
+
+		if self.entries.contains_key(&index) {
+			return;
+		}
+
+		let tick = self.next_tick();
+
+		self.used += data.len() as u64;
+		self.entries.insert(index, Entry { data, tick });
+		self.lru.insert(tick, index);
+
+		/* always keep the block that was just inserted */
+		while self.used > limit && self.lru.len() > 1 {
+			let Some((_, index)) = self.lru.pop_first() else {
+				break;
+			};
+
+			if let Some(entry) = self.entries.remove(&index) {
+				self.used -= entry.data.len() as u64;
+				self.stats.evicted += 1;
+			}
+		}
+	}
+}
+
+pub enum Lookup {
+	Hit(Arc<[u8]>),
+	Pending,
+	Miss
+}
+
+/// A block cache shared by every stream of an [`HttpResource`]
+pub struct BlockCache {
+	options: CacheOptions,
+	url: String,
+	blocks: Mutex<Blocks>,
+
+	/// Woken whenever a pending block is stored or given up on
+	settled: Notify
+}
+
+/// 64-bit FNV-1a, which unlike the std hashers is the same
+/// across runs and builds
+#[allow(clippy::arithmetic_side_effects)]
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+	const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+	const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+	parts
+		.iter()
+		.flat_map(|part| part.iter())
+		.fold(OFFSET, |hash, byte| {
+			(hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+		})
+}
+
+/// The name of the blocks of this version of `url` on disk
+fn disk_key(url: &str, validator: Option<&Validator>) -> Option<u64> {
+	let (kind, value) = match validator? {
+		Validator::ETag(tag) => (b"E", tag),
+		Validator::LastModified(date) => (b"L", date)
+	};
+
+	Some(fnv1a(&[url.as_bytes(), b"\0", kind, value.as_bytes()]))
+}
+
+#[asynchronous]
+impl BlockCache {
+	pub fn new(options: CacheOptions, url: &str) -> Result<Self> {
+		if options.block_size == 0 {
+			return Err(fmt_error!("Cache block size must be non-zero"));
+		}
+
+		if let Some(path) = &options.disk_path {
+			std::fs::create_dir_all(path)?;
+		}
+
+		Ok(Self {
+			options,
+			url: url.to_string(),
+			blocks: Mutex::new(Blocks::default()),
+			settled: Notify::new()
+		})
+	}
+
+	pub const fn options(&self) -> &CacheOptions {
+		&self.options
+	}
+
+	pub const fn block_size(&self) -> u64 {
+		self.options.block_size as u64
+	}
+
+	#[allow(clippy::unwrap_used)]
+	fn blocks(&self) -> MutexGuard<'_, Blocks> {
+		self.blocks.lock().unwrap()
+	}
+
+	pub fn stats(&self) -> CacheStats {
+		self.blocks().stats
+	}
+
+	#[allow(clippy::arithmetic_side_effects)]
+	pub fn lookup(&self, index: u64) -> Lookup {
+		let mut blocks = self.blocks();
+
+		if let Some(data) = blocks.touch(index) {
+			blocks.stats.hits += 1;
+
+			Lookup::Hit(data)
+		} else if blocks.pending.contains(&index) {
+			Lookup::Pending
+		} else {
+			Lookup::Miss
+		}
+	}
+
+	/// Get a block from memory or disk, waiting for it if
+	/// another request is already fetching it
+	#[allow(clippy::arithmetic_side_effects)]
+	pub async fn get(&self, index: u64) -> Result<Option<Arc<[u8]>>> {
+		loop {
+			match self.lookup(index) {
+				Lookup::Hit(data) => return Ok(Some(data)),
+
+				/* nothing runs between the lookup and waiting, so a block
+				 * settled in between can't be missed. any block settling
+				 * wakes every waiter, which then looks again
+				 */
+				Lookup::Pending => self.settled.notified().await,
+				Lookup::Miss => break
+			}
+		}
+
+		if let Some(data) = self.read_disk(index).await {
+			let mut blocks = self.blocks();
+
+			blocks.stats.disk_hits += 1;
+			blocks.insert(index, data.clone(), self.options.memory_limit);
+
+			return Ok(Some(data));
+		}
+
+		self.blocks().stats.misses += 1;
+
+		Ok(None)
+	}
+
+	/// Drop every block if the resource changed since they were fetched,
+	/// so blocks from two versions are never mixed
+	pub fn validate(&self, validator: Option<&Validator>) {
+		let mut blocks = self.blocks();
+
+		if blocks.validator.as_ref() == validator {
+			return;
+		}
+
+		debug!(target: self, "== Resource changed, dropping cached blocks");
+
+		blocks.clear();
+		blocks.validator = validator.cloned();
+		blocks.disk_key = disk_key(&self.url, validator);
+	}
+
+	/// Mark a block as being fetched. Returns `false` if
+	/// another request is already fetching it
+	pub fn begin(&self, index: u64) -> bool {
+		self.blocks().pending.insert(index)
+	}
+
+	pub fn cancel(&self, index: u64) {
+		self.blocks().pending.remove(&index);
+		self.settled.notify_waiters();
+	}
+
+	#[allow(clippy::arithmetic_side_effects)]
+	pub async fn store(&self, index: u64, data: Vec<u8>, prefetched: bool) -> Arc<[u8]> {
+		let data: Arc<[u8]> = data.into();
+
+		{
+			let mut blocks = self.blocks();
+
+			if prefetched {
+				blocks.stats.prefetched += 1;
+			}
+
+			blocks.insert(index, data.clone(), self.options.memory_limit);
+		}
+
+		self.settled.notify_waiters();
+		self.write_disk(index, &data).await;
+
+		data
+	}
+
+	/// Reserve the missing blocks in the read ahead window after
+	/// `index`, if no other read ahead is in progress
+	///
+	/// Returns the inclusive ranges of blocks to fetch, up to
+	/// `max_connections` of them, each to be released separately
+	#[allow(clippy::arithmetic_side_effects)]
+	pub fn reserve_read_ahead(&self, index: u64, length: Option<u64>) -> Vec<(u64, u64)> {
+		let block_size = self.block_size();
+		let mut blocks = self.blocks();
+
+		if blocks.prefetching != 0 || self.options.read_ahead == 0 {
+			return Vec::new();
+		}
+
+		let in_range = |block: u64| {
+			length.is_none_or(|len| block.checked_mul(block_size).is_some_and(|pos| pos < len))
+		};
+
+		let end = index.saturating_add(self.options.read_ahead as u64);
+		let Some(first) =
+			((index + 1)..=end).find(|block| in_range(*block) && blocks.missing(*block))
+		else {
+			return Vec::new();
+		};
+
+		let mut last = first;
+
+		while last < end && in_range(last + 1) && blocks.missing(last + 1) {
+			last += 1;
+		}
+
+		for block in first..=last {
+			blocks.pending.insert(block);
+		}
+
+		let count = last - first + 1;
+		let per_range = count.div_ceil(count.min(self.options.max_connections.max(1) as u64));
+		let mut ranges = Vec::new();
+		let mut start = first;
+
+		while start <= last {
+			let end = (start + per_range - 1).min(last);
+
+			ranges.push((start, end));
+			start = end + 1;
+		}
+
+		blocks.prefetching = ranges.len();
+
+		ranges
+	}
+
+	/// Release one range of blocks reserved by [`Self::reserve_read_ahead`],
+	/// counting the read ahead as failed if `failed` is set
+	#[allow(clippy::arithmetic_side_effects)]
+	pub fn release_read_ahead(&self, first: u64, last: u64, failed: bool) {
+		{
+			let mut blocks = self.blocks();
+
+			for block in first..=last {
+				blocks.pending.remove(&block);
+			}
+
+			if failed {
+				blocks.stats.read_ahead_failures += 1;
+			}
+
+			blocks.prefetching = blocks.prefetching.saturating_sub(1);
+		}
+
+		self.settled.notify_waiters();
+	}
+
+	fn block_path(&self, index: u64) -> Option<PathBuf> {
+		let path = self.options.disk_path.as_ref()?;
+		let key = self.blocks().disk_key?;
+
+		Some(path.join(format!("{:016x}-{}.block", key, index)))
+	}
+
+	/// Only full blocks are stored on disk, so a short file
+	/// is a partial write and is ignored
+	async fn read_disk(&self, index: u64) -> Option<Arc<[u8]>> {
+		let path = self.block_path(index)?;
+		let mut file = File::open(&path).await.ok()?;
+
+		#[allow(clippy::cast_possible_truncation)]
+		let data = read_full(&mut file, self.options.block_size as usize)
+			.await
+			.ok()?;
+
+		(data.len() == self.options.block_size as usize).then(|| data.into())
+	}
+
+	async fn write_disk(&self, index: u64, data: &[u8]) {
+		let Some(path) = self.block_path(index) else {
+			return;
+		};
+
+		if data.len() != self.options.block_size as usize {
+			return;
+		}
+
+		let mut file = match File::create(&path).await {
+			Ok(file) => file,
+			Err(err) => {
+				warn!(target: self, "== Failed to create cache block {}: {:?}", index, err);
+
+				return;
+			}
+		};
+
+		if let Err(err) = file.write_all(data).await {
+			warn!(target: self, "== Failed to write cache block {}: {:?}", index, err);
+		}
+	}
+}
+
+/// Fill `buf`, stopping early only at the end of the stream
+#[asynchronous]
+pub async fn read_into<R>(reader: &mut R, buf: &mut [u8]) -> Result<usize>
+where
+	R: Read
+{
+	let mut filled = 0;
+
+	while filled < buf.len() {
+		let read = reader.read(&mut buf[filled..]).await?;
+
+		if read == 0 {
+			break;
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(filled += read);
+	}
+
+	Ok(filled)
+}
+
+/// Read up to `size` bytes, stopping early only at the end of the stream
+#[asynchronous]
+pub async fn read_full<R>(reader: &mut R, size: usize) -> Result<Vec<u8>>
+where
+	R: Read
+{
+	let mut data = vec![0; size];
+	let filled = read_into(reader, &mut data).await?;
+
+	data.truncate(filled);
+
+	Ok(data)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	const URL: &str = "https://example.com/audio.webm";
+
+	#[test]
+	fn disk_keys() {
+		let etag = Validator::ETag("\"abc\"".to_string());
+		let date = Validator::LastModified("\"abc\"".to_string());
+
+		assert_eq!(fnv1a(&[b"a"]), 0xaf63_dc4c_8601_ec8c);
+		assert_eq!(fnv1a(&[b"foo", b"bar"]), 0x8594_4171_f739_67e8);
+
+		assert_eq!(disk_key(URL, None), None);
+		assert_eq!(disk_key(URL, Some(&etag)), disk_key(URL, Some(&etag)));
+		assert_ne!(disk_key(URL, Some(&etag)), disk_key(URL, Some(&date)));
+		assert_ne!(
+			disk_key(URL, Some(&etag)),
+			disk_key("https://example.com/", Some(&etag))
+		);
+	}
+
+	#[test]
+	fn read_ahead_ranges() {
+		let options = CacheOptions {
+			read_ahead: 5,
+			max_connections: 2,
+			..Default::default()
+		};
+		let cache = BlockCache::new(options, URL).unwrap();
+
+		assert_eq!(cache.reserve_read_ahead(0, None), vec![(1, 3), (4, 5)]);
+		assert!(cache.reserve_read_ahead(0, None).is_empty());
+
+		cache.release_read_ahead(1, 3, false);
+
+		assert!(cache.reserve_read_ahead(0, None).is_empty());
+
+		cache.release_read_ahead(4, 5, true);
+
+		assert_eq!(cache.stats().read_ahead_failures, 1);
+
+		/* blocks past the end of the resource are never fetched */
+		let length = cache.block_size() * 3;
+
+		assert_eq!(
+			cache.reserve_read_ahead(0, Some(length)),
+			vec![(1, 1), (2, 2)]
+		);
+	}
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+/// A now playing update sent by an internet radio station
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IcyEvent {
+	/// The offset in the stream, after metadata is removed,
+	/// of the first byte the update applies to
+	pub position: u64,
+
+	/// When the update was read from the connection
+	pub received: Instant,
+
+	/// The `StreamTitle` field, usually `Artist - Title`
+	pub title: Option<String>,
+
+	/// The `StreamUrl` field
+	pub url: Option<String>
+}
+
+#[derive(Default)]
+struct Events {
+	queue: VecDeque<IcyEvent>,
+	last: Option<(Option<String>, Option<String>)>
+}
+
+/// A queue of ICY metadata updates shared by every stream
+/// of an [`HttpResource`]
+///
+/// [`HttpResource`]: super::HttpResource
+#[derive(Clone, Default)]
+pub struct IcyEvents(Arc<Mutex<Events>>);
+
+impl IcyEvents {
+	#[allow(clippy::unwrap_used)]
+	fn events(&self) -> MutexGuard<'_, Events> {
+		self.0.lock().unwrap()
+	}
+
+	/// Take the oldest update
+	#[must_use]
+	pub fn pop(&self) -> Option<IcyEvent> {
+		self.events().queue.pop_front()
+	}
+
+	/// Take the oldest update if it applies at or before `position`
+	#[must_use]
+	pub fn pop_until(&self, position: u64) -> Option<IcyEvent> {
+		let mut events = self.events();
+
+		if events.queue.front()?.position > position {
+			return None;
+		}
+
+		events.queue.pop_front()
+	}
+
+	/// Take every pending update
+	#[must_use]
+	pub fn drain(&self) -> Vec<IcyEvent> {
+		self.events().queue.drain(..).collect()
+	}
+
+	/// Parse a metadata block, such as
+	/// `StreamTitle='Artist - Title';StreamUrl='';`
+	///
+	/// Repeats of the previous update are ignored
+	pub(super) fn parse(&self, data: &[u8], position: u64) {
+		let text = decode(data);
+		let text = text.trim_end_matches('\0');
+
+		if text.is_empty() {
+			return;
+		}
+
+		let mut title = None;
+		let mut url = None;
+
+		for (key, value) in fields(text) {
+			let value = (!value.is_empty()).then(|| value.to_string());
+
+			match key {
+				"StreamTitle" => title = value,
+				"StreamUrl" => url = value,
+				_ => ()
+			}
+		}
+
+		let mut events = self.events();
+		let current = Some((title.clone(), url.clone()));
+
+		if events.last == current {
+			return;
+		}
+
+		events.last = current;
+		events
+			.queue
+			.push_back(IcyEvent { position, received: Instant::now(), title, url });
+	}
+}
+
+/// Metadata is usually UTF-8, but older stations send Latin-1
+fn decode(data: &[u8]) -> String {
+	match std::str::from_utf8(data) {
+		Ok(text) => text.to_string(),
+		Err(_) => data.iter().map(|byte| char::from(*byte)).collect()
+	}
+}
+
+/// Split `key='value';` pairs. Values may contain quotes
+/// and semicolons, so only `';` ends a value
+#[allow(clippy::arithmetic_side_effects)]
+fn fields(mut text: &str) -> Vec<(&str, &str)> {
+	let mut fields = Vec::new();
+
+	while let Some(start) = text.find("='") {
+		let key = text[0..start].trim_matches(|ch: char| ch == ';' || ch.is_whitespace());
+		let rest = &text[start + 2..];
+
+		let (value, next) = match rest.find("';") {
+			Some(end) => (&rest[0..end], &rest[end + 2..]),
+			None => (rest.strip_suffix('\'').unwrap_or(rest), "")
+		};
+
+		fields.push((key, value));
+		text = next;
+	}
+
+	fields
+}
+
+/// Removes the metadata interleaved every `interval` bytes
+pub(super) struct IcyReader {
+	pub(super) interval: usize,
+
+	/// Audio bytes left before the next metadata block
+	pub(super) remaining: usize
+}
+
+impl IcyReader {
+	pub(super) const fn new(interval: usize) -> Self {
+		Self { interval, remaining: interval }
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	fn update(event: &IcyEvent) -> (u64, Option<&str>, Option<&str>) {
+		(event.position, event.title.as_deref(), event.url.as_deref())
+	}
+
+	#[test]
+	fn split_fields() {
+		assert_eq!(
+			fields("StreamTitle='Artist - Title';StreamUrl='http://a.b/';"),
+			[
+				("StreamTitle", "Artist - Title"),
+				("StreamUrl", "http://a.b/")
+			]
+		);
+
+		/* quotes and semicolons inside values */
+		assert_eq!(
+			fields("StreamTitle='Guns N' Roses; Live';"),
+			[("StreamTitle", "Guns N' Roses; Live")]
+		);
+
+		/* the final terminator may be missing */
+		assert_eq!(fields("StreamTitle='Title'"), [("StreamTitle", "Title")]);
+		assert_eq!(fields("StreamTitle='';"), [("StreamTitle", "")]);
+		assert!(fields("garbage").is_empty());
+	}
+
+	#[test]
+	fn latin1() {
+		assert_eq!(decode("Café".as_bytes()), "Café");
+		assert_eq!(decode(b"Caf\xe9"), "Café");
+	}
+
+	#[test]
+	fn parse() {
+		let events = IcyEvents::default();
+
+		events.parse(b"StreamTitle='A - B';StreamUrl='';\0\0\0\0", 100);
+
+		/* repeats are dropped, and empty blocks are not updates */
+		events.parse(b"StreamTitle='A - B';StreamUrl='';\0\0\0\0", 200);
+		events.parse(b"\0\0\0\0\0\0\0\0", 300);
+		events.parse(b"StreamTitle='C - D';StreamUrl='http://c.d/';", 400);
+		events.parse(b"StreamTitle='';", 500);
+
+		let updates = events.drain();
+		let updates: Vec<_> = updates.iter().map(update).collect();
+
+		assert_eq!(
+			updates,
+			[
+				(100, Some("A - B"), None),
+				(400, Some("C - D"), Some("http://c.d/")),
+				(500, None, None)
+			]
+		);
+
+		assert!(events.pop().is_none());
+	}
+
+	#[test]
+	fn pop_until() {
+		let events = IcyEvents::default();
+
+		events.parse(b"StreamTitle='A';", 100);
+		events.parse(b"StreamTitle='B';", 200);
+
+		assert!(events.pop_until(99).is_none());
+		assert_eq!(events.pop_until(150).unwrap().title.as_deref(), Some("A"));
+		assert!(events.pop_until(150).is_none());
+		assert_eq!(events.pop_until(200).unwrap().title.as_deref(), Some("B"));
+		assert!(events.pop_until(u64::MAX).is_none());
+	}
+}
@@ -0,0 +1,1111 @@
+use std::io::SeekFrom;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use xx_core::{debug, warn};
+use xx_url::http::{get, Body, HttpRequest, StatusCode};
+use xx_url::net::conn::IpStrategy;
+
+use super::*;
+
+mod cache;
+mod icy;
+mod proxy;
+mod request;
+mod retry;
+
+use self::cache::*;
+pub use self::cache::{CacheOptions, CacheStats};
+use self::icy::*;
+pub use self::icy::{IcyEvent, IcyEvents};
+pub use self::proxy::Proxy;
+use self::proxy::*;
+pub use self::request::RedirectPolicy;
+use self::request::*;
+pub use self::retry::*;
+
+#[errors]
+pub enum HttpError {
+	#[display("HTTP error {}", f0)]
+	BadStatus(StatusCode),
+
+	#[display("HTTP request timed out")]
+	#[kind = ErrorKind::TimedOut]
+	TimedOut,
+
+	#[display("Resource changed on the server")]
+	#[kind = ErrorKind::InvalidData]
+	ContentChanged,
+
+	#[display("Too many redirects")]
+	TooManyRedirects,
+
+	#[display("Proxy error {}", f0)]
+	ProxyStatus(StatusCode),
+
+	#[display("SOCKS proxy error {}", f0)]
+	SocksFailed(u8),
+
+	#[display("Invalid HTTP response")]
+	#[kind = ErrorKind::InvalidData]
+	InvalidResponse,
+
+	#[display("TLS error: {}", f0)]
+	Tls(String)
+}
+
+/// Identifies one version of the resource, sent as `If-Range` so
+/// the server refuses to mix a different version into a range
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Validator {
+	ETag(String),
+	LastModified(String)
+}
+
+impl Validator {
+	/// The validator of a response. Weak tags can't be used with
+	/// `If-Range`, so the modification date is used instead
+	fn from_headers(etag: Option<&str>, last_modified: Option<&str>) -> Option<Self> {
+		match etag.filter(|tag| !tag.starts_with("W/")) {
+			Some(tag) => Some(Self::ETag(tag.to_string())),
+			None => last_modified.map(|date| Self::LastModified(date.to_string()))
+		}
+	}
+
+	fn as_str(&self) -> &str {
+		match self {
+			Self::ETag(tag) => tag,
+			Self::LastModified(date) => date
+		}
+	}
+}
+
+/// The headers of a response that are used
+const RESPONSE_HEADERS: [&str; 7] = [
+	"Location",
+	"Retry-After",
+	"ETag",
+	"Last-Modified",
+	"icy-metaint",
+	"Content-Range",
+	"Content-Type"
+];
+
+enum ResponseBody {
+	Direct(Body),
+	Proxied(ProxyBody)
+}
+
+#[asynchronous]
+impl Read for ResponseBody {
+	async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		match self {
+			Self::Direct(body) => body.read(buf).await,
+			Self::Proxied(body) => body.read(buf).await
+		}
+	}
+}
+
+/// A response from the HTTP client, or from a proxy
+struct Response {
+	status: StatusCode,
+	headers: Vec<(String, String)>,
+	body: ResponseBody
+}
+
+impl Response {
+	fn header(&self, name: &str) -> Option<&str> {
+		self.headers
+			.iter()
+			.find(|(header, _)| header.eq_ignore_ascii_case(name))
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+struct RangeResponse {
+	body: ResponseBody,
+	position: u64,
+	length: Option<u64>,
+	validator: Option<Validator>,
+	content_type: Option<String>,
+
+	/// The number of audio bytes between ICY metadata blocks
+	icy_interval: Option<usize>
+}
+
+enum Attempt {
+	Done(RangeResponse),
+
+	/// A non success status, and the delay requested by the server
+	Status(StatusCode, Option<Duration>),
+
+	/// A failure that retrying cannot fix
+	Fatal(Error)
+}
+
+/// Request settings shared by the resource, its streams and background
+/// fetches
+#[derive(Clone)]
+struct HttpOptions {
+	url: String,
+	strategy: IpStrategy,
+	proxy: Option<Proxy>,
+	headers: Vec<(String, String)>,
+	cookies: Vec<(String, String)>,
+	redirects: RedirectPolicy,
+	retry: RetryPolicy,
+	timeouts: Timeouts,
+	icy_metadata: bool,
+	icy_events: IcyEvents,
+
+	/// The URL at the end of the redirect chain, shared by every
+	/// stream so later requests skip the chain
+	final_url: Arc<Mutex<Option<String>>>
+}
+
+#[asynchronous]
+impl HttpOptions {
+	fn get_range(range: &str) -> Option<(u64, u64)> {
+		let mut split = range.split_whitespace();
+
+		if !split.next()?.eq_ignore_ascii_case("bytes") {
+			return None;
+		}
+
+		let mut range_and_length = split.next()?.split('/');
+		let start = range_and_length.next()?.split('-').next()?;
+
+		Some((start.parse().ok()?, range_and_length.next()?.parse().ok()?))
+	}
+
+	/// The `Range` header for the bytes from `start` to `end`, inclusive
+	fn range_header(start: u64, end: Option<u64>) -> String {
+		match end {
+			Some(end) => format!("bytes={}-{}", start, end),
+			None => format!("bytes={}-", start)
+		}
+	}
+
+	#[allow(clippy::unwrap_used)]
+	fn final_url(&self) -> Option<String> {
+		self.final_url.lock().unwrap().clone()
+	}
+
+	#[allow(clippy::unwrap_used)]
+	fn set_final_url(&self, url: Option<String>) {
+		*self.final_url.lock().unwrap() = url;
+	}
+
+	fn deadline(&self) -> Option<Instant> {
+		self.timeouts
+			.total
+			.and_then(|total| Instant::now().checked_add(total))
+	}
+
+	fn connect_timeout(&self, deadline: Option<Instant>) -> Option<Duration> {
+		let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+		match (self.timeouts.connect, remaining) {
+			(Some(connect), Some(remaining)) => Some(connect.min(remaining)),
+			(connect, remaining) => connect.or(remaining)
+		}
+	}
+
+	/// Wait before the next attempt, failing if the delay
+	/// would pass the deadline
+	async fn wait(&self, delay: Duration, deadline: Option<Instant>) -> Result<()> {
+		if deadline.is_some_and(|deadline| Instant::now().checked_add(delay) >= Some(deadline)) {
+			return Err(HttpError::TimedOut.into());
+		}
+
+		debug!("== Retrying request in {:?}", delay);
+
+		sleep(delay).await
+	}
+
+	/// Send a `GET` request, through the proxy if one is set
+	async fn send(&self, url: &str, headers: &[(String, String)]) -> Result<Response> {
+		if let Some(proxy) = &self.proxy {
+			let response = get_through(proxy, url, headers).await?;
+
+			return Ok(Response {
+				status: response.status,
+				headers: response.headers,
+				body: ResponseBody::Proxied(response.body)
+			});
+		}
+
+		let mut request = get(url);
+
+		request.set_strategy(self.strategy);
+
+		for (name, value) in headers {
+			request.header(name.as_str(), value.as_str());
+		}
+
+		let response = HttpRequest::run(&mut request).await?;
+		let mut kept = Vec::new();
+
+		for name in RESPONSE_HEADERS {
+			if let Some(value) = response.headers().get_str(name)? {
+				kept.push((name.to_string(), value.to_string()));
+			}
+		}
+
+		Ok(Response {
+			status: response.status(),
+			headers: kept,
+			body: ResponseBody::Direct(response.into_body())
+		})
+	}
+
+	async fn try_get_body_for(
+		&self, start: u64, end: Option<u64>, validator: Option<&Validator>,
+		deadline: Option<Instant>
+	) -> Result<Attempt> {
+		let cached_url = self.final_url();
+		let mut url = cached_url.clone().unwrap_or_else(|| self.url.clone());
+		let mut redirects = 0;
+		let mut position = 0;
+		let mut length = None;
+
+		let range = Self::range_header(start, end);
+
+		let origin = url_origin(&self.url);
+
+		let response = loop {
+			/* credentials are only for the origin they were set for */
+			let same_origin = url_origin(&url) == origin;
+			let mut headers: Vec<_> = self
+				.headers
+				.iter()
+				.filter(|(name, _)| same_origin || !is_credential_header(name))
+				.cloned()
+				.collect();
+
+			if same_origin && !self.cookies.is_empty() {
+				let cookies: Vec<_> = self
+					.cookies
+					.iter()
+					.map(|(name, value)| format!("{}={}", name, value))
+					.collect();
+
+				headers.push(("Cookie".to_string(), cookies.join("; ")));
+			}
+
+			headers.push(("Range".to_string(), range.clone()));
+
+			if let Some(validator) = validator {
+				headers.push(("If-Range".to_string(), validator.as_str().to_string()));
+			}
+
+			if self.icy_metadata {
+				headers.push(("Icy-MetaData".to_string(), "1".to_string()));
+			}
+
+			let response = match self.connect_timeout(deadline) {
+				Some(duration) => timeout(duration, self.send(&url, &headers))
+					.await
+					.ok_or(HttpError::TimedOut)??,
+				None => self.send(&url, &headers).await?
+			};
+
+			if !response.status.is_redirection() {
+				break response;
+			}
+
+			let RedirectPolicy::Follow(max_redirects) = self.redirects else {
+				break response;
+			};
+
+			let Some(location) = response.header("Location") else {
+				break response;
+			};
+
+			if redirects >= max_redirects {
+				return Ok(Attempt::Fatal(HttpError::TooManyRedirects.into()));
+			}
+
+			url = resolve_location(&url, location);
+
+			debug!("== Redirected to {}", url);
+
+			#[allow(clippy::arithmetic_side_effects)]
+			(redirects += 1);
+		};
+
+		let status = response.status;
+
+		if !status.is_success() {
+			/* the remembered url may have expired, so the next
+			 * request starts from the original
+			 */
+			if cached_url.is_some() {
+				self.set_final_url(None);
+			}
+
+			let retry_after = response
+				.header("Retry-After")
+				.and_then(|value| self.retry.retry_after(value));
+
+			return Ok(Attempt::Status(status, retry_after));
+		}
+
+		let current =
+			Validator::from_headers(response.header("ETag"), response.header("Last-Modified"));
+
+		/* a full response to an If-Range request means
+		 * the validator no longer matches
+		 */
+		if let Some(validator) = validator {
+			if status != StatusCode::PARTIAL_CONTENT || current.as_ref() != Some(validator) {
+				return Ok(Attempt::Fatal(HttpError::ContentChanged.into()));
+			}
+		}
+
+		let icy_interval = if self.icy_metadata {
+			response
+				.header("icy-metaint")
+				.and_then(|interval| interval.trim().parse().ok())
+				.filter(|interval| *interval > 0)
+		} else {
+			None
+		};
+
+		#[allow(clippy::never_loop)]
+		loop {
+			let Some(range) = response.header("Content-Range") else {
+				break;
+			};
+
+			let Some((pos, len)) = Self::get_range(range) else {
+				break;
+			};
+
+			position = pos;
+			length = Some(len);
+
+			if pos == start {
+				break;
+			}
+
+			return Ok(Attempt::Fatal(FormatError::InvalidSeek(pos, start).into()));
+		}
+
+		let content_type = response.header("Content-Type").map(ToString::to_string);
+
+		if redirects > 0 {
+			self.set_final_url(Some(url));
+		}
+
+		Ok(Attempt::Done(RangeResponse {
+			body: response.body,
+			position,
+			length,
+			validator: current,
+			content_type,
+			icy_interval
+		}))
+	}
+
+	/// Request the bytes from `start` to `end`, inclusive,
+	/// or to the end of the file if `end` is `None`
+	///
+	/// Failed requests are retried according to the retry policy
+	/// until `deadline`
+	async fn get_body_for(
+		&self, start: u64, end: Option<u64>, validator: Option<&Validator>,
+		deadline: Option<Instant>
+	) -> Result<RangeResponse> {
+		let mut attempt = 0;
+
+		loop {
+			#[allow(clippy::arithmetic_side_effects)]
+			(attempt += 1);
+
+			let last = attempt >= self.retry.max_attempts;
+			let delay = match self.try_get_body_for(start, end, validator, deadline).await {
+				Ok(Attempt::Done(response)) => return Ok(response),
+				Ok(Attempt::Fatal(err)) => return Err(err),
+				Ok(Attempt::Status(status, retry_after)) => {
+					if last || !self.retry.should_retry_status(status.as_u16()) {
+						return Err(HttpError::BadStatus(status).into());
+					}
+
+					warn!("== Request failed with status {}, retrying", status);
+
+					retry_after.unwrap_or_else(|| self.retry.backoff(attempt))
+				}
+
+				Err(err) => {
+					if last || err.is_interrupted() {
+						return Err(err);
+					}
+
+					warn!("== Request failed, retrying ({:?})", err);
+
+					self.retry.backoff(attempt)
+				}
+			};
+
+			self.wait(delay, deadline).await?;
+		}
+	}
+}
+
+struct Connection {
+	body: ResponseBody,
+	position: u64,
+	idle_timeout: Option<Duration>
+}
+
+impl Connection {
+	fn new(response: RangeResponse, idle_timeout: Option<Duration>) -> Self {
+		Self {
+			body: response.body,
+			position: response.position,
+			idle_timeout
+		}
+	}
+}
+
+#[asynchronous]
+impl Read for Connection {
+	async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let read = match self.idle_timeout {
+			Some(duration) => timeout(duration, self.body.read(buf))
+				.await
+				.ok_or(HttpError::TimedOut)??,
+			None => self.body.read(buf).await?
+		};
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += read as u64);
+
+		Ok(read)
+	}
+}
+
+struct HttpStream {
+	options: Arc<HttpOptions>,
+	cache: Option<Arc<BlockCache>>,
+
+	/// The version of the resource this stream reads. Only set if
+	/// the server supports ranges
+	validator: Option<Validator>,
+
+	/// Open ranges, least recently used first
+	connections: Vec<Connection>,
+	max_connections: usize,
+
+	/// Set if the server interleaves ICY metadata, which makes
+	/// the stream live and unseekable
+	icy: Option<IcyReader>,
+
+	/// Format hints from the first response and the URL
+	content_type: Option<String>,
+	extension: Option<String>,
+
+	position: u64,
+	length: Option<u64>
+}
+
+#[asynchronous]
+impl HttpStream {
+	async fn new(options: Arc<HttpOptions>, mut cache: Option<Arc<BlockCache>>) -> Result<Self> {
+		let response = options
+			.get_body_for(0, None, None, options.deadline())
+			.await?;
+		let (position, length) = (response.position, response.length);
+		let validator = response.validator.clone().filter(|_| length.is_some());
+		let icy = response.icy_interval.map(IcyReader::new);
+		let content_type = response.content_type.clone();
+		let extension = options
+			.final_url()
+			.and_then(|url| url_extension(&url))
+			.or_else(|| url_extension(&options.url));
+
+		if cache.is_some() && length.is_none() {
+			debug!("== Server does not support range requests, disabling cache");
+
+			cache = None;
+		}
+
+		if icy.is_some() {
+			debug!("== Stream has ICY metadata, disabling cache");
+
+			cache = None;
+		}
+
+		if let Some(cache) = &cache {
+			cache.validate(validator.as_ref());
+		}
+
+		let max_connections = cache
+			.as_ref()
+			.map_or(1, |cache| cache.options().max_connections.max(1) as usize);
+
+		Ok(Self {
+			connections: vec![Connection::new(response, options.timeouts.idle_read)],
+			options,
+			cache,
+			validator,
+			max_connections,
+			icy,
+			content_type,
+			extension,
+			position,
+			length
+		})
+	}
+
+	/// Find an open range at `position`, or open a new one,
+	/// closing the least recently used range if at the limit
+	///
+	/// Returns the index of the connection, which is always the last
+	async fn connection_at(&mut self, position: u64, deadline: Option<Instant>) -> Result<usize> {
+		match self
+			.connections
+			.iter()
+			.position(|conn| conn.position == position)
+		{
+			Some(index) => {
+				let conn = self.connections.remove(index);
+
+				self.connections.push(conn);
+			}
+
+			None => {
+				let response = self
+					.options
+					.get_body_for(position, None, self.validator.as_ref(), deadline)
+					.await?;
+
+				if self.connections.len() >= self.max_connections {
+					self.connections.remove(0);
+				}
+
+				if response.length.is_some() {
+					self.length = response.length;
+				}
+
+				self.connections
+					.push(Connection::new(response, self.options.timeouts.idle_read));
+			}
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		Ok(self.connections.len() - 1)
+	}
+
+	/// Read at `start` through an open range, reconnecting according
+	/// to the retry policy if the body fails
+	///
+	/// If `full` is set, fills `buf` unless the stream ends first
+	async fn read_at(&mut self, start: u64, buf: &mut [u8], full: bool) -> Result<usize> {
+		let deadline = self.options.deadline();
+		let mut attempt = 0;
+
+		loop {
+			#[allow(clippy::arithmetic_side_effects)]
+			(attempt += 1);
+
+			let index = self.connection_at(start, deadline).await?;
+			let conn = &mut self.connections[index];
+
+			if conn.position != start {
+				return Err(FormatError::InvalidSeek(start, conn.position).into());
+			}
+
+			let result = if full {
+				read_into(conn, buf).await
+			} else {
+				conn.read(buf).await
+			};
+
+			match result {
+				Ok(read) => return Ok(read),
+				Err(err) if err.is_interrupted() || attempt >= self.options.retry.max_attempts => {
+					return Err(err)
+				}
+
+				Err(err) => {
+					warn!(target: &*self, "== Read from body failed, retrying ({:?})", err);
+
+					self.connections.remove(index);
+					self.options
+						.wait(self.options.retry.backoff(attempt), deadline)
+						.await?;
+				}
+			}
+		}
+	}
+
+	async fn read_direct(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let read = self.read_at(self.position, buf, false).await?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += read as u64);
+
+		Ok(read)
+	}
+
+	/// Read audio from an ICY stream, removing the metadata blocks
+	async fn try_read_icy(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let (Some(icy), Some(conn)) = (self.icy.as_mut(), self.connections.last_mut()) else {
+			return Err(ErrorKind::UnexpectedEof.into());
+		};
+
+		if icy.remaining == 0 {
+			let mut length = [0];
+
+			if read_into(conn, &mut length).await? == 0 {
+				return Ok(0);
+			}
+
+			#[allow(clippy::arithmetic_side_effects)]
+			let mut data = vec![0; usize::from(length[0]) * 16];
+
+			if read_into(conn, &mut data).await? != data.len() {
+				return Err(ErrorKind::UnexpectedEof.into());
+			}
+
+			self.options.icy_events.parse(&data, self.position);
+
+			icy.remaining = icy.interval;
+		}
+
+		let len = buf.len().min(icy.remaining);
+		let read = conn.read(&mut buf[0..len]).await?;
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(icy.remaining -= read);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += read as u64);
+
+		Ok(read)
+	}
+
+	/// Live streams cannot resume where they failed, so reconnect
+	/// and continue from wherever the station is now
+	async fn read_icy(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let deadline = self.options.deadline();
+		let mut attempt = 0;
+
+		loop {
+			#[allow(clippy::arithmetic_side_effects)]
+			(attempt += 1);
+
+			let err = match self.try_read_icy(buf).await {
+				Ok(read) => return Ok(read),
+				Err(err) => err
+			};
+
+			if err.is_interrupted() || attempt >= self.options.retry.max_attempts {
+				return Err(err);
+			}
+
+			warn!(target: &*self, "== Read from ICY stream failed, reconnecting ({:?})", err);
+
+			self.options
+				.wait(self.options.retry.backoff(attempt), deadline)
+				.await?;
+
+			let response = self.options.get_body_for(0, None, None, deadline).await?;
+
+			let Some(interval) = response.icy_interval else {
+				return Err(fmt_error!("Reconnected stream has no ICY metadata"));
+			};
+
+			self.icy = Some(IcyReader::new(interval));
+			self.connections = vec![Connection::new(response, self.options.timeouts.idle_read)];
+		}
+	}
+
+	async fn fetch_block(&mut self, cache: &BlockCache, block: u64) -> Result<Vec<u8>> {
+		let start = block
+			.checked_mul(cache.block_size())
+			.ok_or(ErrorKind::Overflow)?;
+
+		#[allow(clippy::cast_possible_truncation)]
+		let mut data = vec![0; cache.block_size() as usize];
+		let read = self.read_at(start, &mut data, true).await?;
+
+		data.truncate(read);
+
+		Ok(data)
+	}
+
+	async fn read_cached(&mut self, cache: Arc<BlockCache>, buf: &mut [u8]) -> Result<usize> {
+		if self.length.is_some_and(|len| self.position >= len) {
+			return Ok(0);
+		}
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let (block, offset) = (
+			self.position / cache.block_size(),
+			self.position % cache.block_size()
+		);
+
+		let data = loop {
+			if let Some(data) = cache.get(block).await? {
+				break data;
+			}
+
+			/* another reader started fetching it first, so wait for theirs */
+			if !cache.begin(block) {
+				continue;
+			}
+
+			match self.fetch_block(&cache, block).await {
+				Ok(data) => break cache.store(block, data, false).await,
+				Err(err) => {
+					cache.cancel(block);
+
+					return Err(err);
+				}
+			}
+		};
+
+		self.read_ahead(&cache, block).await;
+
+		#[allow(clippy::cast_possible_truncation)]
+		let available = data.get(offset as usize..).unwrap_or_default();
+		let read = available.len().min(buf.len());
+
+		buf[0..read].copy_from_slice(&available[0..read]);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += read as u64);
+
+		Ok(read)
+	}
+
+	/// Fetch the blocks after `block` in the background, with one
+	/// ranged request at once for each range reserved
+	async fn read_ahead(&self, cache: &Arc<BlockCache>, block: u64) {
+		for (first, last) in cache.reserve_read_ahead(block, self.length) {
+			let options = self.options.clone();
+			let cache = cache.clone();
+			let validator = self.validator.clone();
+
+			/* the tasks are detached, so failures are logged and counted in the
+			 * cache stats. readers fetch the blocks themselves once released
+			 */
+			let _ = spawn(async move {
+				let result =
+					read_ahead_blocks(&options, &cache, validator.as_ref(), first, last).await;
+
+				if let Err(err) = &result {
+					warn!(
+						"== Read ahead of blocks {}-{} failed: {:?}",
+						first, last, err
+					);
+				}
+
+				cache.release_read_ahead(first, last, result.is_err());
+			})
+			.await;
+		}
+	}
+}
+
+#[asynchronous]
+#[allow(clippy::arithmetic_side_effects)]
+async fn read_ahead_blocks(
+	options: &HttpOptions, cache: &BlockCache, validator: Option<&Validator>, first: u64, last: u64
+) -> Result<()> {
+	let block_size = cache.block_size();
+	let start = first.checked_mul(block_size).ok_or(ErrorKind::Overflow)?;
+	let end = (last + 1)
+		.checked_mul(block_size)
+		.and_then(|end| end.checked_sub(1))
+		.ok_or(ErrorKind::Overflow)?;
+
+	let response = options
+		.get_body_for(start, Some(end), validator, options.deadline())
+		.await?;
+	let mut conn = Connection::new(response, options.timeouts.idle_read);
+
+	for block in first..=last {
+		#[allow(clippy::cast_possible_truncation)]
+		let data = read_full(&mut conn, block_size as usize).await?;
+
+		if data.is_empty() {
+			break;
+		}
+
+		cache.store(block, data, true).await;
+	}
+
+	Ok(())
+}
+
+#[asynchronous]
+impl Read for HttpStream {
+	async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		if self.icy.is_some() {
+			return self.read_icy(buf).await;
+		}
+
+		match self.cache.clone() {
+			Some(cache) => self.read_cached(cache, buf).await,
+			None => self.read_direct(buf).await
+		}
+	}
+}
+
+#[asynchronous]
+impl Seek for HttpStream {
+	#[allow(clippy::unwrap_used)]
+	async fn seek(&mut self, seek: SeekFrom) -> Result<u64> {
+		let pos = match seek {
+			SeekFrom::Current(pos) => self.position.checked_add_signed(pos).unwrap(),
+			SeekFrom::Start(pos) => pos,
+			SeekFrom::End(pos) => self.stream_len().await?.checked_add_signed(pos).unwrap()
+		};
+
+		if self.icy.is_some() {
+			if pos != self.position {
+				return Err(FormatError::CannotSeek.into());
+			}
+
+			return Ok(self.position);
+		}
+
+		/* cached streams fetch blocks lazily */
+		if self.cache.is_some() {
+			self.position = pos;
+
+			return Ok(self.position);
+		}
+
+		let index = self.connection_at(pos, self.options.deadline()).await?;
+
+		self.position = self.connections[index].position;
+
+		Ok(self.position)
+	}
+
+	fn stream_position_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_position(&mut self) -> Result<u64> {
+		Ok(self.position)
+	}
+
+	fn stream_len_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_len(&mut self) -> Result<u64> {
+		match self.length {
+			Some(len) => Ok(len),
+			None => return Err(fmt_error!("Unknown length"))
+		}
+	}
+}
+
+impl StreamImpl for HttpStream {
+	fn seekable(&self) -> bool {
+		self.icy.is_none()
+	}
+
+	fn content_type(&self) -> Option<&str> {
+		self.content_type.as_deref()
+	}
+
+	fn extension(&self) -> Option<&str> {
+		self.extension.as_deref()
+	}
+}
+
+pub struct HttpResource {
+	options: HttpOptions,
+	cache: Option<Arc<BlockCache>>
+}
+
+impl HttpResource {
+	#[must_use]
+	#[allow(clippy::impl_trait_in_params)]
+	pub fn new(url: impl Into<String>) -> Self {
+		Self {
+			options: HttpOptions {
+				url: url.into(),
+				strategy: IpStrategy::Default,
+				proxy: None,
+				headers: Vec::new(),
+				cookies: Vec::new(),
+				redirects: RedirectPolicy::default(),
+				retry: RetryPolicy::default(),
+				timeouts: Timeouts::default(),
+				icy_metadata: false,
+				icy_events: IcyEvents::default(),
+				final_url: Arc::new(Mutex::new(None))
+			},
+			cache: None
+		}
+	}
+
+	pub fn set_strategy(&mut self, strategy: IpStrategy) -> &mut Self {
+		self.options.strategy = strategy;
+		self
+	}
+
+	/// Send requests through a proxy. The IP strategy does not
+	/// apply to proxied requests
+	///
+	/// Passing `None` connects directly
+	pub fn set_proxy(&mut self, proxy: Option<Proxy>) -> &mut Self {
+		self.options.proxy = proxy;
+		self
+	}
+
+	/// Set a header sent with every request, replacing any
+	/// previous value of the same header
+	#[allow(clippy::impl_trait_in_params)]
+	pub fn set_header(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+		let name = name.into();
+
+		self.options
+			.headers
+			.retain(|(existing, _)| !existing.eq_ignore_ascii_case(&name));
+		self.options.headers.push((name, value.into()));
+		self
+	}
+
+	/// Set a cookie sent with every request
+	#[allow(clippy::impl_trait_in_params)]
+	pub fn set_cookie(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+		let name = name.into();
+
+		self.options
+			.cookies
+			.retain(|(existing, _)| *existing != name);
+		self.options.cookies.push((name, value.into()));
+		self
+	}
+
+	pub fn set_redirect_policy(&mut self, redirects: RedirectPolicy) -> &mut Self {
+		self.options.redirects = redirects;
+		self
+	}
+
+	/// The URL at the end of the redirect chain, once a request
+	/// has followed one
+	#[must_use]
+	pub fn final_url(&self) -> Option<String> {
+		self.options.final_url()
+	}
+
+	/// Request ICY metadata from internet radio stations. The metadata
+	/// is removed from the stream and queued in [`Self::icy_events`]
+	pub fn set_icy_metadata(&mut self, enabled: bool) -> &mut Self {
+		self.options.icy_metadata = enabled;
+		self
+	}
+
+	/// Now playing updates from every stream of this resource
+	#[must_use]
+	pub fn icy_events(&self) -> IcyEvents {
+		self.options.icy_events.clone()
+	}
+
+	/// Set how failed requests and body reads are retried
+	pub fn set_retry_policy(&mut self, retry: RetryPolicy) -> &mut Self {
+		self.options.retry = retry;
+		self
+	}
+
+	pub fn set_timeouts(&mut self, timeouts: Timeouts) -> &mut Self {
+		self.options.timeouts = timeouts;
+		self
+	}
+
+	/// Cache the fetched bytes in blocks. Every stream created
+	/// from this resource shares the same cache
+	///
+	/// Passing `None` disables the cache
+	pub fn set_cache(&mut self, options: Option<CacheOptions>) -> Result<&mut Self> {
+		self.cache = match options {
+			Some(options) => Some(Arc::new(BlockCache::new(options, &self.options.url)?)),
+			None => None
+		};
+
+		Ok(self)
+	}
+
+	#[must_use]
+	pub fn cache_stats(&self) -> Option<CacheStats> {
+		self.cache.as_ref().map(|cache| cache.stats())
+	}
+}
+
+#[asynchronous]
+impl ResourceImpl for HttpResource {
+	async fn create_stream(&self) -> Result<Stream> {
+		let options = Arc::new(self.options.clone());
+
+		Ok(Box::new(
+			HttpStream::new(options, self.cache.clone()).await?
+		))
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn range_header() {
+		assert_eq!(HttpOptions::range_header(0, None), "bytes=0-");
+		assert_eq!(HttpOptions::range_header(1024, None), "bytes=1024-");
+		assert_eq!(
+			HttpOptions::range_header(1024, Some(2047)),
+			"bytes=1024-2047"
+		);
+	}
+
+	#[test]
+	fn validator() {
+		let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+		assert_eq!(
+			Validator::from_headers(Some("\"abc\""), Some(date)),
+			Some(Validator::ETag("\"abc\"".to_string()))
+		);
+
+		assert_eq!(
+			Validator::from_headers(Some("W/\"abc\""), Some(date)),
+			Some(Validator::LastModified(date.to_string()))
+		);
+
+		assert_eq!(Validator::from_headers(Some("W/\"abc\""), None), None);
+		assert_eq!(Validator::from_headers(None, None), None);
+		assert_eq!(Validator::ETag("\"abc\"".to_string()).as_str(), "\"abc\"");
+		assert_eq!(Validator::LastModified(date.to_string()).as_str(), date);
+	}
+
+	#[test]
+	fn content_range() {
+		assert_eq!(HttpOptions::get_range("bytes 0-99/1000"), Some((0, 1000)));
+		assert_eq!(
+			HttpOptions::get_range("BYTES 500-999/1000"),
+			Some((500, 1000))
+		);
+		assert_eq!(
+			HttpOptions::get_range("bytes  200-299/300"),
+			Some((200, 300))
+		);
+
+		/* an unknown length can't be used */
+		assert_eq!(HttpOptions::get_range("bytes 0-99/*"), None);
+		assert_eq!(HttpOptions::get_range("bytes */1000"), None);
+		assert_eq!(HttpOptions::get_range("items 0-99/1000"), None);
+		assert_eq!(HttpOptions::get_range("bytes"), None);
+		assert_eq!(HttpOptions::get_range(""), None);
+	}
+}
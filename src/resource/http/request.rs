@@ -0,0 +1,244 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedirectPolicy {
+	/// Redirects are returned as a bad status
+	None,
+
+	/// Follow up to this many redirects per request
+	Follow(u32)
+}
+
+impl Default for RedirectPolicy {
+	fn default() -> Self {
+		Self::Follow(10)
+	}
+}
+
+/// The user info, host and port of `url`. An ipv6 host keeps
+/// its brackets
+pub(super) fn url_authority(url: &str) -> (Option<&str>, &str, Option<u16>) {
+	let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+	let authority = &rest[0..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+	let (user_info, host) = match authority.rsplit_once('@') {
+		Some((user_info, host)) => (Some(user_info), host),
+		None => (None, authority)
+	};
+
+	/* the port follows the last colon, unless it is inside an ipv6 address */
+	match host.rsplit_once(':') {
+		Some((host, port)) if !port.contains(']') => (user_info, host, port.parse().ok()),
+		_ => (user_info, host, None)
+	}
+}
+
+/// The scheme, host and port of `url`, lowercased and with the
+/// default port filled in, to tell whether two URLs share an origin
+pub(super) fn url_origin(url: &str) -> (String, String, u16) {
+	let scheme = url
+		.split_once("://")
+		.map_or("http", |(scheme, _)| scheme)
+		.to_ascii_lowercase();
+	let (_, host, port) = url_authority(url);
+	let port = port.unwrap_or(if scheme == "https" { 443 } else { 80 });
+
+	(scheme, host.to_ascii_lowercase(), port)
+}
+
+/// The path and query of `url`, as the target of a request
+/// sent to its origin
+pub(super) fn url_target(url: &str) -> String {
+	let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+	let path = &rest[rest.find(['/', '?', '#']).unwrap_or(rest.len())..];
+	let path = &path[0..path.find('#').unwrap_or(path.len())];
+
+	if path.starts_with('/') {
+		path.to_string()
+	} else {
+		format!("/{}", path)
+	}
+}
+
+/// Whether a header carries credentials, which are not sent
+/// to another origin after a redirect
+pub(super) fn is_credential_header(name: &str) -> bool {
+	["Authorization", "Proxy-Authorization", "Cookie"]
+		.iter()
+		.any(|header| name.eq_ignore_ascii_case(header))
+}
+
+/// Resolve the `Location` header of a redirect against the URL
+/// that returned it
+#[allow(clippy::arithmetic_side_effects)]
+pub(super) fn resolve_location(base: &str, location: &str) -> String {
+	if location.contains("://") {
+		return location.to_string();
+	}
+
+	let (scheme, rest) = base.split_once("://").unwrap_or(("http", base));
+
+	if let Some(location) = location.strip_prefix("//") {
+		return format!("{}://{}", scheme, location);
+	}
+
+	let path_start = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+	let authority = &rest[0..path_start];
+
+	if location.starts_with('/') {
+		return format!("{}://{}{}", scheme, authority, location);
+	}
+
+	/* relative to the directory of the current path, ignoring its query */
+	let path = &rest[path_start..];
+	let path = &path[0..path.find(['?', '#']).unwrap_or(path.len())];
+	let directory = &path[0..path.rfind('/').map_or(0, |index| index + 1)];
+
+	if directory.is_empty() {
+		format!("{}://{}/{}", scheme, authority, location)
+	} else {
+		format!("{}://{}{}{}", scheme, authority, directory, location)
+	}
+}
+
+/// The extension of the last path segment of `url`, if any
+pub(super) fn url_extension(url: &str) -> Option<String> {
+	let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+	let path = &rest[rest.find('/')?..];
+	let path = &path[0..path.find(['?', '#']).unwrap_or(path.len())];
+	let name = &path[path.rfind('/').map_or(0, |index| index + 1)..];
+	let (stem, extension) = name.rsplit_once('.')?;
+
+	(!stem.is_empty() && !extension.is_empty()).then(|| extension.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn origin(scheme: &str, host: &str, port: u16) -> (String, String, u16) {
+		(scheme.to_string(), host.to_string(), port)
+	}
+
+	#[test]
+	fn origins() {
+		assert_eq!(
+			url_origin("https://example.com/a.mp3"),
+			origin("https", "example.com", 443)
+		);
+		assert_eq!(
+			url_origin("HTTP://Example.COM"),
+			origin("http", "example.com", 80)
+		);
+		assert_eq!(
+			url_origin("http://example.com:8000/"),
+			origin("http", "example.com", 8000)
+		);
+		assert_eq!(
+			url_origin("http://user:pw@example.com/"),
+			origin("http", "example.com", 80)
+		);
+		assert_eq!(url_origin("http://[::1]/a"), origin("http", "[::1]", 80));
+		assert_eq!(
+			url_origin("http://[::1]:8080/a"),
+			origin("http", "[::1]", 8080)
+		);
+		assert_eq!(
+			url_origin("example.com?q"),
+			origin("http", "example.com", 80)
+		);
+
+		/* the default port is the same origin as no port */
+		assert_eq!(
+			url_origin("https://example.com:443/"),
+			url_origin("https://example.com/")
+		);
+		assert_ne!(
+			url_origin("https://example.com/"),
+			url_origin("http://example.com/")
+		);
+		assert_ne!(
+			url_origin("https://example.com/"),
+			url_origin("https://cdn.example.com/")
+		);
+	}
+
+	#[test]
+	fn authorities() {
+		assert_eq!(
+			url_authority("http://example.com/a"),
+			(None, "example.com", None)
+		);
+		assert_eq!(
+			url_authority("socks5://user:pw@proxy:1080"),
+			(Some("user:pw"), "proxy", Some(1080))
+		);
+		assert_eq!(
+			url_authority("http://[::1]:8080/a"),
+			(None, "[::1]", Some(8080))
+		);
+		assert_eq!(url_authority("proxy:3128"), (None, "proxy", Some(3128)));
+	}
+
+	#[test]
+	fn targets() {
+		assert_eq!(url_target("http://example.com"), "/");
+		assert_eq!(url_target("http://example.com/a.mp3?x=1"), "/a.mp3?x=1");
+		assert_eq!(url_target("http://example.com?x=1"), "/?x=1");
+		assert_eq!(url_target("http://example.com/a.mp3#t=10"), "/a.mp3");
+	}
+
+	#[test]
+	fn credential_headers() {
+		assert!(is_credential_header("Authorization"));
+		assert!(is_credential_header("proxy-authorization"));
+		assert!(is_credential_header("COOKIE"));
+		assert!(!is_credential_header("User-Agent"));
+		assert!(!is_credential_header("Set-Cookie"));
+	}
+
+	#[test]
+	fn locations() {
+		let base = "http://example.com/music/a.mp3?token=1";
+
+		assert_eq!(
+			resolve_location(base, "https://cdn.net/b.mp3"),
+			"https://cdn.net/b.mp3"
+		);
+		assert_eq!(
+			resolve_location(base, "//cdn.net/b.mp3"),
+			"http://cdn.net/b.mp3"
+		);
+		assert_eq!(resolve_location(base, "/b.mp3"), "http://example.com/b.mp3");
+		assert_eq!(
+			resolve_location(base, "b.mp3"),
+			"http://example.com/music/b.mp3"
+		);
+		assert_eq!(
+			resolve_location("http://example.com", "b.mp3"),
+			"http://example.com/b.mp3"
+		);
+		assert_eq!(
+			resolve_location("http://example.com?q=a/b", "b.mp3"),
+			"http://example.com/b.mp3"
+		);
+	}
+
+	#[test]
+	fn extensions() {
+		assert_eq!(
+			url_extension("http://example.com/a.mp3"),
+			Some("mp3".to_string())
+		);
+		assert_eq!(
+			url_extension("http://example.com/b/a.tar.gz?x=1"),
+			Some("gz".to_string())
+		);
+		assert_eq!(
+			url_extension("http://example.com/a.flac#t=10"),
+			Some("flac".to_string())
+		);
+		assert_eq!(url_extension("http://example.com/stream"), None);
+		assert_eq!(url_extension("http://example.com/.hidden"), None);
+		assert_eq!(url_extension("http://example.com/a."), None);
+		assert_eq!(url_extension("http://example.com"), None);
+		assert_eq!(url_extension("http://example.com/dir.d/"), None);
+	}
+}
@@ -0,0 +1,259 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+	/// The maximum number of attempts, including the first.
+	/// A value of 1 disables retries
+	pub max_attempts: u32,
+
+	/// The delay before the first retry
+	pub initial_backoff: Duration,
+
+	/// The upper bound on the delay between retries
+	pub max_backoff: Duration,
+
+	/// The factor the delay grows by after each retry
+	pub multiplier: f64,
+
+	/// The fraction of each delay that is randomized, from 0.0 to 1.0
+	pub jitter: f64,
+
+	/// Status codes that are retried. Other non success
+	/// statuses fail immediately
+	pub retry_statuses: Vec<u16>,
+
+	/// Wait as long as the server asks in a `Retry-After` header,
+	/// up to `max_retry_after`
+	pub respect_retry_after: bool,
+	pub max_retry_after: Duration
+}
+
+impl RetryPolicy {
+	#[must_use]
+	pub fn none() -> Self {
+		Self { max_attempts: 1, ..Default::default() }
+	}
+
+	#[must_use]
+	pub fn should_retry_status(&self, status: u16) -> bool {
+		self.retry_statuses.contains(&status)
+	}
+
+	/// The delay before retrying after `attempt` failed attempts,
+	/// with jitter applied
+	#[must_use]
+	#[allow(
+		clippy::cast_possible_wrap,
+		clippy::cast_possible_truncation,
+		clippy::cast_precision_loss,
+		clippy::arithmetic_side_effects
+	)]
+	pub fn backoff(&self, attempt: u32) -> Duration {
+		let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+		let delay = self.initial_backoff.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+		let delay = delay.min(self.max_backoff.as_secs_f64());
+
+		let jitter = self.jitter.clamp(0.0, 1.0);
+		let random = (random_u64() >> 11) as f64 / (1u64 << 53) as f64;
+
+		Duration::from_secs_f64(delay * (1.0 - jitter * random))
+	}
+
+	/// The delay requested by a `Retry-After` header, if it should be
+	/// respected
+	#[must_use]
+	pub fn retry_after(&self, value: &str) -> Option<Duration> {
+		if !self.respect_retry_after {
+			return None;
+		}
+
+		parse_retry_after(value).map(|delay| delay.min(self.max_retry_after))
+	}
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_attempts: 4,
+			initial_backoff: Duration::from_millis(250),
+			max_backoff: Duration::from_secs(8),
+			multiplier: 2.0,
+			jitter: 0.5,
+			retry_statuses: vec![408, 429, 500, 502, 503, 504],
+			respect_retry_after: true,
+			max_retry_after: Duration::from_secs(30)
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timeouts {
+	/// Time allowed to connect and receive the response headers
+	pub connect: Option<Duration>,
+
+	/// Time allowed between receiving two chunks of the body
+	pub idle_read: Option<Duration>,
+
+	/// Time allowed to get a response, including all retries and
+	/// backoff. Reads that reconnect get this long again to do so.
+	/// Reading the body is only bounded by `idle_read`, so a stream
+	/// can be read for longer than this
+	pub total: Option<Duration>
+}
+
+/// The next number of a splitmix64 sequence shared by the process,
+/// seeded from the random keys of the std hasher
+fn random_u64() -> u64 {
+	const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+	static STATE: OnceLock<AtomicU64> = OnceLock::new();
+
+	let state = STATE.get_or_init(|| AtomicU64::new(RandomState::new().build_hasher().finish()));
+	let mut value = state
+		.fetch_add(GAMMA, Ordering::Relaxed)
+		.wrapping_add(GAMMA);
+
+	value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+	value ^ (value >> 31)
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+	let value = value.trim();
+
+	if let Ok(seconds) = value.parse() {
+		return Some(Duration::from_secs(seconds));
+	}
+
+	let date = parse_http_date(value)?;
+
+	Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// Parse an IMF-fixdate, such as `Sun, 06 Nov 1994 08:49:37 GMT`
+#[allow(clippy::arithmetic_side_effects, clippy::cast_sign_loss)]
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+	const MONTHS: &[&str] = &[
+		"Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"
+	];
+
+	let mut parts = value.split_whitespace().skip(1);
+	let day: i64 = parts.next()?.parse().ok()?;
+	let month = parts.next()?;
+	let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+	let year: i64 = parts.next()?.parse().ok()?;
+
+	let mut time = parts.next()?.split(':');
+	let hour: i64 = time.next()?.parse().ok()?;
+	let minute: i64 = time.next()?.parse().ok()?;
+	let second: i64 = time.next()?.parse().ok()?;
+
+	if parts.next()? != "GMT" || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+		return None;
+	}
+
+	/* days from civil, proleptic gregorian calendar */
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let year_of_era = year - era * 400;
+	let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	let days = era * 146_097 + day_of_era - 719_468;
+
+	let seconds = days * 86_400 + hour * 3600 + minute * 60 + second;
+
+	if seconds < 0 {
+		return None;
+	}
+
+	Some(UNIX_EPOCH + Duration::from_secs(seconds as u64))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn backoff() {
+		let policy = RetryPolicy { jitter: 0.0, ..Default::default() };
+
+		assert_eq!(policy.backoff(1), Duration::from_millis(250));
+		assert_eq!(policy.backoff(2), Duration::from_millis(500));
+		assert_eq!(policy.backoff(3), Duration::from_secs(1));
+		assert_eq!(policy.backoff(10), Duration::from_secs(8));
+		assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(8));
+	}
+
+	#[test]
+	fn backoff_jitter() {
+		let policy = RetryPolicy::default();
+
+		for _ in 0..100 {
+			let delay = policy.backoff(3);
+
+			assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1));
+		}
+	}
+
+	#[test]
+	fn random() {
+		let values: Vec<_> = (0..100).map(|_| random_u64()).collect();
+
+		assert!(values.windows(2).all(|pair| pair[0] != pair[1]));
+	}
+
+	#[test]
+	fn statuses() {
+		let policy = RetryPolicy::default();
+
+		assert!(policy.should_retry_status(503));
+		assert!(!policy.should_retry_status(404));
+		assert_eq!(RetryPolicy::none().max_attempts, 1);
+	}
+
+	#[test]
+	fn retry_after() {
+		let mut policy = RetryPolicy::default();
+
+		assert_eq!(policy.retry_after("5"), Some(Duration::from_secs(5)));
+		assert_eq!(policy.retry_after(" 120 "), Some(Duration::from_secs(30)));
+		assert_eq!(policy.retry_after("soon"), None);
+
+		/* dates in the past mean now */
+		assert_eq!(
+			policy.retry_after("Sun, 06 Nov 1994 08:49:37 GMT"),
+			Some(Duration::ZERO)
+		);
+
+		policy.respect_retry_after = false;
+
+		assert_eq!(policy.retry_after("5"), None);
+	}
+
+	#[test]
+	fn http_date() {
+		let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+
+		assert_eq!(date, UNIX_EPOCH + Duration::from_secs(784_111_777));
+
+		let date = parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT").unwrap();
+
+		assert_eq!(date, UNIX_EPOCH + Duration::from_secs(1_709_164_800));
+
+		assert_eq!(
+			parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"),
+			Some(UNIX_EPOCH)
+		);
+		assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+		assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+		assert_eq!(parse_http_date("Sun, 32 Nov 1994 08:49:37 GMT"), None);
+		assert_eq!(parse_http_date("Sun, 06 Nov 1994 24:49:37 GMT"), None);
+		assert_eq!(parse_http_date("Sun, 06 Nov 1994"), None);
+		assert_eq!(parse_http_date("Wed, 31 Dec 1969 23:59:59 GMT"), None);
+	}
+}
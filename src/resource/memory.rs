@@ -0,0 +1,99 @@
+use std::io::SeekFrom;
+use std::sync::Arc;
+
+use super::*;
+
+struct MemoryStream {
+	data: Arc<[u8]>,
+	position: u64
+}
+
+#[asynchronous]
+impl Read for MemoryStream {
+	async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		#[allow(clippy::cast_possible_truncation)]
+		let start = self.position.min(self.data.len() as u64) as usize;
+		let available = &self.data[start..];
+		let read = available.len().min(buf.len());
+
+		buf[0..read].copy_from_slice(&available[0..read]);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		(self.position += read as u64);
+
+		Ok(read)
+	}
+}
+
+#[asynchronous]
+impl Seek for MemoryStream {
+	async fn seek(&mut self, seek: SeekFrom) -> Result<u64> {
+		let pos = match seek {
+			SeekFrom::Current(pos) => self.position.checked_add_signed(pos),
+			SeekFrom::Start(pos) => Some(pos),
+			SeekFrom::End(pos) => (self.data.len() as u64).checked_add_signed(pos)
+		};
+
+		self.position = pos.ok_or(ErrorKind::InvalidInput)?;
+
+		Ok(self.position)
+	}
+
+	fn stream_position_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_position(&mut self) -> Result<u64> {
+		Ok(self.position)
+	}
+
+	fn stream_len_fast(&self) -> bool {
+		true
+	}
+
+	async fn stream_len(&mut self) -> Result<u64> {
+		Ok(self.data.len() as u64)
+	}
+}
+
+impl StreamImpl for MemoryStream {
+	/// Seeking is free, never read through a gap
+	fn suggested_seek_threshold(&self) -> u64 {
+		0
+	}
+
+	fn seekable(&self) -> bool {
+		true
+	}
+}
+
+/// A resource over shared bytes, such as embedded test fixtures
+///
+/// Every stream created from this resource shares the same data
+#[derive(Clone)]
+pub struct MemoryResource {
+	data: Arc<[u8]>
+}
+
+impl MemoryResource {
+	#[must_use]
+	#[allow(clippy::impl_trait_in_params)]
+	pub fn new(data: impl Into<Arc<[u8]>>) -> Self {
+		Self { data: data.into() }
+	}
+
+	#[must_use]
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+}
+
+#[asynchronous]
+impl ResourceImpl for MemoryResource {
+	async fn create_stream(&self) -> Result<Stream> {
+		Ok(Box::new(MemoryStream {
+			data: self.data.clone(),
+			position: 0
+		}))
+	}
+}
@@ -2,8 +2,13 @@ use xx_core::async_std::io::*;
 
 use super::*;
 
+pub mod file;
 pub mod http;
+pub mod memory;
+
+pub use file::*;
 pub use http::*;
+pub use memory::*;
 
 pub const DEFAULT_SEEK_THRESHOLD: u64 = 512 * 1024;
 
@@ -16,6 +21,16 @@ pub trait StreamImpl: Read + Seek {
 	fn seekable(&self) -> bool {
 		false
 	}
+
+	/// The MIME type of the content, if known
+	fn content_type(&self) -> Option<&str> {
+		None
+	}
+
+	/// The file name extension of the content, if known
+	fn extension(&self) -> Option<&str> {
+		None
+	}
 }
 
 #[asynchronous(impl(ref, mut, box))]
@@ -0,0 +1,466 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::*;
+use crate::filter::*;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+	Reading,
+
+	/// The decoder was told to drain, and is reopened with new codec
+	/// parameters once empty if `reopen` is set
+	Draining {
+		reopen: bool
+	},
+	Finished
+}
+
+/// The format of the frames going into the filter graph
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct GraphInput {
+	sample_fmt: SampleFormat,
+	ch_layout: ChannelLayout,
+	sample_rate: u32
+}
+
+impl GraphInput {
+	pub(crate) fn new(frame: &Frame) -> Self {
+		Self {
+			sample_fmt: frame.sample_format,
+			ch_layout: frame.ch_layout.clone(),
+			sample_rate: frame.sample_rate
+		}
+	}
+}
+
+/// What converts decoded frames to the output format
+enum Converter {
+	Graph(AudioFilterGraph),
+
+	/// Used instead of a graph when there are no filters,
+	/// with the frame converted last
+	Resampler(Resampler, Option<Frame>)
+}
+
+impl Converter {
+	fn update_filter(&mut self, handle: FilterHandle, filter: &dyn Filter) -> Result<()> {
+		match self {
+			Self::Graph(graph) => graph.update_filter(handle, filter),
+			Self::Resampler(..) => Err(ErrorKind::Unsupported.into())
+		}
+	}
+
+	fn send_frame(&mut self, frame: Frame) -> Result<()> {
+		match self {
+			Self::Graph(graph) => graph.send_frame(frame),
+			Self::Resampler(resampler, ready) => {
+				*ready = resampler.convert(&frame)?;
+
+				Ok(())
+			}
+		}
+	}
+
+	fn receive_frame(&mut self) -> Result<Option<Frame>> {
+		match self {
+			Self::Graph(graph) => graph.receive_frame(),
+			Self::Resampler(_, ready) => Ok(ready.take())
+		}
+	}
+
+	fn drain(&mut self) -> Result<()> {
+		match self {
+			Self::Graph(graph) => graph.drain(),
+			Self::Resampler(resampler, ready) => {
+				*ready = resampler.flush()?;
+
+				Ok(())
+			}
+		}
+	}
+}
+
+/// Decodes one audio track of a format through a filter graph,
+/// producing frames in the requested output format
+///
+/// Without filters or a frame size, frames are converted with
+/// a [`Resampler`] instead of a filter graph
+///
+/// Packets are read, decoded, trimmed for gapless playback and
+/// filtered as frames are requested. Codec parameter changes,
+/// seeking and the end of the stream are handled internally
+pub struct AudioSource {
+	format: Format,
+	track_index: u32,
+	codec: Codec,
+	state: DecodeState,
+
+	gapless: GaplessTrimmer,
+	seek_trimmer: Option<FrameTrimmer>,
+
+	filters: Vec<Box<dyn Filter + Send + Sync>>,
+	output: AudioSinkOptions,
+	graph: Option<(Converter, GraphInput)>,
+	graph_drained: bool,
+	gain: GainRamp,
+
+	/// Frames flushed out of a replaced filter graph
+	ready: VecDeque<Frame>
+}
+
+#[asynchronous]
+impl AudioSource {
+	/// Open `stream` and decode its first audio track
+	pub async fn open(stream: Stream, output: AudioSinkOptions) -> Result<Self> {
+		let format = Format::open(stream).await?;
+		let track_index = format
+			.tracks
+			.iter()
+			.position(|track| track.ty == MediaType::Audio)
+			.ok_or(FormatError::NoTracks)?;
+
+		#[allow(clippy::cast_possible_truncation)]
+		Self::new(format, track_index as u32, output)
+	}
+
+	/// Decode the track at `track_index` of `format`. Other tracks
+	/// are discarded
+	pub fn new(mut format: Format, track_index: u32, output: AudioSinkOptions) -> Result<Self> {
+		let track = format
+			.tracks
+			.get(track_index as usize)
+			.ok_or(FormatError::TrackNotFound)?;
+
+		if track.ty != MediaType::Audio {
+			return Err(FormatError::TrackNotFound.into());
+		}
+
+		for (index, track) in format.tracks.iter_mut().enumerate() {
+			if index != track_index as usize {
+				track.discard = Discard::All;
+			}
+		}
+
+		let track = &mut format.tracks[track_index as usize];
+		let codec = Codec::new(&mut track.codec_params, Mode::Decode)?;
+		let gapless = GaplessTrimmer::new(&track.codec_params);
+
+		Ok(Self {
+			format,
+			track_index,
+			codec,
+			state: DecodeState::Reading,
+			gapless,
+			seek_trimmer: None,
+			filters: Vec::new(),
+			output,
+			graph: None,
+			graph_drained: false,
+			gain: GainRamp::default(),
+			ready: VecDeque::new()
+		})
+	}
+
+	#[must_use]
+	pub const fn format(&self) -> &Format {
+		&self.format
+	}
+
+	#[must_use]
+	pub const fn track_index(&self) -> u32 {
+		self.track_index
+	}
+
+	#[must_use]
+	pub fn track(&self) -> &Track {
+		&self.format.tracks[self.track_index as usize]
+	}
+
+	/// Replace the filters. Frames already in the old graph are
+	/// flushed out first, so no audio is lost
+	pub fn set_filters(&mut self, filters: Vec<Box<dyn Filter + Send + Sync>>) -> Result<()> {
+		self.flush_graph()?;
+		self.filters = filters;
+
+		Ok(())
+	}
+
+	/// The handle to the filter at `index` in the filters
+	#[must_use]
+	pub fn filter(&self, index: usize) -> Option<FilterHandle> {
+		(index < self.filters.len()).then_some(FilterHandle::new(index))
+	}
+
+	/// Change the settings of the filter at `handle`. The running filter
+	/// is changed in place if it can be, otherwise the graph is flushed
+	/// and rebuilt
+	pub fn update_filter(
+		&mut self, handle: FilterHandle, filter: Box<dyn Filter + Send + Sync>
+	) -> Result<()> {
+		let index = handle.index();
+
+		if index >= self.filters.len() {
+			return Err(ErrorKind::InvalidInput.into());
+		}
+
+		let updated = match &mut self.graph {
+			Some((graph, _)) => match graph.update_filter(handle, &*filter) {
+				Ok(()) => true,
+				Err(err) if err == ErrorKind::Unsupported => false,
+				Err(err) => return Err(err)
+			},
+
+			None => true
+		};
+
+		if !updated {
+			self.flush_graph()?;
+		}
+
+		self.filters[index] = filter;
+
+		Ok(())
+	}
+
+	/// Move the gain of the output to `gain` over the next
+	/// `ramp_samples` samples, such as for a volume slider
+	pub fn set_gain(&mut self, gain: f64, ramp_samples: u32) {
+		self.gain.set(gain, ramp_samples);
+	}
+
+	/// Drain the filter graph into the ready queue, so the next frame
+	/// builds a new graph
+	fn flush_graph(&mut self) -> Result<()> {
+		let Some((mut graph, _)) = self.graph.take() else {
+			return Ok(());
+		};
+
+		if !self.graph_drained {
+			graph.drain()?;
+		}
+
+		while let Some(frame) = graph.receive_frame()? {
+			self.ready.push_back(frame);
+		}
+
+		self.graph_drained = false;
+
+		Ok(())
+	}
+
+	fn create_graph(&self, frame: &Frame, input: &GraphInput) -> Result<Converter> {
+		let time_base = if frame.time_base.num != 0 {
+			frame.time_base
+		} else {
+			self.track().time_base
+		};
+
+		let src = AudioSrcOptions {
+			time_base: Some(time_base),
+			sample_fmt: input.sample_fmt,
+			ch_layout: input.ch_layout.clone(),
+			sample_rate: input.sample_rate
+		};
+
+		if self.filters.is_empty() && self.output.frame_size.is_none() {
+			let resampler = Resampler::new(&src, &self.output, &ResamplerOptions::default())?;
+
+			return Ok(Converter::Resampler(resampler, None));
+		}
+
+		let filters: Vec<_> = self
+			.filters
+			.iter()
+			.map(|filter| &**filter as &dyn Filter)
+			.collect();
+
+		Ok(Converter::Graph(AudioFilterGraph::new(
+			&src,
+			&self.output,
+			&filters
+		)?))
+	}
+
+	fn filter_frame(&mut self, frame: Frame) -> Result<()> {
+		let input = GraphInput::new(&frame);
+
+		/* the decoder output may change format mid stream */
+		let (mut graph, input) = match self.graph.take() {
+			Some((graph, current)) if current == input => (graph, current),
+			graph => {
+				self.graph = graph;
+				self.flush_graph()?;
+
+				(self.create_graph(&frame, &input)?, input)
+			}
+		};
+
+		let result = graph.send_frame(frame);
+
+		self.graph = Some((graph, input));
+
+		result
+	}
+
+	/// The next decoded frame, trimmed for gapless playback and seeking
+	async fn next_decoded(&mut self) -> Result<Option<Frame>> {
+		loop {
+			if let Some(mut frame) = self.gapless.pop() {
+				if let Some(trimmer) = &mut self.seek_trimmer {
+					if !trimmer.trim(&mut frame)? {
+						continue;
+					}
+
+					self.seek_trimmer = None;
+				}
+
+				return Ok(Some(frame));
+			}
+
+			if let Some(frame) = self.codec.receive_frame()? {
+				self.gapless.push(frame)?;
+
+				continue;
+			}
+
+			match self.state {
+				DecodeState::Reading => self.read_packet().await?,
+				DecodeState::Draining { reopen: true } if !self.gapless.is_finished() => {
+					/* the frames held back end the old stream */
+					self.gapless.finish();
+				}
+
+				DecodeState::Draining { reopen: true } => {
+					let track = &mut self.format.tracks[self.track_index as usize];
+
+					self.codec = Codec::new(&mut track.codec_params, Mode::Decode)?;
+					self.gapless = GaplessTrimmer::new(&track.codec_params);
+					self.state = DecodeState::Reading;
+				}
+
+				DecodeState::Draining { reopen: false } => {
+					self.gapless.finish();
+					self.state = DecodeState::Finished;
+				}
+
+				DecodeState::Finished => return Ok(None)
+			}
+		}
+	}
+
+	/// Feed the decoder the next packet of the track
+	async fn read_packet(&mut self) -> Result<()> {
+		match self.format.read().await? {
+			Some(FormatItem::Packet(packet)) if packet.track_index == self.track_index => {
+				self.gapless.push_packet(&packet);
+				self.codec.send_packet(&packet)?;
+			}
+
+			Some(FormatItem::Event(FormatEvent::CodecParamsChanged(index)))
+				if index == self.track_index =>
+			{
+				self.codec.drain()?;
+				self.state = DecodeState::Draining { reopen: true };
+			}
+
+			Some(FormatItem::Event(FormatEvent::TrackAdded(index))) => {
+				self.format.tracks[index as usize].discard = Discard::All;
+			}
+
+			Some(_) => (),
+			None => {
+				self.codec.drain()?;
+				self.state = DecodeState::Draining { reopen: false };
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Get the next filtered frame, or `None` at the end of the stream
+	pub async fn next_frame(&mut self) -> Result<Option<Frame>> {
+		let Some(mut frame) = self.next_filtered().await? else {
+			return Ok(None);
+		};
+
+		self.gain.apply(&mut frame)?;
+
+		Ok(Some(frame))
+	}
+
+	async fn next_filtered(&mut self) -> Result<Option<Frame>> {
+		loop {
+			if let Some(frame) = self.ready.pop_front() {
+				return Ok(Some(frame));
+			}
+
+			if let Some((graph, _)) = &mut self.graph {
+				if let Some(frame) = graph.receive_frame()? {
+					return Ok(Some(frame));
+				}
+
+				if self.graph_drained {
+					return Ok(None);
+				}
+			}
+
+			if let Some(frame) = self.next_decoded().await? {
+				self.filter_frame(frame)?;
+
+				continue;
+			}
+
+			match &mut self.graph {
+				Some((graph, _)) => {
+					graph.drain()?;
+					self.graph_drained = true;
+				}
+
+				None => return Ok(None)
+			}
+		}
+	}
+
+	/// Seek to `time`. The next frame starts exactly at `time`,
+	/// if the format allows seeking
+	pub async fn seek(&mut self, time: Duration) -> Result<()> {
+		let time_base = self.track().time_base;
+		let nanos: u64 = time
+			.as_nanos()
+			.try_into()
+			.map_err(|_| ErrorKind::Overflow)?;
+		let timecode = time_base.rescale(nanos, Rational::nanos());
+
+		let target = self
+			.format
+			.seek_with(
+				self.track_index,
+				timecode,
+				BitFlags::default(),
+				SeekMode::Accurate
+			)
+			.await?;
+
+		let track = &mut self.format.tracks[self.track_index as usize];
+
+		/* new parameters arrived before the seek */
+		if self.state == (DecodeState::Draining { reopen: true }) {
+			self.codec = Codec::new(&mut track.codec_params, Mode::Decode)?;
+		} else {
+			self.codec.flush()?;
+		}
+
+		self.state = DecodeState::Reading;
+		self.gapless = GaplessTrimmer::new(&track.codec_params);
+		self.gapless.reset();
+		self.seek_trimmer = Some(FrameTrimmer::new(target));
+
+		/* frames in the graph are from before the seek */
+		self.graph = None;
+		self.graph_drained = false;
+		self.ready.clear();
+
+		Ok(())
+	}
+}
@@ -0,0 +1,394 @@
+use super::*;
+use crate::filter::{AudioFilterGraph, AudioSinkOptions, AudioSrcOptions};
+use crate::muxer::*;
+use crate::source::GraphInput;
+
+/// What to do with a track of the input
+pub enum TrackAction {
+	/// Write the packets to the output as they are
+	Copy,
+
+	/// Leave the track out of the output
+	Drop,
+
+	/// Decode the track and encode it again. Unset sample rates and
+	/// channel layouts are taken from the input, and an unsupported
+	/// sample format or rate is replaced with one the encoder accepts
+	Encode(Box<CodecParams>)
+}
+
+impl TrackAction {
+	#[must_use]
+	pub fn encode(params: CodecParams) -> Self {
+		Self::Encode(Box::new(params))
+	}
+}
+
+/// Decodes an audio track, converts it to the encoder's format
+/// and encodes it
+struct Reencoder {
+	output_index: u32,
+
+	decoder: Codec,
+	gapless: GaplessTrimmer,
+
+	graph: Option<(AudioFilterGraph, GraphInput)>,
+	sink_options: AudioSinkOptions,
+
+	encoder: Codec,
+	time_base: Rational,
+
+	/// The timestamp of the next frame to encode, in `time_base`
+	next_timestamp: i64
+}
+
+impl Reencoder {
+	fn new(track: &mut Track, params: &mut CodecParams) -> Result<Self> {
+		if track.ty != MediaType::Audio {
+			return Err(FormatError::CodecNotFound.into());
+		}
+
+		let input = &mut track.codec_params;
+
+		params.ty = MediaType::Audio;
+
+		if params.sample_rate == 0 {
+			params.sample_rate = input.sample_rate;
+		}
+
+		if params.ch_layout.channel_count() == 0 {
+			params.ch_layout = input.ch_layout.clone();
+		}
+
+		let encoder = Codec::new(params, Mode::Encode)?;
+		let decoder = Codec::new(input, Mode::Decode)?;
+
+		let time_base = if params.time_base.num != 0 {
+			params.time_base
+		} else {
+			Rational::inverse(params.sample_rate)
+		};
+
+		let sink_options = AudioSinkOptions {
+			ch_layout: Some(params.ch_layout.clone()),
+			sample_fmt: params.sample_format,
+			sample_rate: params.sample_rate,
+
+			/* encoders without variable frame sizes need exact frames */
+			frame_size: Some(params.frame_size).filter(|size| *size != 0)
+		};
+
+		Ok(Self {
+			output_index: 0,
+			decoder,
+			gapless: GaplessTrimmer::new(input),
+			graph: None,
+			sink_options,
+			encoder,
+			time_base,
+			next_timestamp: 0
+		})
+	}
+
+	fn receive_packets(&mut self, packets: &mut Vec<Packet>) -> Result<()> {
+		while let Some(mut packet) = self.encoder.receive_packet()? {
+			packet.track_index = self.output_index;
+
+			if packet.time_base.num == 0 {
+				packet.time_base = self.time_base;
+			}
+
+			packets.push(packet);
+		}
+
+		Ok(())
+	}
+
+	/// Encoded tracks start at zero and stay contiguous, as
+	/// decoded timestamps no longer apply once trimmed and resampled
+	#[allow(clippy::cast_possible_wrap)]
+	fn encode(&mut self, mut frame: Frame, packets: &mut Vec<Packet>) -> Result<()> {
+		let samples = self.time_base.rescale(
+			u64::from(frame.samples),
+			Rational::inverse(frame.sample_rate)
+		);
+
+		frame.time_base = self.time_base;
+		frame.presentation_timestamp = self.next_timestamp;
+		frame.best_effort_timestamp = self.next_timestamp;
+		frame.decode_timestamp = self.next_timestamp;
+		frame.duration = samples;
+
+		self.next_timestamp = self
+			.next_timestamp
+			.checked_add(samples as i64)
+			.ok_or(ErrorKind::Overflow)?;
+
+		self.encoder.send_frame(&frame)?;
+		self.receive_packets(packets)
+	}
+
+	fn receive_filtered(&mut self, packets: &mut Vec<Packet>) -> Result<()> {
+		let Some((graph, _)) = &mut self.graph else {
+			return Ok(());
+		};
+
+		let mut frames = Vec::new();
+
+		while let Some(frame) = graph.receive_frame()? {
+			frames.push(frame);
+		}
+
+		for frame in frames {
+			self.encode(frame, packets)?;
+		}
+
+		Ok(())
+	}
+
+	/// Drain the filter graph into the encoder, so a new one can be built
+	fn flush_graph(&mut self, packets: &mut Vec<Packet>) -> Result<()> {
+		if let Some((graph, _)) = &mut self.graph {
+			graph.drain()?;
+		}
+
+		self.receive_filtered(packets)?;
+		self.graph = None;
+
+		Ok(())
+	}
+
+	fn filter(&mut self, frame: Frame, packets: &mut Vec<Packet>) -> Result<()> {
+		let input = GraphInput::new(&frame);
+
+		/* the decoder output may change format mid stream */
+		if !matches!(&self.graph, Some((_, current)) if *current == input) {
+			self.flush_graph(packets)?;
+
+			let src = AudioSrcOptions {
+				time_base: Some(Rational::inverse(frame.sample_rate)),
+				sample_fmt: frame.sample_format,
+				ch_layout: frame.ch_layout.clone(),
+				sample_rate: frame.sample_rate
+			};
+
+			let graph = AudioFilterGraph::new(&src, &self.sink_options, &[])?;
+
+			self.graph = Some((graph, input));
+		}
+
+		if let Some((graph, _)) = &mut self.graph {
+			graph.send_frame(frame)?;
+		}
+
+		self.receive_filtered(packets)
+	}
+
+	fn receive_decoded(&mut self, packets: &mut Vec<Packet>) -> Result<()> {
+		while let Some(frame) = self.decoder.receive_frame()? {
+			self.gapless.push(frame)?;
+		}
+
+		while let Some(frame) = self.gapless.pop() {
+			self.filter(frame, packets)?;
+		}
+
+		Ok(())
+	}
+
+	fn send_packet(&mut self, packet: &Packet, packets: &mut Vec<Packet>) -> Result<()> {
+		self.gapless.push_packet(packet);
+		self.decoder.send_packet(packet)?;
+		self.receive_decoded(packets)
+	}
+
+	/// Decode what is left with the old parameters, then start over
+	/// with the new ones
+	fn reopen_decoder(
+		&mut self, params: &mut CodecParams, packets: &mut Vec<Packet>
+	) -> Result<()> {
+		self.decoder.drain()?;
+		self.receive_decoded(packets)?;
+
+		/* the frames held back end the old stream */
+		self.gapless.finish();
+		self.receive_decoded(packets)?;
+
+		self.decoder = Codec::new(params, Mode::Decode)?;
+		self.gapless = GaplessTrimmer::new(params);
+
+		Ok(())
+	}
+
+	fn finish(&mut self, packets: &mut Vec<Packet>) -> Result<()> {
+		self.decoder.drain()?;
+		self.receive_decoded(packets)?;
+
+		self.gapless.finish();
+		self.receive_decoded(packets)?;
+		self.flush_graph(packets)?;
+
+		self.encoder.drain()?;
+		self.receive_packets(packets)
+	}
+}
+
+enum OutputTrack {
+	Copy(u32),
+	Encode(Box<Reencoder>)
+}
+
+/// Reads a format and writes its tracks to a muxer, copying or
+/// re-encoding each as requested
+///
+/// Timestamps are rescaled to the output, and the encoder delay
+/// of re-encoded tracks is recorded in the output so players can
+/// remove it. Tracks added to the input while transcoding are dropped
+pub struct Transcoder {
+	input: Format,
+	output: Muxer,
+	tracks: Vec<Option<OutputTrack>>,
+	packets: Vec<Packet>,
+	finished: bool
+}
+
+#[asynchronous]
+impl Transcoder {
+	/// Transcode `input` into `output`. The action for each track is
+	/// at its index in `actions`, and tracks without one are dropped
+	pub fn new(mut input: Format, mut output: Muxer, actions: Vec<TrackAction>) -> Result<Self> {
+		let mut tracks = Vec::new();
+		let mut actions = actions.into_iter();
+
+		for track in &mut input.tracks {
+			let action = actions.next().unwrap_or(TrackAction::Drop);
+			let output_track = match action {
+				TrackAction::Drop => None,
+				TrackAction::Copy => {
+					let index = output.add_track(&track.codec_params, track.time_base)?;
+
+					Some(OutputTrack::Copy(index))
+				}
+
+				TrackAction::Encode(mut params) => {
+					let mut reencoder = Reencoder::new(track, &mut params)?;
+
+					reencoder.output_index = output.add_track(&params, reencoder.time_base)?;
+
+					Some(OutputTrack::Encode(Box::new(reencoder)))
+				}
+			};
+
+			if output_track.is_none() {
+				track.discard = Discard::All;
+			}
+
+			tracks.push(output_track);
+		}
+
+		if actions.next().is_some() {
+			return Err(FormatError::TrackNotFound.into());
+		}
+
+		Ok(Self {
+			input,
+			output,
+			tracks,
+			packets: Vec::new(),
+			finished: false
+		})
+	}
+
+	#[must_use]
+	pub const fn input(&self) -> &Format {
+		&self.input
+	}
+
+	async fn write_packets(&mut self) -> Result<()> {
+		for packet in self.packets.drain(..) {
+			self.output.write_packet(&packet).await?;
+		}
+
+		Ok(())
+	}
+
+	fn handle_packet(&mut self, mut packet: Packet) -> Result<()> {
+		match self.tracks.get_mut(packet.track_index as usize) {
+			Some(Some(OutputTrack::Copy(index))) => {
+				packet.track_index = *index;
+
+				self.packets.push(packet);
+			}
+
+			Some(Some(OutputTrack::Encode(reencoder))) => {
+				reencoder.send_packet(&packet, &mut self.packets)?;
+			}
+
+			_ => ()
+		}
+
+		Ok(())
+	}
+
+	fn handle_event(&mut self, event: FormatEvent) -> Result<()> {
+		match event {
+			FormatEvent::TrackAdded(index) => {
+				self.input.tracks[index as usize].discard = Discard::All;
+				self.tracks.push(None);
+			}
+
+			FormatEvent::CodecParamsChanged(index) => {
+				if let Some(Some(OutputTrack::Encode(reencoder))) =
+					self.tracks.get_mut(index as usize)
+				{
+					let params = &mut self.input.tracks[index as usize].codec_params;
+
+					reencoder.reopen_decoder(params, &mut self.packets)?;
+				}
+			}
+
+			FormatEvent::MetadataUpdated(_) => ()
+		}
+
+		Ok(())
+	}
+
+	/// Process the next packet of the input. Returns `false` once
+	/// the input has ended and every track is flushed
+	pub async fn step(&mut self) -> Result<bool> {
+		if self.finished {
+			return Ok(false);
+		}
+
+		match self.input.read().await? {
+			Some(FormatItem::Packet(packet)) => self.handle_packet(packet)?,
+			Some(FormatItem::Event(event)) => self.handle_event(event)?,
+			None => {
+				for track in self.tracks.iter_mut().flatten() {
+					if let OutputTrack::Encode(reencoder) = track {
+						reencoder.finish(&mut self.packets)?;
+					}
+				}
+
+				self.finished = true;
+			}
+		}
+
+		self.write_packets().await?;
+
+		Ok(!self.finished)
+	}
+
+	/// Write the trailer of the output, returning its sink. Any input
+	/// not yet processed is left out
+	pub async fn finish(self) -> Result<Sink> {
+		self.output.finish().await
+	}
+
+	/// Transcode the whole input, returning the sink of the output
+	pub async fn run(mut self) -> Result<Sink> {
+		while self.step().await? {}
+
+		self.finish().await
+	}
+}
@@ -0,0 +1,539 @@
+use std::collections::VecDeque;
+
+use super::*;
+
+/// Drops decoded data before a [`SeekTarget`], so that the first
+/// frame after an accurate seek starts exactly at the requested time
+pub struct FrameTrimmer {
+	target: SeekTarget,
+
+	/// Audio samples left to drop, known once the first frame
+	/// with a timestamp arrives
+	remaining: Option<u64>
+}
+
+impl FrameTrimmer {
+	#[must_use]
+	pub const fn new(target: SeekTarget) -> Self {
+		Self { target, remaining: None }
+	}
+
+	#[must_use]
+	pub const fn target(&self) -> &SeekTarget {
+		&self.target
+	}
+
+	/// Returns `true` once the target is reached, after which
+	/// frames pass through untouched
+	#[must_use]
+	pub const fn done(&self) -> bool {
+		matches!(self.remaining, Some(0))
+	}
+
+	/// Trim the start of `frame` to the target. Returns `false` if the
+	/// whole frame comes before the target and should be dropped
+	///
+	/// Frames without a time base are assumed to be in the time base
+	/// of the target's track, as decoders produce
+	pub fn trim(&mut self, frame: &mut Frame) -> Result<bool> {
+		if self.done() {
+			return Ok(true);
+		}
+
+		if frame.time_base.num == 0 {
+			frame.time_base = self.target.time_base;
+		}
+
+		if frame.samples == 0 {
+			return Ok(self.trim_picture(frame));
+		}
+
+		let remaining = match self.remaining {
+			Some(remaining) => remaining,
+			None => {
+				let Some(start) = self.start_of(frame) else {
+					/* nothing to measure from */
+					self.remaining = Some(0);
+
+					return Ok(true);
+				};
+
+				#[allow(clippy::cast_sign_loss)]
+				let ahead = self.target.timestamp.saturating_sub(start).max(0) as u64;
+
+				*self.remaining.insert(
+					Rational::inverse(frame.sample_rate).rescale(ahead, self.target.time_base)
+				)
+			}
+		};
+
+		let samples = u64::from(frame.samples);
+
+		if remaining >= samples {
+			#[allow(clippy::arithmetic_side_effects)]
+			(self.remaining = Some(remaining - samples));
+
+			return Ok(false);
+		}
+
+		#[allow(clippy::cast_possible_truncation)]
+		frame.skip_samples(remaining as u32)?;
+
+		self.remaining = Some(0);
+
+		Ok(true)
+	}
+
+	/// The presentation time of `frame`, in the target's time base
+	fn start_of(&self, frame: &Frame) -> Option<i64> {
+		if frame.best_effort_timestamp == UNKNOWN_TIMESTAMP {
+			return None;
+		}
+
+		Some(
+			self.target
+				.time_base
+				.rescale(frame.best_effort_timestamp, frame.time_base)
+		)
+	}
+
+	fn trim_picture(&mut self, frame: &Frame) -> bool {
+		if frame.best_effort_timestamp == UNKNOWN_TIMESTAMP {
+			return true;
+		}
+
+		let start = self
+			.target
+			.time_base
+			.rescale(frame.best_effort_timestamp, frame.time_base);
+
+		#[allow(clippy::cast_possible_wrap)]
+		let end = self
+			.target
+			.time_base
+			.rescale(frame.duration as i64, frame.time_base)
+			.saturating_add(start);
+
+		/* a picture is kept if it is still showing at the target */
+		if end <= self.target.timestamp && start < self.target.timestamp {
+			return false;
+		}
+
+		self.remaining = Some(0);
+
+		true
+	}
+}
+
+/// Removes encoder delay and padding from decoded audio, so that
+/// consecutive tracks play without silence or clicks between them
+///
+/// Frames are held back until enough samples follow them to cover
+/// the padding, which is removed once [`GaplessTrimmer::finish`]
+/// marks the end of the stream
+pub struct GaplessTrimmer {
+	/// Samples left to drop from the start
+	skip: u64,
+
+	/// Samples to drop from the end, from the codec parameters
+	padding: u64,
+
+	/// Samples to drop from the end, from the last packet that had any
+	packet_padding: u64,
+
+	/// Valid samples left to output, if known
+	remaining: Option<u64>,
+
+	started: bool,
+	finished: bool,
+
+	held: VecDeque<Frame>,
+	held_samples: u64
+}
+
+impl GaplessTrimmer {
+	#[must_use]
+	pub fn new(params: &CodecParams) -> Self {
+		let to_samples = |value: u32| {
+			let value = u64::from(value);
+
+			if params.time_base.num == 0 || params.sample_rate == 0 {
+				value
+			} else {
+				Rational::inverse(params.sample_rate).rescale(value, params.time_base)
+			}
+		};
+
+		Self {
+			skip: to_samples(params.delay),
+			padding: to_samples(params.padding),
+			packet_padding: 0,
+			remaining: params.valid_samples,
+			started: false,
+			finished: false,
+			held: VecDeque::new(),
+			held_samples: 0
+		}
+	}
+
+	/// Note the trimming carried by `packet`. Call before pushing
+	/// the frames decoded from it
+	pub fn push_packet(&mut self, packet: &Packet) {
+		let trim_start = u64::from(packet.trim_start);
+
+		/* the codec delay and the first packet may describe the same priming */
+		self.skip = if self.started {
+			self.skip.saturating_add(trim_start)
+		} else {
+			self.skip.max(trim_start)
+		};
+
+		if packet.trim_end != 0 {
+			self.packet_padding = packet.trim_end.into();
+		}
+	}
+
+	/// Add a decoded frame
+	#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+	pub fn push(&mut self, mut frame: Frame) -> Result<()> {
+		self.started = true;
+
+		let samples = u64::from(frame.samples);
+
+		if self.skip >= samples {
+			self.skip -= samples;
+
+			return Ok(());
+		}
+
+		if self.skip != 0 {
+			frame.skip_samples(self.skip as u32)?;
+			self.skip = 0;
+		}
+
+		if let Some(remaining) = &mut self.remaining {
+			if *remaining < u64::from(frame.samples) {
+				frame.truncate_samples(*remaining as u32);
+			}
+
+			*remaining -= u64::from(frame.samples);
+		}
+
+		if frame.samples == 0 {
+			return Ok(());
+		}
+
+		self.held_samples += u64::from(frame.samples);
+		self.held.push_back(frame);
+
+		Ok(())
+	}
+
+	/// Samples that may still need to be dropped from the end
+	fn tail(&self) -> u64 {
+		/* an exact sample count already excludes the padding */
+		if self.remaining.is_some() {
+			0
+		} else {
+			self.padding.max(self.packet_padding)
+		}
+	}
+
+	/// The next frame ready to play
+	pub fn pop(&mut self) -> Option<Frame> {
+		let samples = u64::from(self.held.front()?.samples);
+
+		#[allow(clippy::arithmetic_side_effects)]
+		let after = self.held_samples - samples;
+
+		/* keep the last frame, as the final packet may carry padding */
+		if !self.finished && (self.held.len() < 2 || after < self.tail()) {
+			return None;
+		}
+
+		self.held_samples = after;
+		self.held.pop_front()
+	}
+
+	#[must_use]
+	pub const fn is_finished(&self) -> bool {
+		self.finished
+	}
+
+	/// Mark the end of the stream, dropping the padding from
+	/// the frames still held
+	#[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+	pub fn finish(&mut self) {
+		if self.finished {
+			return;
+		}
+
+		self.finished = true;
+
+		let mut tail = self.tail();
+
+		while tail != 0 {
+			let Some(frame) = self.held.back_mut() else {
+				break;
+			};
+
+			let samples = u64::from(frame.samples);
+
+			if samples <= tail {
+				tail -= samples;
+				self.held_samples -= samples;
+				self.held.pop_back();
+			} else {
+				frame.truncate_samples((samples - tail) as u32);
+				self.held_samples -= tail;
+				tail = 0;
+			}
+		}
+	}
+
+	/// Forget the position in the stream after a seek. The start is no
+	/// longer trimmed, but the padding still is
+	pub fn reset(&mut self) {
+		self.skip = 0;
+		self.packet_padding = 0;
+		self.remaining = None;
+		self.started = true;
+		self.finished = false;
+		self.held.clear();
+		self.held_samples = 0;
+	}
+}
+
+#[cfg(test)]
+#[allow(
+	clippy::unwrap_used,
+	clippy::arithmetic_side_effects,
+	clippy::cast_precision_loss,
+	clippy::cast_possible_truncation,
+	clippy::cast_sign_loss
+)]
+mod tests {
+	use super::*;
+
+	const RATE: u32 = 1000;
+
+	/// A mono frame of `samples` samples starting at `start`, where
+	/// each sample holds its own position
+	fn audio(start: i64, samples: u32) -> Frame {
+		let values: Vec<f32> = (0..samples)
+			.map(|index| (start + i64::from(index)) as f32)
+			.collect();
+
+		Frame::from_samples(
+			&[&values],
+			&ChannelLayout::LAYOUT_MONO,
+			RATE,
+			SampleFormat::F32,
+			start
+		)
+		.unwrap()
+	}
+
+	fn first_sample(frame: &Frame) -> f32 {
+		frame.sample_plane::<f32>(0).unwrap()[0]
+	}
+
+	fn seek_target(timestamp: i64, time_base: Rational) -> SeekTarget {
+		SeekTarget { track_index: 0, timestamp, time_base }
+	}
+
+	#[test]
+	fn trim_audio_to_target() {
+		let mut trimmer = FrameTrimmer::new(seek_target(250, Rational::millis()));
+
+		assert!(!trimmer.trim(&mut audio(0, 100)).unwrap());
+		assert!(!trimmer.trim(&mut audio(100, 100)).unwrap());
+
+		let mut frame = audio(200, 100);
+
+		assert!(trimmer.trim(&mut frame).unwrap());
+		assert!(trimmer.done());
+		assert_eq!(frame.samples, 50);
+		assert_eq!(frame.presentation_timestamp, 250);
+		assert!((first_sample(&frame) - 250.0).abs() < f32::EPSILON);
+
+		/* frames after the target pass untouched */
+		let mut frame = audio(300, 100);
+
+		assert!(trimmer.trim(&mut frame).unwrap());
+		assert_eq!(frame.samples, 100);
+	}
+
+	#[test]
+	fn trim_audio_in_other_time_base() {
+		/* 0.35 seconds, with frames timed in samples */
+		let mut trimmer = FrameTrimmer::new(seek_target(7, Rational::new(1, 20)));
+
+		assert!(!trimmer.trim(&mut audio(0, 300)).unwrap());
+
+		let mut frame = audio(300, 300);
+
+		assert!(trimmer.trim(&mut frame).unwrap());
+		assert_eq!(frame.samples, 250);
+		assert!((first_sample(&frame) - 350.0).abs() < f32::EPSILON);
+	}
+
+	#[test]
+	fn trim_audio_before_first_frame() {
+		let mut trimmer = FrameTrimmer::new(seek_target(50, Rational::millis()));
+		let mut frame = audio(100, 100);
+
+		assert!(trimmer.trim(&mut frame).unwrap());
+		assert_eq!(frame.samples, 100);
+		assert!(trimmer.done());
+	}
+
+	#[test]
+	fn trim_audio_without_timestamps() {
+		let mut trimmer = FrameTrimmer::new(seek_target(250, Rational::millis()));
+		let mut frame = audio(0, 100);
+
+		frame.best_effort_timestamp = UNKNOWN_TIMESTAMP;
+
+		assert!(trimmer.trim(&mut frame).unwrap());
+		assert_eq!(frame.samples, 100);
+		assert!(trimmer.done());
+	}
+
+	#[test]
+	fn trim_pictures() {
+		let mut trimmer = FrameTrimmer::new(seek_target(100, Rational::millis()));
+		let mut picture = |timestamp: i64| {
+			let mut frame = Frame::new();
+
+			/* no time base, so the target's is assumed */
+			frame.best_effort_timestamp = timestamp;
+			frame.duration = 40;
+			trimmer.trim(&mut frame).unwrap()
+		};
+
+		assert!(!picture(0));
+		assert!(!picture(40));
+
+		/* still showing at the target */
+		assert!(picture(80));
+		assert!(picture(120));
+	}
+
+	fn gapless_params(delay: u32, padding: u32, valid_samples: Option<u64>) -> CodecParams {
+		let mut params = CodecParams::default();
+
+		params.sample_rate = RATE;
+		params.time_base = Rational::inverse(RATE);
+		params.delay = delay;
+		params.padding = padding;
+		params.valid_samples = valid_samples;
+		params
+	}
+
+	fn drain(trimmer: &mut GaplessTrimmer, output: &mut Vec<f32>) {
+		while let Some(frame) = trimmer.pop() {
+			output.extend_from_slice(frame.sample_plane::<f32>(0).unwrap());
+		}
+	}
+
+	/// Play `frames` frames of 100 samples, with `trims` as the start
+	/// and end trims of the packet of each frame
+	fn play(trimmer: &mut GaplessTrimmer, frames: i64, trims: &[(u32, u32)]) -> Vec<f32> {
+		let mut output = Vec::new();
+
+		for index in 0..frames {
+			let mut packet = Packet::new();
+
+			if let Some((trim_start, trim_end)) = trims.get(index as usize) {
+				packet.trim_start = *trim_start;
+				packet.trim_end = *trim_end;
+			}
+
+			trimmer.push_packet(&packet);
+			trimmer.push(audio(index * 100, 100)).unwrap();
+
+			drain(trimmer, &mut output);
+		}
+
+		trimmer.finish();
+
+		drain(trimmer, &mut output);
+		output
+	}
+
+	fn assert_range(output: &[f32], start: u32, end: u32) {
+		let expected: Vec<f32> = (start..end).map(|value| value as f32).collect();
+
+		assert_eq!(output, expected);
+	}
+
+	#[test]
+	fn gapless_delay_and_padding() {
+		let mut trimmer = GaplessTrimmer::new(&gapless_params(150, 70, None));
+
+		assert_range(&play(&mut trimmer, 5, &[]), 150, 430);
+	}
+
+	#[test]
+	fn gapless_valid_samples() {
+		let mut trimmer = GaplessTrimmer::new(&gapless_params(150, 70, Some(200)));
+
+		assert_range(&play(&mut trimmer, 5, &[]), 150, 350);
+	}
+
+	#[test]
+	fn gapless_padding_in_other_time_base() {
+		/* 15 and 7 hundredths of a second */
+		let mut params = gapless_params(15, 7, None);
+
+		params.time_base = Rational::new(1, 100);
+
+		let mut trimmer = GaplessTrimmer::new(&params);
+
+		assert_range(&play(&mut trimmer, 5, &[]), 150, 430);
+	}
+
+	#[test]
+	fn gapless_packet_trims() {
+		let mut trimmer = GaplessTrimmer::new(&gapless_params(0, 0, None));
+		let trims = [(30, 0), (0, 0), (0, 0), (0, 120)];
+
+		/* the end trim covers the last frame and part of the one before */
+		assert_range(&play(&mut trimmer, 4, &trims), 30, 280);
+	}
+
+	#[test]
+	fn gapless_priming_counted_once() {
+		let mut trimmer = GaplessTrimmer::new(&gapless_params(100, 0, None));
+
+		assert_range(&play(&mut trimmer, 3, &[(100, 0)]), 100, 300);
+	}
+
+	#[test]
+	fn gapless_holds_padding() {
+		let mut trimmer = GaplessTrimmer::new(&gapless_params(0, 150, None));
+
+		trimmer.push(audio(0, 100)).unwrap();
+		assert!(trimmer.pop().is_none());
+
+		trimmer.push(audio(100, 100)).unwrap();
+		assert!(trimmer.pop().is_none());
+
+		/* enough samples follow the first frame to cover the padding */
+		trimmer.push(audio(200, 100)).unwrap();
+		assert_eq!(trimmer.pop().unwrap().presentation_timestamp, 0);
+		assert!(trimmer.pop().is_none());
+	}
+
+	#[test]
+	fn gapless_reset() {
+		let mut trimmer = GaplessTrimmer::new(&gapless_params(150, 70, None));
+
+		trimmer.push(audio(0, 100)).unwrap();
+		trimmer.reset();
+
+		/* the start is no longer trimmed after a seek */
+		assert_range(&play(&mut trimmer, 3, &[]), 0, 230);
+	}
+}